@@ -0,0 +1,145 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use unitypack::asset_bundle::read_unity_asset_bundle;
+#[cfg(feature = "lzma")]
+use unitypack::asset_bundle::{read_unity_asset_bundle_with_options, ParseOptions};
+use unitypack::compression::{compress, CompressionType};
+#[cfg(feature = "lzma")]
+use unitypack::compression::{Decompressor, DefaultDecompressor};
+#[cfg(feature = "lzma")]
+use unitypack::error::UnityPackError;
+
+// counts heap allocations, so the benches can report how many a read makes
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[cfg(feature = "lzma")]
+fn count_allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+// only implements `decompress`, so every block goes through a Vec of its own and is then
+// copied into the output, as before `decompress_into`
+#[cfg(feature = "lzma")]
+struct PerBlockDecompressor;
+
+#[cfg(feature = "lzma")]
+impl Decompressor for PerBlockDecompressor {
+    fn decompress(
+        &self,
+        input: &[u8],
+        out_size: usize,
+        kind: CompressionType,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        DefaultDecompressor.decompress(input, out_size, kind)
+    }
+}
+
+// 32 blocks of 128KiB behind an uncompressed blocks info
+fn build_multi_block_bundle(compression: CompressionType) -> Vec<u8> {
+    let data = (0..32 * 0x20000u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8 & 0x0F)
+        .collect::<Vec<_>>();
+    let chunks = data.chunks(0x20000).collect::<Vec<_>>();
+
+    let mut blocks = vec![];
+    let mut block_info = vec![0u8; 16];
+    block_info.extend_from_slice(&(chunks.len() as i32).to_be_bytes());
+    for chunk in chunks {
+        let block = compress(chunk, compression).unwrap();
+        block_info.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        block_info.extend_from_slice(&(block.len() as u32).to_be_bytes());
+        block_info.extend_from_slice(&(compression.to_flags() as u16).to_be_bytes());
+        blocks.extend_from_slice(&block);
+    }
+    block_info.extend_from_slice(&1i32.to_be_bytes());
+    block_info.extend_from_slice(&0i64.to_be_bytes());
+    block_info.extend_from_slice(&(data.len() as i64).to_be_bytes());
+    block_info.extend_from_slice(&4u32.to_be_bytes());
+    block_info.extend_from_slice(b"CAB-bench\0");
+
+    let mut bundle = b"UnityFS\0".to_vec();
+    bundle.extend_from_slice(&6u32.to_be_bytes());
+    bundle.extend_from_slice(b"5.x.x\0");
+    bundle.extend_from_slice(b"2019.4.1f1\0");
+    let size = bundle.len() + 20 + block_info.len() + blocks.len();
+    bundle.extend_from_slice(&(size as i64).to_be_bytes());
+    bundle.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+    bundle.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+    bundle.extend_from_slice(&0x40u32.to_be_bytes());
+    bundle.extend_from_slice(&block_info);
+    bundle.extend_from_slice(&blocks);
+    bundle
+}
+
+fn bench_read_multi_block_bundle(c: &mut Criterion) {
+    let bundle = build_multi_block_bundle(CompressionType::Lz4);
+    c.bench_function("read 32 block bundle", |b| {
+        b.iter(|| read_unity_asset_bundle(black_box(&bundle)).unwrap())
+    });
+}
+
+// every block is decoded straight into the shared output buffer
+#[cfg(feature = "lzma")]
+fn bench_read_multi_block_lzma_bundle(c: &mut Criterion) {
+    let bundle = build_multi_block_bundle(CompressionType::Lzma);
+    let per_block = ParseOptions {
+        decompressor: &PerBlockDecompressor,
+        ..Default::default()
+    };
+    println!(
+        "read 32 block LZMA bundle: {} allocations, {} with a Vec per block",
+        count_allocations(|| read_unity_asset_bundle(&bundle).unwrap()),
+        count_allocations(|| read_unity_asset_bundle_with_options(&bundle, &per_block).unwrap()),
+    );
+    c.bench_function("read 32 block LZMA bundle", |b| {
+        b.iter(|| read_unity_asset_bundle(black_box(&bundle)).unwrap())
+    });
+    c.bench_function("read 32 block LZMA bundle, a Vec per block", |b| {
+        b.iter(|| read_unity_asset_bundle_with_options(black_box(&bundle), &per_block).unwrap())
+    });
+}
+
+#[cfg(not(feature = "lzma"))]
+fn bench_read_multi_block_lzma_bundle(_c: &mut Criterion) {}
+
+fn bench_read_sample_bundle(c: &mut Criterion) {
+    let bundle = std::fs::read("./item_icon_00000").unwrap();
+    c.bench_function("read item_icon_00000", |b| {
+        b.iter(|| read_unity_asset_bundle(black_box(&bundle)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_read_multi_block_bundle,
+    bench_read_multi_block_lzma_bundle,
+    bench_read_sample_bundle
+);
+criterion_main!(benches);
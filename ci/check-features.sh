@@ -0,0 +1,16 @@
+#!/bin/sh
+# Builds and tests the crate without the default codecs and with each codec alone, so
+# tests reading the LZ4HC sample bundles stay gated on the feature they need.
+set -eu
+
+cd "$(dirname "$0")/.."
+
+for features in "" lz4 lzma "lzma encoding json manifest mmap bytes lru rayon png sha2 texture2ddecoder"; do
+    echo "== --no-default-features --features \"$features\""
+    cargo clippy --no-default-features --features "$features" --all-targets -- -D warnings
+    cargo test --no-default-features --features "$features"
+done
+
+echo "== --all-features"
+cargo clippy --all-features --all-targets -- -D warnings
+cargo test --all-features
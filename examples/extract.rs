@@ -0,0 +1,66 @@
+// cargo run --example extract -- <bundle> <output dir>
+use std::{env, path::Path, process};
+
+use unitypack::error::UnityPackError;
+use unitypack::lazy_bundle::read_lazy_unity_asset_bundle;
+
+fn extract(bundle: &Path, dir: &Path) -> Result<usize, UnityPackError> {
+    let file = std::fs::read(bundle)?;
+    let (_, bundle) = read_lazy_unity_asset_bundle(&file)?;
+    bundle.extract_all(dir)?;
+    Ok(bundle
+        .directory_info
+        .iter()
+        .filter(|node| !node.is_directory() && !node.is_deleted())
+        .count())
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() != 3 {
+        eprintln!("usage: {} <bundle> <output dir>", args[0]);
+        process::exit(2);
+    }
+    match extract(Path::new(&args[1]), Path::new(&args[2])) {
+        Ok(count) => println!("extracted {} files to {}", count, args[2]),
+        Err(e) => {
+            eprintln!("{}: {}", args[1], e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use unitypack::asset_bundle::read_unity_asset_bundle;
+    use unitypack::error::UnityPackError;
+
+    use crate::extract;
+
+    #[test]
+    fn test_extract() {
+        let file = std::fs::read("./item_icon_00000").unwrap();
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let dir = std::env::temp_dir().join("unitypack_example_extract");
+        assert_eq!(2, extract(Path::new("./item_icon_00000"), &dir).unwrap());
+        for stream_file in unity_asset_bundle.stream_files.iter() {
+            assert_eq!(
+                stream_file.body,
+                std::fs::read(dir.join(&stream_file.path)).unwrap()
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            extract(Path::new("./does_not_exist"), &dir),
+            Err(UnityPackError::Io(_))
+        ));
+        assert!(matches!(
+            extract(Path::new("./Cargo.toml"), &dir),
+            Err(UnityPackError::Parse { .. })
+        ));
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,1321 @@
+use nom::{
+    bytes::complete::take,
+    error::{ErrorKind, ParseError},
+    number::complete::{be_i64, be_u32, be_u8, i16, i32, i64, u16, u32, u64, u8},
+    IResult,
+};
+
+use std::{cell::OnceCell, collections::HashMap, fmt};
+
+pub use nom::number::Endianness;
+
+use crate::error::UnityPackError;
+use crate::{bounded_count, read_cstring, MAX_VERSION_STRING_LENGTH};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SerializedFileHeader {
+    pub metadata_size: u32,
+    pub file_size: u64,
+    pub version: u32,
+    pub data_offset: u64,
+    pub endianness: u8,
+}
+
+// which relation between the header's sizes and offsets does not hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderInconsistency {
+    DataOffsetPastFileSize { data_offset: u64, file_size: u64 },
+    MetadataPastDataOffset { metadata_end: u64, data_offset: u64 },
+}
+
+impl fmt::Display for HeaderInconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderInconsistency::DataOffsetPastFileSize {
+                data_offset,
+                file_size,
+            } => write!(
+                f,
+                "data offset {} is past the file size {}",
+                data_offset, file_size
+            ),
+            HeaderInconsistency::MetadataPastDataOffset {
+                metadata_end,
+                data_offset,
+            } => write!(
+                f,
+                "metadata ends at {}, past the data offset {}",
+                metadata_end, data_offset
+            ),
+        }
+    }
+}
+
+impl SerializedFileHeader {
+    // the fixed part in front of the metadata, which grew 64-bit fields in version 22
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        if self.version >= 22 {
+            48
+        } else {
+            20
+        }
+    }
+
+    // object offsets are relative to data_offset, so a header breaking these would send
+    // object reads outside the file
+    pub fn check_consistency(&self) -> Result<(), HeaderInconsistency> {
+        if self.data_offset > self.file_size {
+            return Err(HeaderInconsistency::DataOffsetPastFileSize {
+                data_offset: self.data_offset,
+                file_size: self.file_size,
+            });
+        }
+        let metadata_end = self.size() + self.metadata_size as u64;
+        if metadata_end > self.data_offset {
+            return Err(HeaderInconsistency::MetadataPastDataOffset {
+                metadata_end,
+                data_offset: self.data_offset,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TypeTreeNode {
+    pub version: u16,
+    pub level: u8,
+    pub type_flags: u8,
+    pub type_str_offset: u32,
+    pub name_str_offset: u32,
+    pub byte_size: i32,
+    pub index: i32,
+    pub meta_flag: u32,
+    pub ref_type_hash: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TypeTree {
+    pub nodes: Vec<TypeTreeNode>,
+    pub string_buffer: Vec<u8>,
+}
+
+// Unity's CommonString table; type tree string offsets with the top bit set point into it
+// instead of into the tree's own string buffer
+const COMMON_STRINGS: &str = concat!(
+    "AABB\0",
+    "AnimationClip\0",
+    "AnimationCurve\0",
+    "AnimationState\0",
+    "Array\0",
+    "Base\0",
+    "BitField\0",
+    "bitset\0",
+    "bool\0",
+    "char\0",
+    "ColorRGBA\0",
+    "Component\0",
+    "data\0",
+    "deque\0",
+    "double\0",
+    "dynamic_array\0",
+    "FastPropertyName\0",
+    "first\0",
+    "float\0",
+    "Font\0",
+    "GameObject\0",
+    "Generic Mono\0",
+    "GradientNEW\0",
+    "GUID\0",
+    "GUIStyle\0",
+    "int\0",
+    "list\0",
+    "long long\0",
+    "map\0",
+    "Matrix4x4f\0",
+    "MdFour\0",
+    "MonoBehaviour\0",
+    "MonoScript\0",
+    "m_ByteSize\0",
+    "m_Curve\0",
+    "m_EditorClassIdentifier\0",
+    "m_EditorHideFlags\0",
+    "m_Enabled\0",
+    "m_ExtensionPtr\0",
+    "m_GameObject\0",
+    "m_Index\0",
+    "m_IsArray\0",
+    "m_IsStatic\0",
+    "m_MetaFlag\0",
+    "m_Name\0",
+    "m_ObjectHideFlags\0",
+    "m_PrefabInternal\0",
+    "m_PrefabParentObject\0",
+    "m_Script\0",
+    "m_StaticEditorFlags\0",
+    "m_Type\0",
+    "m_Version\0",
+    "Object\0",
+    "pair\0",
+    "PPtr<Component>\0",
+    "PPtr<GameObject>\0",
+    "PPtr<Material>\0",
+    "PPtr<MonoBehaviour>\0",
+    "PPtr<MonoScript>\0",
+    "PPtr<Object>\0",
+    "PPtr<Prefab>\0",
+    "PPtr<Sprite>\0",
+    "PPtr<TextAsset>\0",
+    "PPtr<Texture>\0",
+    "PPtr<Texture2D>\0",
+    "PPtr<Transform>\0",
+    "Prefab\0",
+    "Quaternionf\0",
+    "Rectf\0",
+    "RectInt\0",
+    "RectOffset\0",
+    "second\0",
+    "set\0",
+    "short\0",
+    "size\0",
+    "SInt16\0",
+    "SInt32\0",
+    "SInt64\0",
+    "SInt8\0",
+    "staticvector\0",
+    "string\0",
+    "TextAsset\0",
+    "TextMesh\0",
+    "Texture\0",
+    "Texture2D\0",
+    "Transform\0",
+    "TypelessData\0",
+    "UInt16\0",
+    "UInt32\0",
+    "UInt64\0",
+    "UInt8\0",
+    "unsigned int\0",
+    "unsigned long long\0",
+    "unsigned short\0",
+    "vector\0",
+    "Vector2f\0",
+    "Vector3f\0",
+    "Vector4f\0",
+    "m_ScriptingClassIdentifier\0",
+    "Gradient\0",
+    "Type*\0",
+    "int2_storage\0",
+    "int3_storage\0",
+    "BoundsInt\0",
+    "m_CorrespondingSourceObject\0",
+    "m_PrefabInstance\0",
+    "m_PrefabAsset\0",
+    "FileSize\0",
+    "Hash128\0",
+);
+
+impl TypeTree {
+    #[must_use]
+    pub fn node_type(&self, node: &TypeTreeNode) -> Option<&str> {
+        self.string_at(node.type_str_offset)
+    }
+
+    #[must_use]
+    pub fn node_name(&self, node: &TypeTreeNode) -> Option<&str> {
+        self.string_at(node.name_str_offset)
+    }
+
+    fn string_at(&self, offset: u32) -> Option<&str> {
+        let (buffer, offset) = if offset & 0x8000_0000 != 0 {
+            (COMMON_STRINGS.as_bytes(), offset & 0x7FFF_FFFF)
+        } else {
+            (&self.string_buffer[..], offset)
+        };
+        let rest = buffer.get(offset as usize..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&rest[..end]).ok()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SerializedType {
+    pub class_id: i32,
+    pub is_stripped_type: bool,
+    pub script_type_index: i16,
+    pub script_id: Option<[u8; 16]>,
+    pub old_type_hash: [u8; 16],
+    pub type_tree: Option<TypeTree>,
+    pub type_dependencies: Vec<i32>,
+    // only set on m_RefTypes entries, which name their managed type instead of
+    // listing dependencies
+    pub type_reference: Option<SerializedTypeReference>,
+}
+
+// the managed type behind a [SerializeReference] field
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SerializedTypeReference {
+    pub class_name: String,
+    pub namespace: String,
+    pub assembly_name: String,
+}
+
+// m_Externals entry naming another serialized file that PPtrs may point into
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileIdentifier {
+    pub guid: [u8; 16],
+    pub file_type: i32,
+    pub path: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectInfo {
+    pub path_id: i64,
+    // absolute offset within the serialized file
+    pub byte_start: u64,
+    pub byte_size: u32,
+    pub type_id: i32,
+    pub class_id: i32,
+}
+
+// m_ScriptTypes entry pointing at a MonoScript, possibly in an external file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalSerializedObjectIdentifier {
+    pub local_serialized_file_index: i32,
+    pub local_identifier_in_file: i64,
+}
+
+// BuildTarget values of m_TargetPlatform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types)]
+pub enum Platform {
+    NoTarget = -2,
+    StandaloneOSX = 2,
+    StandaloneWindows = 5,
+    iOS = 9,
+    Android = 13,
+    StandaloneLinux = 17,
+    StandaloneWindows64 = 19,
+    WebGL = 20,
+    WSAPlayer = 21,
+    StandaloneLinux64 = 24,
+    StandaloneLinuxUniversal = 25,
+    PS4 = 31,
+    XboxOne = 33,
+    tvOS = 37,
+    Switch = 38,
+    Stadia = 40,
+    GameCoreXboxSeries = 42,
+    GameCoreXboxOne = 43,
+    PS5 = 44,
+}
+
+impl Platform {
+    #[must_use]
+    pub fn from_i32(value: i32) -> Option<Platform> {
+        use Platform::*;
+        let platform = match value {
+            -2 => NoTarget,
+            2 => StandaloneOSX,
+            5 => StandaloneWindows,
+            9 => iOS,
+            13 => Android,
+            17 => StandaloneLinux,
+            19 => StandaloneWindows64,
+            20 => WebGL,
+            21 => WSAPlayer,
+            24 => StandaloneLinux64,
+            25 => StandaloneLinuxUniversal,
+            31 => PS4,
+            33 => XboxOne,
+            37 => tvOS,
+            38 => Switch,
+            40 => Stadia,
+            42 => GameCoreXboxSeries,
+            43 => GameCoreXboxOne,
+            44 => PS5,
+            _ => return None,
+        };
+        Some(platform)
+    }
+}
+
+#[derive(Debug)]
+pub struct SerializedFile<'a> {
+    pub header: SerializedFileHeader,
+    pub unity_version: String,
+    pub target_platform: i32,
+    pub enable_type_tree: bool,
+    pub types: Vec<SerializedType>,
+    pub objects: Vec<ObjectInfo>,
+    pub script_types: Vec<LocalSerializedObjectIdentifier>,
+    pub externals: Vec<FileIdentifier>,
+    pub ref_types: Vec<SerializedType>,
+    data: &'a [u8],
+    // path id -> index into `objects`, built on the first lookup
+    path_id_index: OnceCell<HashMap<i64, usize>>,
+}
+
+impl<'a> SerializedFile<'a> {
+    #[must_use]
+    pub fn unity_version(&self) -> &str {
+        &self.unity_version
+    }
+
+    // `None` for build targets this crate does not know; the raw value is in `target_platform`
+    #[must_use]
+    pub fn target_platform(&self) -> Option<Platform> {
+        Platform::from_i32(self.target_platform)
+    }
+
+    #[must_use]
+    pub fn endianness(&self) -> Endianness {
+        if self.header.endianness == 0 {
+            Endianness::Little
+        } else {
+            Endianness::Big
+        }
+    }
+
+    #[must_use]
+    pub fn object_data(&self, object: &ObjectInfo) -> Option<&'a [u8]> {
+        use std::convert::TryFrom;
+        let start = usize::try_from(object.byte_start).ok()?;
+        self.data
+            .get(start..start.checked_add(object.byte_size as usize)?)
+    }
+
+    // the MonoScript behind a MonoBehaviour type
+    #[must_use]
+    pub fn script_type(
+        &self,
+        serialized_type: &SerializedType,
+    ) -> Option<&LocalSerializedObjectIdentifier> {
+        if serialized_type.script_type_index < 0 {
+            return None;
+        }
+        self.script_types
+            .get(serialized_type.script_type_index as usize)
+    }
+
+    #[must_use]
+    pub fn object_by_path_id(&self, path_id: i64) -> Option<(ObjectInfo, &'a [u8])> {
+        let index = self.path_id_index.get_or_init(|| {
+            self.objects
+                .iter()
+                .enumerate()
+                .map(|(index, object)| (object.path_id, index))
+                .collect()
+        });
+        let object = self.objects[*index.get(&path_id)?];
+        Some((object, self.object_data(&object)?))
+    }
+
+    // objects whose bytes lie outside the file are skipped
+    pub fn object_iter(&self) -> impl Iterator<Item = (ObjectInfo, &'a [u8])> + '_ {
+        self.objects
+            .iter()
+            .filter_map(move |object| Some((*object, self.object_data(object)?)))
+    }
+
+    #[must_use]
+    pub fn objects_of_class(&self, class_id: i32) -> Vec<(ObjectInfo, &'a [u8])> {
+        self.object_iter()
+            .filter(|(object, _)| object.class_id == class_id)
+            .collect()
+    }
+}
+
+// formats older than this predate the blob type tree and 64-bit path ids
+const MIN_SERIALIZED_FILE_VERSION: u32 = 14;
+
+pub fn read_serialized_file(input: &[u8]) -> Result<SerializedFile<'_>, UnityPackError> {
+    let (metadata, header) = read_serialized_file_header(input)
+        .map_err(|e| UnityPackError::from(e).locate(input.len(), 0))?;
+    if header.version < MIN_SERIALIZED_FILE_VERSION {
+        return Err(UnityPackError::UnsupportedSerializedFileVersion(
+            header.version,
+        ));
+    }
+    header
+        .check_consistency()
+        .map_err(UnityPackError::InconsistentSerializedFileHeader)?;
+    let endianness = if header.endianness == 0 {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    };
+    let (
+        _,
+        (
+            unity_version,
+            target_platform,
+            enable_type_tree,
+            types,
+            objects,
+            script_types,
+            externals,
+            ref_types,
+        ),
+    ) = read_metadata(metadata, input, &header, endianness)
+        .map_err(|e| UnityPackError::from(e).locate(input.len(), 0))?;
+
+    Ok(SerializedFile {
+        header,
+        unity_version,
+        target_platform,
+        enable_type_tree,
+        types,
+        objects,
+        script_types,
+        externals,
+        ref_types,
+        data: input,
+        path_id_index: OnceCell::new(),
+    })
+}
+
+fn read_serialized_file_header(
+    input: &[u8],
+) -> IResult<&[u8], SerializedFileHeader, UnityPackError> {
+    let (input, metadata_size) = be_u32(input)?;
+    let (input, file_size) = be_u32(input)?;
+    let (input, version) = be_u32(input)?;
+    let (input, data_offset) = be_u32(input)?;
+    let (input, endianness) = be_u8(input)?;
+    let (input, _reserved) = take(3usize)(input)?;
+    let mut header = SerializedFileHeader {
+        metadata_size,
+        file_size: file_size as u64,
+        version,
+        data_offset: data_offset as u64,
+        endianness,
+    };
+    if version < 22 {
+        return Ok((input, header));
+    }
+
+    let (input, metadata_size) = be_u32(input)?;
+    let (input, file_size) = offset64(Endianness::Big)(input)?;
+    let (input, data_offset) = offset64(Endianness::Big)(input)?;
+    let (input, _unknown) = be_i64(input)?;
+    header.metadata_size = metadata_size;
+    header.file_size = file_size;
+    header.data_offset = data_offset;
+    Ok((input, header))
+}
+
+// version 22 stores sizes and offsets as signed 64-bit values, none of which may be negative
+fn offset64(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], u64, UnityPackError> {
+    use std::convert::TryFrom;
+    move |input| {
+        let (rest, value) = i64(endianness)(input)?;
+        let value = u64::try_from(value).map_err(|_| {
+            nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Verify))
+        })?;
+        Ok((rest, value))
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn read_metadata<'a>(
+    input: &'a [u8],
+    file: &[u8],
+    header: &SerializedFileHeader,
+    endianness: Endianness,
+) -> IResult<
+    &'a [u8],
+    (
+        String,
+        i32,
+        bool,
+        Vec<SerializedType>,
+        Vec<ObjectInfo>,
+        Vec<LocalSerializedObjectIdentifier>,
+        Vec<FileIdentifier>,
+        Vec<SerializedType>,
+    ),
+    UnityPackError,
+> {
+    let version = header.version;
+    let (input, unity_version) = read_cstring(input, MAX_VERSION_STRING_LENGTH)?;
+    let (input, target_platform) = i32(endianness)(input)?;
+    let (input, enable_type_tree) = u8(input)?;
+    let enable_type_tree = enable_type_tree != 0;
+
+    let (input, type_count) = i32(endianness)(input)?;
+    let (input, types) = bounded_count(
+        |i| read_serialized_type(i, version, enable_type_tree, false, endianness),
+        type_count as usize,
+    )(input)?;
+
+    let (input, object_count) = i32(endianness)(input)?;
+    let (input, objects) = bounded_count(
+        |i| read_object_info(i, file, header, &types, endianness),
+        object_count as usize,
+    )(input)?;
+
+    let (input, script_type_count) = i32(endianness)(input)?;
+    let (input, script_types) = bounded_count(
+        |i| read_local_serialized_object_identifier(i, file, endianness),
+        script_type_count as usize,
+    )(input)?;
+
+    let (input, external_count) = i32(endianness)(input)?;
+    let (input, externals) = bounded_count(
+        |i| read_file_identifier(i, endianness),
+        external_count as usize,
+    )(input)?;
+
+    let (input, ref_types) = if version >= 20 {
+        let (input, ref_type_count) = i32(endianness)(input)?;
+        bounded_count(
+            |i| read_serialized_type(i, version, enable_type_tree, true, endianness),
+            ref_type_count as usize,
+        )(input)?
+    } else {
+        (input, vec![])
+    };
+
+    Ok((
+        input,
+        (
+            unity_version,
+            target_platform,
+            enable_type_tree,
+            types,
+            objects,
+            script_types,
+            externals,
+            ref_types,
+        ),
+    ))
+}
+
+fn read_serialized_type(
+    input: &[u8],
+    version: u32,
+    enable_type_tree: bool,
+    is_ref_type: bool,
+    endianness: Endianness,
+) -> IResult<&[u8], SerializedType, UnityPackError> {
+    let (input, class_id) = i32(endianness)(input)?;
+    let (input, is_stripped_type) = if version >= 16 {
+        let (input, stripped) = u8(input)?;
+        (input, stripped != 0)
+    } else {
+        (input, false)
+    };
+    let (input, script_type_index) = if version >= 17 {
+        i16(endianness)(input)?
+    } else {
+        (input, -1)
+    };
+
+    // MonoBehaviour types carry the hash of their script as well
+    let has_script_id = if is_ref_type {
+        script_type_index >= 0
+    } else {
+        (version < 16 && class_id < 0) || (version >= 16 && class_id == 114)
+    };
+    let (input, script_id) = if has_script_id {
+        let (input, hash) = read_hash(input)?;
+        (input, Some(hash))
+    } else {
+        (input, None)
+    };
+    let (input, old_type_hash) = read_hash(input)?;
+
+    let (input, type_tree) = if enable_type_tree {
+        let (input, type_tree) = read_type_tree(input, version, endianness)?;
+        (input, Some(type_tree))
+    } else {
+        (input, None)
+    };
+    let (input, type_dependencies, type_reference) = if version < 21 || !enable_type_tree {
+        (input, vec![], None)
+    } else if is_ref_type {
+        let (input, class_name) = read_cstring(input, MAX_TYPE_NAME_LENGTH)?;
+        let (input, namespace) = read_cstring(input, MAX_TYPE_NAME_LENGTH)?;
+        let (input, assembly_name) = read_cstring(input, MAX_TYPE_NAME_LENGTH)?;
+        let type_reference = SerializedTypeReference {
+            class_name,
+            namespace,
+            assembly_name,
+        };
+        (input, vec![], Some(type_reference))
+    } else {
+        let (input, dependency_count) = i32(endianness)(input)?;
+        let (input, type_dependencies) =
+            bounded_count(i32(endianness), dependency_count as usize)(input)?;
+        (input, type_dependencies, None)
+    };
+
+    Ok((
+        input,
+        SerializedType {
+            class_id,
+            is_stripped_type,
+            script_type_index,
+            script_id,
+            old_type_hash,
+            type_tree,
+            type_dependencies,
+            type_reference,
+        },
+    ))
+}
+
+// managed type names and external paths; generous, but keeps a corrupt string bounded
+const MAX_TYPE_NAME_LENGTH: usize = 1024;
+const MAX_EXTERNAL_PATH_LENGTH: usize = 4096;
+
+fn read_file_identifier(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], FileIdentifier, UnityPackError> {
+    let (input, _temp_empty) = read_cstring(input, MAX_EXTERNAL_PATH_LENGTH)?;
+    let (input, guid) = read_hash(input)?;
+    let (input, file_type) = i32(endianness)(input)?;
+    let (input, path) = read_cstring(input, MAX_EXTERNAL_PATH_LENGTH)?;
+    Ok((
+        input,
+        FileIdentifier {
+            guid,
+            file_type,
+            path,
+        },
+    ))
+}
+
+fn read_hash(input: &[u8]) -> IResult<&[u8], [u8; 16], UnityPackError> {
+    let (input, bytes) = take(16usize)(input)?;
+    let mut hash = [0; 16];
+    hash.copy_from_slice(bytes);
+    Ok((input, hash))
+}
+
+fn read_type_tree(
+    input: &[u8],
+    version: u32,
+    endianness: Endianness,
+) -> IResult<&[u8], TypeTree, UnityPackError> {
+    let (input, node_count) = i32(endianness)(input)?;
+    let (input, string_buffer_size) = i32(endianness)(input)?;
+    let (input, nodes) = bounded_count(
+        |i| read_type_tree_node(i, version, endianness),
+        node_count as usize,
+    )(input)?;
+    let (input, string_buffer) = take(string_buffer_size as usize)(input)?;
+    Ok((
+        input,
+        TypeTree {
+            nodes,
+            string_buffer: string_buffer.to_vec(),
+        },
+    ))
+}
+
+fn read_type_tree_node(
+    input: &[u8],
+    version: u32,
+    endianness: Endianness,
+) -> IResult<&[u8], TypeTreeNode, UnityPackError> {
+    let (input, node_version) = u16(endianness)(input)?;
+    let (input, level) = u8(input)?;
+    let (input, type_flags) = u8(input)?;
+    let (input, type_str_offset) = u32(endianness)(input)?;
+    let (input, name_str_offset) = u32(endianness)(input)?;
+    let (input, byte_size) = i32(endianness)(input)?;
+    let (input, index) = i32(endianness)(input)?;
+    let (input, meta_flag) = u32(endianness)(input)?;
+    let (input, ref_type_hash) = if version >= 19 {
+        u64(endianness)(input)?
+    } else {
+        (input, 0)
+    };
+    Ok((
+        input,
+        TypeTreeNode {
+            version: node_version,
+            level,
+            type_flags,
+            type_str_offset,
+            name_str_offset,
+            byte_size,
+            index,
+            meta_flag,
+            ref_type_hash,
+        },
+    ))
+}
+
+fn read_object_info<'a>(
+    input: &'a [u8],
+    file: &[u8],
+    header: &SerializedFileHeader,
+    types: &[SerializedType],
+    endianness: Endianness,
+) -> IResult<&'a [u8], ObjectInfo, UnityPackError> {
+    // entries are aligned to 4 bytes relative to the start of the file
+    let padding = (4 - (file.len() - input.len()) % 4) % 4;
+    let (input, _) = take(padding)(input)?;
+    let (input, path_id) = i64(endianness)(input)?;
+    let byte_start_input = input;
+    let (input, byte_start) = if header.version >= 22 {
+        offset64(endianness)(input)?
+    } else {
+        let (input, byte_start) = u32(endianness)(input)?;
+        (input, byte_start as u64)
+    };
+    let (input, byte_size) = u32(endianness)(input)?;
+    let (input, type_id) = i32(endianness)(input)?;
+    let (input, class_id) = if header.version < 16 {
+        let (input, class_id) = u16(endianness)(input)?;
+        (input, class_id as i32)
+    } else {
+        let class_id = types
+            .get(type_id as usize)
+            .map(|t| t.class_id)
+            .ok_or_else(|| {
+                nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Verify))
+            })?;
+        (input, class_id)
+    };
+    let input = if header.version < 17 {
+        let (input, _script_type_index) = i16(endianness)(input)?;
+        if header.version >= 15 {
+            let (input, _stripped) = u8(input)?;
+            input
+        } else {
+            input
+        }
+    } else {
+        input
+    };
+
+    let byte_start = header.data_offset.checked_add(byte_start).ok_or_else(|| {
+        nom::Err::Error(UnityPackError::from_error_kind(
+            byte_start_input,
+            ErrorKind::Verify,
+        ))
+    })?;
+
+    Ok((
+        input,
+        ObjectInfo {
+            path_id,
+            byte_start,
+            byte_size,
+            type_id,
+            class_id,
+        },
+    ))
+}
+
+fn read_local_serialized_object_identifier<'a>(
+    input: &'a [u8],
+    file: &[u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], LocalSerializedObjectIdentifier, UnityPackError> {
+    let (input, local_serialized_file_index) = i32(endianness)(input)?;
+    // the identifier is aligned to 4 bytes relative to the start of the file
+    let padding = (4 - (file.len() - input.len()) % 4) % 4;
+    let (input, _) = take(padding)(input)?;
+    let (input, local_identifier_in_file) = i64(endianness)(input)?;
+    Ok((
+        input,
+        LocalSerializedObjectIdentifier {
+            local_serialized_file_index,
+            local_identifier_in_file,
+        },
+    ))
+}
+
+#[must_use]
+pub fn read_asset_name(data: &[u8], endianness: Endianness) -> Option<String> {
+    read_aligned_string(data, endianness)
+        .ok()
+        .map(|(_, name)| name)
+}
+
+pub(crate) fn read_aligned_string(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], String, UnityPackError> {
+    let original_len = input.len();
+    let (input, length) = i32(endianness)(input)?;
+    let (input, bytes) = take(length as usize)(input)?;
+    let string = std::str::from_utf8(bytes)
+        .map_err(|_| nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Char)))?
+        .to_string();
+    // strings are padded to 4 bytes, the last field of an object may omit it
+    let padding = (4 - (original_len - input.len()) % 4) % 4;
+    let (input, _) = take(padding.min(input.len()))(input)?;
+    Ok((input, string))
+}
+
+#[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
+mod tests {
+    use crate::asset::{
+        read_aligned_string, read_asset_name, read_serialized_file, Endianness, FileIdentifier,
+        HeaderInconsistency, LocalSerializedObjectIdentifier, ObjectInfo, Platform, SerializedType,
+        SerializedTypeReference, TypeTreeNode,
+    };
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::error::UnityPackError;
+    use crate::test_fixtures::read_file;
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_asset_name() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the Texture2D object occupies 4312..4512 of the serialized file
+        let data = &unity_asset_bundle.stream_files[0].body[4312..4512];
+        assert_eq!(
+            Some("item_icon_00000".to_string()),
+            read_asset_name(data, Endianness::Little)
+        );
+    }
+
+    #[test]
+    fn test_read_aligned_string_skips_padding() {
+        let data = [5, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, 42];
+        let (rest, string) = read_aligned_string(&data, Endianness::Little).unwrap();
+        assert_eq!("hello", string);
+        assert_eq!(&[42], rest);
+        assert_eq!(None, read_asset_name(&data[..6], Endianness::Little));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_serialized_file() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        assert_eq!(21, serialized_file.header.version);
+        assert_eq!(4096, serialized_file.header.data_offset);
+        assert_eq!("2019.4.1f1", serialized_file.unity_version());
+        assert_eq!(19, serialized_file.target_platform);
+        assert!(serialized_file.enable_type_tree);
+        assert_eq!(
+            vec![142, 28],
+            serialized_file
+                .types
+                .iter()
+                .map(|t| t.class_id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ObjectInfo {
+                    path_id: 1,
+                    byte_start: 4096,
+                    byte_size: 212,
+                    type_id: 0,
+                    class_id: 142,
+                },
+                ObjectInfo {
+                    path_id: 7665299545025588016,
+                    byte_start: 4312,
+                    byte_size: 200,
+                    type_id: 1,
+                    class_id: 28,
+                },
+            ],
+            serialized_file.objects
+        );
+        let texture = serialized_file
+            .object_data(&serialized_file.objects[1])
+            .unwrap();
+        assert_eq!(
+            Some("item_icon_00000".to_string()),
+            read_asset_name(texture, serialized_file.endianness())
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_target_platform() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut body = unity_asset_bundle.stream_files[0].body.clone();
+        let serialized_file = read_serialized_file(&body).unwrap();
+        assert_eq!(
+            Some(Platform::StandaloneWindows64),
+            serialized_file.target_platform()
+        );
+
+        // m_TargetPlatform follows the "2019.4.1f1" version string at 20
+        body[31..35].copy_from_slice(&13i32.to_le_bytes());
+        let serialized_file = read_serialized_file(&body).unwrap();
+        assert_eq!(Some(Platform::Android), serialized_file.target_platform());
+        body[31..35].copy_from_slice(&1000i32.to_le_bytes());
+        let serialized_file = read_serialized_file(&body).unwrap();
+        assert_eq!(None, serialized_file.target_platform());
+        assert_eq!(1000, serialized_file.target_platform);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_serialized_file_version() {
+        let file = read_file("./anm_chara_tear_animator");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        assert_eq!("2019.4.1f1", serialized_file.unity_version());
+        assert_eq!(3, serialized_file.objects.len());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_object_iter() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let body = &unity_asset_bundle.stream_files[0].body;
+        let serialized_file = read_serialized_file(body).unwrap();
+        let objects = serialized_file
+            .object_iter()
+            .map(|(object, data)| (object.class_id, data))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(142, &body[4096..4308]), (28, &body[4312..4512])],
+            objects
+        );
+        let names = serialized_file
+            .object_iter()
+            .filter_map(|(_, data)| read_asset_name(data, serialized_file.endianness()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["item/item_icon_00000.a", "item_icon_00000"], names);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_objects_of_class() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let body = &unity_asset_bundle.stream_files[0].body;
+        let serialized_file = read_serialized_file(body).unwrap();
+        let textures = serialized_file.objects_of_class(28);
+        assert_eq!(1, textures.len());
+        assert_eq!(7665299545025588016, textures[0].0.path_id);
+        assert_eq!(&body[4312..4512], textures[0].1);
+        assert!(serialized_file.objects_of_class(114).is_empty());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_script_types() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let body = &unity_asset_bundle.stream_files[0].body;
+        let serialized_file = read_serialized_file(body).unwrap();
+        assert!(serialized_file.script_types.is_empty());
+
+        // m_ScriptTypes starts at 3832, right after the object table; fill in one entry
+        // and drop as much of the zero padding in front of the object data at 4096
+        let mut entry = 1i32.to_le_bytes().to_vec();
+        entry.extend_from_slice(&0i32.to_le_bytes());
+        entry.extend_from_slice(&11500000i64.to_le_bytes());
+        let mut patched = body[..3832].to_vec();
+        patched.extend_from_slice(&entry);
+        patched.extend_from_slice(&body[3836..4096 - (entry.len() - 4)]);
+        patched.extend_from_slice(&body[4096..]);
+
+        let serialized_file = read_serialized_file(&patched).unwrap();
+        let script = LocalSerializedObjectIdentifier {
+            local_serialized_file_index: 0,
+            local_identifier_in_file: 11500000,
+        };
+        assert_eq!(vec![script], serialized_file.script_types);
+        assert_eq!(2, serialized_file.object_iter().count());
+        assert_eq!(None, serialized_file.script_type(&serialized_file.types[0]));
+        let mono_behaviour = SerializedType {
+            class_id: 114,
+            script_type_index: 0,
+            ..Default::default()
+        };
+        assert_eq!(Some(&script), serialized_file.script_type(&mono_behaviour));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_object_by_path_id() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let body = &unity_asset_bundle.stream_files[0].body;
+        let serialized_file = read_serialized_file(body).unwrap();
+        let (object, data) = serialized_file
+            .object_by_path_id(7665299545025588016)
+            .unwrap();
+        assert_eq!(28, object.class_id);
+        assert_eq!(&body[4312..4512], data);
+        assert_eq!(
+            142,
+            serialized_file.object_by_path_id(1).unwrap().0.class_id
+        );
+        assert_eq!(None, serialized_file.object_by_path_id(2));
+    }
+
+    fn build_header(metadata_size: u32, file_size: u32, data_offset: u32) -> Vec<u8> {
+        let mut file = metadata_size.to_be_bytes().to_vec();
+        file.extend_from_slice(&file_size.to_be_bytes());
+        file.extend_from_slice(&21u32.to_be_bytes());
+        file.extend_from_slice(&data_offset.to_be_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.resize(file_size as usize, 0);
+        file
+    }
+
+    #[test]
+    fn test_read_serialized_file_data_offset_past_file_size() {
+        let file = build_header(16, 64, 128);
+        let error = read_serialized_file(&file).unwrap_err();
+        assert!(matches!(
+            error,
+            UnityPackError::InconsistentSerializedFileHeader(
+                HeaderInconsistency::DataOffsetPastFileSize {
+                    data_offset: 128,
+                    file_size: 64,
+                }
+            )
+        ));
+        assert_eq!(
+            "inconsistent serialized file header: data offset 128 is past the file size 64",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_read_serialized_file_metadata_past_data_offset() {
+        // 20 header bytes plus 64 bytes of metadata run into data starting at 64
+        let file = build_header(64, 128, 64);
+        assert!(matches!(
+            read_serialized_file(&file),
+            Err(UnityPackError::InconsistentSerializedFileHeader(
+                HeaderInconsistency::MetadataPastDataOffset {
+                    metadata_end: 84,
+                    data_offset: 64,
+                }
+            ))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_serialized_file_header_is_consistent() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        assert_eq!(20, serialized_file.header.size());
+        assert_eq!(Ok(()), serialized_file.header.check_consistency());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_type_tree_strings() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        let type_tree = serialized_file.types[1].type_tree.as_ref().unwrap();
+        // both strings of the root come from the common table
+        assert_eq!(Some("Texture2D"), type_tree.node_type(&type_tree.nodes[0]));
+        assert_eq!(Some("Base"), type_tree.node_name(&type_tree.nodes[0]));
+        assert!(type_tree
+            .nodes
+            .iter()
+            .any(|node| type_tree.node_name(node) == Some("m_ForcedFallbackFormat")));
+        let node = TypeTreeNode {
+            type_str_offset: 0x8000_0000 | 1161,
+            name_str_offset: 0x8000_0000 | 5000,
+            ..Default::default()
+        };
+        assert_eq!(Some("Hash128"), type_tree.node_type(&node));
+        assert_eq!(None, type_tree.node_name(&node));
+    }
+
+    #[test]
+    fn test_read_serialized_file_without_type_tree() {
+        let mut metadata = b"2019.4.1f1\0".to_vec();
+        metadata.extend_from_slice(&19i32.to_le_bytes());
+        // enable_type_tree
+        metadata.push(0);
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        metadata.extend_from_slice(&28i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend_from_slice(&(-1i16).to_le_bytes());
+        metadata.extend_from_slice(&[0xAB; 16]);
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        // the object table is aligned to 4 bytes from the start of the file
+        while !(20 + metadata.len()).is_multiple_of(4) {
+            metadata.push(0);
+        }
+        metadata.extend_from_slice(&5i64.to_le_bytes());
+        metadata.extend_from_slice(&0u32.to_le_bytes());
+        metadata.extend_from_slice(&4u32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+
+        let data_offset = 128u32;
+        let mut file = (metadata.len() as u32).to_be_bytes().to_vec();
+        file.extend_from_slice(&(data_offset + 4).to_be_bytes());
+        file.extend_from_slice(&21u32.to_be_bytes());
+        file.extend_from_slice(&data_offset.to_be_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&metadata);
+        file.resize(data_offset as usize, 0);
+        file.extend_from_slice(b"body");
+
+        let serialized_file = read_serialized_file(&file).unwrap();
+        assert!(!serialized_file.enable_type_tree);
+        assert_eq!(1, serialized_file.types.len());
+        assert_eq!(None, serialized_file.types[0].type_tree);
+        assert_eq!([0xAB; 16], serialized_file.types[0].old_type_hash);
+        let (object, data) = serialized_file.object_by_path_id(5).unwrap();
+        assert_eq!(28, object.class_id);
+        assert_eq!(b"body", data);
+    }
+
+    fn build_v22_file(byte_start: i64) -> Vec<u8> {
+        let mut metadata = b"2022.3.1f1\0".to_vec();
+        metadata.extend_from_slice(&19i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        metadata.extend_from_slice(&28i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend_from_slice(&(-1i16).to_le_bytes());
+        metadata.extend_from_slice(&[0xAB; 16]);
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        while !(48 + metadata.len()).is_multiple_of(4) {
+            metadata.push(0);
+        }
+        metadata.extend_from_slice(&5i64.to_le_bytes());
+        metadata.extend_from_slice(&byte_start.to_le_bytes());
+        metadata.extend_from_slice(&4u32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+
+        let data_offset = 192i64;
+        let mut file = vec![0; 8];
+        file.extend_from_slice(&22u32.to_be_bytes());
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+        file.extend_from_slice(&(data_offset + 4).to_be_bytes());
+        file.extend_from_slice(&data_offset.to_be_bytes());
+        file.extend_from_slice(&0i64.to_be_bytes());
+        file.extend_from_slice(&metadata);
+        file.resize(data_offset as usize, 0);
+        file.extend_from_slice(b"body");
+        file
+    }
+
+    #[test]
+    fn test_read_serialized_file_v22_byte_start() {
+        let file = build_v22_file(0);
+        let serialized_file = read_serialized_file(&file).unwrap();
+        assert_eq!(48, serialized_file.header.size());
+        assert_eq!(b"body", serialized_file.object_by_path_id(5).unwrap().1);
+
+        assert!(matches!(
+            read_serialized_file(&build_v22_file(-1)),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Verify,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_serialized_file_v22_negative_header_fields() {
+        let file = build_v22_file(0);
+        // file_size, then data_offset
+        for field in [24, 32] {
+            let mut file = file.clone();
+            file[field..field + 8].copy_from_slice(&(-1i64).to_be_bytes());
+            assert!(matches!(
+                read_serialized_file(&file),
+                Err(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Verify,
+                    offset,
+                    ..
+                }) if offset == field
+            ));
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_externals_and_ref_types() {
+        let mut metadata = b"2019.4.1f1\0".to_vec();
+        metadata.extend_from_slice(&19i32.to_le_bytes());
+        // enable_type_tree, then no types, objects or script types
+        metadata.push(1);
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend_from_slice(&[0x11; 16]);
+        metadata.extend_from_slice(&3i32.to_le_bytes());
+        metadata.extend_from_slice(b"archive:/CAB-shared/CAB-shared\0");
+
+        // one [SerializeReference] type backed by the first script type
+        metadata.extend_from_slice(&1i32.to_le_bytes());
+        metadata.extend_from_slice(&114i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend_from_slice(&0i16.to_le_bytes());
+        metadata.extend_from_slice(&[0x22; 16]);
+        metadata.extend_from_slice(&[0x33; 16]);
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(&0i32.to_le_bytes());
+        metadata.extend_from_slice(b"Reward\0Game.Data\0Assembly-CSharp\0");
+        // m_UserInformation
+        metadata.push(0);
+
+        let data_offset = 256u32;
+        let mut file = (metadata.len() as u32).to_be_bytes().to_vec();
+        file.extend_from_slice(&data_offset.to_be_bytes());
+        file.extend_from_slice(&21u32.to_be_bytes());
+        file.extend_from_slice(&data_offset.to_be_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&metadata);
+        file.resize(data_offset as usize, 0);
+
+        let serialized_file = read_serialized_file(&file).unwrap();
+        assert_eq!(
+            vec![FileIdentifier {
+                guid: [0x11; 16],
+                file_type: 3,
+                path: "archive:/CAB-shared/CAB-shared".to_string(),
+            }],
+            serialized_file.externals
+        );
+        assert_eq!(1, serialized_file.ref_types.len());
+        let ref_type = &serialized_file.ref_types[0];
+        assert_eq!(114, ref_type.class_id);
+        assert_eq!(Some([0x22; 16]), ref_type.script_id);
+        assert_eq!([0x33; 16], ref_type.old_type_hash);
+        assert_eq!(
+            Some(SerializedTypeReference {
+                class_name: "Reward".to_string(),
+                namespace: "Game.Data".to_string(),
+                assembly_name: "Assembly-CSharp".to_string(),
+            }),
+            ref_type.type_reference
+        );
+
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        assert!(serialized_file.externals.is_empty());
+        assert!(serialized_file.ref_types.is_empty());
+    }
+}
@@ -1,6 +1,13 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Read,
+    ops::{Deref, Index, Range},
+    path::Path,
+};
+
 use nom::{
     bytes::complete::take,
-    multi::count,
     number::{
         complete::{be_i32, be_i64, be_u16},
         streaming::be_u32,
@@ -8,7 +15,83 @@ use nom::{
     IResult,
 };
 
-use crate::read_string_to_null;
+use crate::asset::{read_serialized_file, SerializedFile};
+use crate::bounded_count;
+use crate::compression::{CompressionType, Decompressor, Decryptor, DefaultDecompressor};
+use crate::error::{staged, ParseStage, UnityPackError};
+use crate::lazy_bundle::{read_lazy_unity_asset_bundle, LazyUnityAssetBundle};
+use crate::{
+    read_cstring, read_cstring_bytes, read_string_to_null, usize_from_i64,
+    MAX_VERSION_STRING_LENGTH,
+};
+
+#[derive(Clone, Copy)]
+pub struct ParseOptions<'a> {
+    pub decompressor: &'a dyn Decompressor,
+    // skip a UTF-8 BOM some proxies prepend to downloaded bundles
+    pub lenient: bool,
+    // only read the headers, blocks info and directory; the storage blocks are checked to be
+    // present but never decompressed, so `stream_files` stays empty
+    pub skip_bodies: bool,
+    // from version 7 on the blocks info starts at a multiple of this many bytes from the
+    // start of the bundle; Unity always uses 16, modified engines may not
+    pub alignment: usize,
+    // reject container flag bits this crate does not know about, which may mean a layout
+    // change, instead of ignoring them
+    pub strict: bool,
+    pub path_style: PathStyle,
+    // accept a format version that a buggy packer wrote little-endian, such as 0x06000000
+    // for 6, and set `UnityAssetBundleHeader::version_swapped`
+    pub swapped_version: bool,
+    // applied to the compressed bytes of each storage block, the blocks info is left as is
+    pub decryptor: Option<&'a dyn Decryptor>,
+    // decodes node paths that are not valid UTF-8
+    #[cfg(feature = "encoding")]
+    pub path_encoding: Option<&'static encoding_rs::Encoding>,
+}
+
+impl Default for ParseOptions<'_> {
+    fn default() -> Self {
+        ParseOptions {
+            decompressor: &DefaultDecompressor,
+            lenient: false,
+            skip_bodies: false,
+            alignment: DEFAULT_ALIGNMENT,
+            strict: false,
+            path_style: PathStyle::Raw,
+            swapped_version: false,
+            decryptor: None,
+            #[cfg(feature = "encoding")]
+            path_encoding: None,
+        }
+    }
+}
+
+pub const DEFAULT_ALIGNMENT: usize = 16;
+
+pub(crate) const ARCHIVE_PREFIX: &str = "archive:/";
+
+// how node and stream file paths such as archive:/CAB-xxxx/CAB-xxxx.resS are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathStyle {
+    // exactly as in the directory info
+    Raw,
+    // without the archive:/ prefix, CAB-xxxx/CAB-xxxx.resS
+    Stripped,
+    // only the last segment, CAB-xxxx.resS
+    FileNameOnly,
+}
+
+impl PathStyle {
+    #[must_use]
+    pub fn apply(self, path: &str) -> &str {
+        match self {
+            PathStyle::Raw => path,
+            PathStyle::Stripped => path.strip_prefix(ARCHIVE_PREFIX).unwrap_or(path),
+            PathStyle::FileNameOnly => path.rsplit('/').next().unwrap_or(path),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct UnityAssetBundle {
@@ -17,14 +100,68 @@ pub struct UnityAssetBundle {
     pub storage_blocks: Vec<UnityStorageBlock>,
     pub directory_info: Vec<UnityNode>,
     pub stream_files: Vec<UnityStreamFile>,
+    pub(crate) blocks_info_range: Range<usize>,
+    // where the first storage block starts in the parsed input
+    pub(crate) blocks_offset: usize,
+    pub(crate) custom_data: Option<Vec<u8>>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct UnityAssetBundleHeader {
     pub signature: String,
     pub version: u32,
+    // trailing whitespace and control characters, such as a stray '\r', are trimmed off
     pub unity_version: String,
     pub unity_revision: String,
+    // the strings exactly as stored, which the header size depends on
+    pub raw_unity_version: String,
+    pub raw_unity_revision: String,
+    // the version was stored little-endian and only read thanks to
+    // `ParseOptions::swapped_version`
+    pub version_swapped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BundleFormat {
+    UnityFS,
+    UnityWeb,
+    UnityRaw,
+    UnityArchive,
+}
+
+impl BundleFormat {
+    #[must_use]
+    pub fn from_signature(signature: &str) -> Option<BundleFormat> {
+        match signature {
+            "UnityFS" => Some(BundleFormat::UnityFS),
+            "UnityWeb" => Some(BundleFormat::UnityWeb),
+            "UnityRaw" => Some(BundleFormat::UnityRaw),
+            "UnityArchive" => Some(BundleFormat::UnityArchive),
+            _ => None,
+        }
+    }
+}
+
+impl UnityAssetBundleHeader {
+    #[must_use]
+    pub fn format(&self) -> Option<BundleFormat> {
+        BundleFormat::from_signature(&self.signature)
+    }
+}
+
+// only looks at the header, so it also recognizes formats whose body can't be read yet
+#[must_use]
+pub fn sniff_bundle_format(input: &[u8]) -> Option<BundleFormat> {
+    let (_, header) = read_unity_asset_bundle_header(input, &ParseOptions::default()).ok()?;
+    header.format()
+}
+
+// only the signature, format version and engine version strings are read, so just the
+// first few dozen bytes of a file are needed
+pub fn peek_header(input: &[u8]) -> Result<UnityAssetBundleHeader, UnityPackError> {
+    let (_, header) = read_unity_asset_bundle_header(input, &ParseOptions::default())
+        .map_err(|e| UnityPackError::from(e).locate(input.len(), 0))?;
+    Ok(header)
 }
 
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -50,6 +187,8 @@ pub struct UnityNode {
     pub path: String,
 }
 
+// ordered by path first; the body only breaks ties between equal paths, which keeps
+// the ordering consistent with `Eq`
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct UnityStreamFile {
     pub path: String,
@@ -57,47 +196,714 @@ pub struct UnityStreamFile {
     pub body: Vec<u8>,
 }
 
-pub fn read_unity_asset_bundle(input: &[u8]) -> IResult<&[u8], UnityAssetBundle> {
-    let (input, unity_asset_bundle_header) = read_unity_asset_bundle_header(input)?;
+/// Lets a stream file go wherever raw bytes are expected:
+///
+/// ```
+/// use unitypack::asset_bundle::read_unity_asset_bundle;
+///
+/// fn starts_with_zero(bytes: impl AsRef<[u8]>) -> bool {
+///     bytes.as_ref().first() == Some(&0)
+/// }
+///
+/// # if cfg!(not(feature = "lz4")) { return; }
+/// let file = std::fs::read("item_icon_00000").unwrap();
+/// let bundle = read_unity_asset_bundle(&file).unwrap().1;
+/// assert!(starts_with_zero(&bundle[0]));
+/// assert_eq!(4512, bundle[0].as_ref().len());
+/// ```
+impl AsRef<[u8]> for UnityStreamFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl UnityStreamFile {
+    #[must_use]
+    pub fn sha256(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(&self.body).into()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl UnityStreamFile {
+    // hands the body over without copying it; clones of the result share the allocation
+    #[must_use]
+    pub fn into_bytes(self) -> bytes::Bytes {
+        bytes::Bytes::from(self.body)
+    }
+}
+
+impl fmt::Display for UnityAssetBundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} blocks, {} files)",
+            self.header,
+            self.storage_blocks.len(),
+            self.stream_files.len()
+        )
+    }
+}
+
+impl fmt::Display for UnityAssetBundleHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} v{} ({} / {})",
+            self.signature, self.version, self.unity_version, self.unity_revision
+        )
+    }
+}
+
+impl fmt::Display for UnityContainerHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes (blocks info {}/{} bytes, flags={:#x})",
+            self.size,
+            self.compressed_blocks_info_size,
+            self.uncompressed_blocks_info_size,
+            self.flags
+        )
+    }
+}
+
+impl fmt::Display for UnityStorageBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} bytes (flags={:#x})",
+            self.compressed_size, self.uncompressed_size, self.flags
+        )
+    }
+}
+
+impl fmt::Display for UnityNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} bytes, flags={})",
+            self.path, self.size, self.flags
+        )
+    }
+}
+
+impl fmt::Display for UnityStreamFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bytes)", self.path, self.body.len())
+    }
+}
+
+// ArchiveNodeFlags
+const NODE_DIRECTORY: u32 = 0x1;
+const NODE_DELETED: u32 = 0x2;
+const NODE_SERIALIZED_FILE: u32 = 0x4;
+
+impl UnityNode {
+    #[must_use]
+    pub fn is_directory(&self) -> bool {
+        self.flags & NODE_DIRECTORY != 0
+    }
+
+    #[must_use]
+    pub fn is_deleted(&self) -> bool {
+        self.flags & NODE_DELETED != 0
+    }
+
+    #[must_use]
+    pub fn is_serialized_file(&self) -> bool {
+        self.flags & NODE_SERIALIZED_FILE != 0
+    }
+
+    // raw .resS/.resource payloads referenced by serialized objects
+    #[must_use]
+    pub fn is_resource(&self) -> bool {
+        self.flags & (NODE_DIRECTORY | NODE_DELETED | NODE_SERIALIZED_FILE) == 0
+    }
+
+    // the node's bytes in the uncompressed data, `None` when a corrupt offset or size
+    // is negative or overflows
+    pub(crate) fn data_range(&self) -> Option<Range<u64>> {
+        use std::convert::TryFrom;
+        let start = u64::try_from(self.offset).ok()?;
+        let end = start.checked_add(u64::try_from(self.size).ok()?)?;
+        Some(start..end)
+    }
+}
+
+impl UnityAssetBundle {
+    // keeps `bytes` next to the parsed bundle, for reads that need the compressed blocks
+    pub fn from_bytes_owned(bytes: Vec<u8>) -> Result<OwnedUnityAssetBundle, UnityPackError> {
+        let (_, bundle) = read_unity_asset_bundle(&bytes)?;
+        Ok(OwnedUnityAssetBundle { bytes, bundle })
+    }
+
+    // every stream file is copied out while parsing, so the file is not kept
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<UnityAssetBundle, UnityPackError> {
+        let file = std::fs::read(path)?;
+        let (_, bundle) = read_unity_asset_bundle(&file)?;
+        Ok(bundle)
+    }
+
+    // pages are loaded as the parser reaches them instead of the whole file being copied
+    // to the heap up front; every stream file is copied out, so the map is only needed
+    // while parsing
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<UnityAssetBundle, UnityPackError> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the file must not be truncated or written to while it is mapped, which
+        // nothing here can enforce. Truncating it raises SIGBUS and a concurrent write
+        // changes the bytes under the parser. Bundles are build artifacts that are
+        // normally replaced rather than edited in place, and the mapping is dropped
+        // before this returns, so the window is the parse itself
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let (_, bundle) = read_unity_asset_bundle(&map)?;
+        Ok(bundle)
+    }
+
+    /// Returns the stream file at `index`, or `None` when it is out of range.
+    ///
+    /// Indexing the bundle directly does the same but panics instead:
+    ///
+    /// ```
+    /// use unitypack::asset_bundle::read_unity_asset_bundle;
+    ///
+    /// # if cfg!(not(feature = "lz4")) { return; }
+    /// let file = std::fs::read("item_icon_00000").unwrap();
+    /// let bundle = read_unity_asset_bundle(&file).unwrap().1;
+    /// assert_eq!("CAB-5813386f0ea15049abeb5a688d9031d3", bundle[0].path);
+    /// assert_eq!(Some(&bundle[1]), bundle.file(1));
+    /// assert_eq!(None, bundle.file(2));
+    /// ```
+    #[must_use]
+    pub fn file(&self, index: usize) -> Option<&UnityStreamFile> {
+        self.stream_files.get(index)
+    }
+
+    // where the compressed blocks info sits in the parsed input
+    #[must_use]
+    pub fn blocks_info_range(&self) -> Range<usize> {
+        self.blocks_info_range.clone()
+    }
+
+    // bytes a build pipeline appended after the last storage block; see `custom_data_size`
+    // for when they count as part of the bundle
+    #[must_use]
+    pub fn custom_data(&self) -> Option<&[u8]> {
+        self.custom_data.as_deref()
+    }
+
+    /// Nodes whose file name ends in `.ext`, compared ignoring ASCII case; the leading
+    /// dot is optional:
+    ///
+    /// ```
+    /// use unitypack::asset_bundle::read_unity_asset_bundle;
+    ///
+    /// # if cfg!(not(feature = "lz4")) { return; }
+    /// let file = std::fs::read("item_icon_00000").unwrap();
+    /// let bundle = read_unity_asset_bundle(&file).unwrap().1;
+    /// let mut nodes = bundle.nodes_with_extension("resS");
+    /// assert_eq!(65536, nodes.next().unwrap().size);
+    /// assert!(nodes.next().is_none());
+    /// assert_eq!(1, bundle.nodes_with_extension(".RESS").count());
+    /// assert_eq!(0, bundle.nodes_with_extension("resource").count());
+    /// ```
+    pub fn nodes_with_extension<'a>(
+        &'a self,
+        ext: &'a str,
+    ) -> impl Iterator<Item = &'a UnityNode> + 'a {
+        let ext = ext.strip_prefix('.').unwrap_or(ext);
+        self.directory_info.iter().filter(move |node| {
+            let file_name = node.path.rsplit('/').next().unwrap_or(&node.path);
+            file_name.rsplit_once('.').is_some_and(|(stem, node_ext)| {
+                !stem.is_empty() && node_ext.eq_ignore_ascii_case(ext)
+            })
+        })
+    }
+
+    /// Nodes flagged as serialized files:
+    ///
+    /// ```
+    /// use unitypack::asset_bundle::read_unity_asset_bundle;
+    ///
+    /// # if cfg!(not(feature = "lz4")) { return; }
+    /// let file = std::fs::read("item_icon_00000").unwrap();
+    /// let bundle = read_unity_asset_bundle(&file).unwrap().1;
+    /// let paths = bundle
+    ///     .nodes_serialized()
+    ///     .map(|node| node.path.as_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vec!["CAB-5813386f0ea15049abeb5a688d9031d3"], paths);
+    /// ```
+    pub fn nodes_serialized(&self) -> impl Iterator<Item = &UnityNode> {
+        self.directory_info
+            .iter()
+            .filter(|node| node.is_serialized_file())
+    }
+
+    /// Nodes holding raw resource payloads, the counterpart of `nodes_serialized`:
+    ///
+    /// ```
+    /// use unitypack::asset_bundle::read_unity_asset_bundle;
+    ///
+    /// # if cfg!(not(feature = "lz4")) { return; }
+    /// let file = std::fs::read("item_icon_00000").unwrap();
+    /// let bundle = read_unity_asset_bundle(&file).unwrap().1;
+    /// let total = bundle.nodes_resources().map(|node| node.size).sum::<i64>();
+    /// assert_eq!(65536, total);
+    /// ```
+    pub fn nodes_resources(&self) -> impl Iterator<Item = &UnityNode> {
+        self.directory_info.iter().filter(|node| node.is_resource())
+    }
+
+    #[must_use]
+    pub fn resource_streams(&self) -> Vec<&UnityStreamFile> {
+        self.directory_info
+            .iter()
+            .zip(self.stream_files.iter())
+            .filter(|(node, _)| node.is_resource())
+            .map(|(_, stream_file)| stream_file)
+            .collect()
+    }
+
+    #[must_use]
+    pub fn serialized_files(&self) -> Vec<&UnityStreamFile> {
+        self.directory_info
+            .iter()
+            .zip(self.stream_files.iter())
+            .filter(|(node, _)| node.is_serialized_file())
+            .map(|(_, stream_file)| stream_file)
+            .collect()
+    }
+
+    // every serialized file with its own parse result, so one corrupt node does not
+    // hide the others
+    #[must_use]
+    pub fn try_parse_serialized_files(
+        &self,
+    ) -> Vec<(String, Result<SerializedFile<'_>, UnityPackError>)> {
+        self.serialized_files()
+            .into_iter()
+            .map(|stream_file| {
+                (
+                    stream_file.path.clone(),
+                    read_serialized_file(&stream_file.body),
+                )
+            })
+            .collect()
+    }
+
+    // serialized files whose header or object table fails to parse are left out
+    #[must_use]
+    pub fn object_class_histogram(&self) -> HashMap<i32, usize> {
+        let mut histogram = HashMap::new();
+        for stream_file in self.serialized_files() {
+            if let Ok(serialized_file) = read_serialized_file(&stream_file.body) {
+                for object in serialized_file.objects.iter() {
+                    *histogram.entry(object.class_id).or_insert(0) += 1;
+                }
+            }
+        }
+        histogram
+    }
+
+    // the bodies are already decompressed, so this only adapts one to `Read`; see
+    // `LazyUnityAssetBundle::file_reader` for decompressing while reading
+    #[must_use]
+    pub fn file_reader(&self, path: &str) -> Option<impl Read + '_> {
+        self.stream_files
+            .iter()
+            .find(|sf| sf.path == path)
+            .map(|sf| sf.body.as_slice())
+    }
+
+    // distinct engine versions of the serialized files in node order, which can differ
+    // from the header when content was rebuilt; files that fail to parse are left out
+    #[must_use]
+    pub fn serialized_file_versions(&self) -> Vec<String> {
+        let mut versions = vec![];
+        for stream_file in self.serialized_files() {
+            if let Ok(serialized_file) = read_serialized_file(&stream_file.body) {
+                if !versions.contains(&serialized_file.unity_version) {
+                    versions.push(serialized_file.unity_version);
+                }
+            }
+        }
+        versions
+    }
+
+    // accepts either the node path or an archive:/ path from StreamingInfo
+    #[must_use]
+    pub fn resource_by_name(&self, name: &str) -> Option<&UnityStreamFile> {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        self.resource_streams()
+            .into_iter()
+            .find(|sf| sf.path == name || sf.path == file_name)
+    }
+
+    #[must_use]
+    pub fn file_sizes(&self) -> Vec<(String, i64)> {
+        self.directory_info
+            .iter()
+            .map(|di| (di.path.clone(), di.size))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn total_uncompressed_size(&self) -> u64 {
+        self.storage_blocks
+            .iter()
+            .map(|sb| sb.uncompressed_size as u64)
+            .sum()
+    }
+
+    // the storage blocks plus the blocks info, as stored
+    #[must_use]
+    pub fn total_compressed_size(&self) -> u64 {
+        self.container_header.compressed_blocks_info_size as u64
+            + self
+                .storage_blocks
+                .iter()
+                .map(|sb| sb.compressed_size as u64)
+                .sum::<u64>()
+    }
+
+    #[must_use]
+    pub fn block_ranges(&self) -> Vec<Range<u64>> {
+        let mut start = 0;
+        self.storage_blocks
+            .iter()
+            .map(|sb| {
+                let range = start..start + sb.uncompressed_size as u64;
+                start = range.end;
+                range
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "sha2")]
+    #[must_use]
+    pub fn file_digests(&self) -> Vec<(&str, [u8; 32])> {
+        self.stream_files
+            .iter()
+            .map(|sf| (sf.path.as_str(), sf.sha256()))
+            .collect()
+    }
+
+    // `source` is the input the bundle was parsed from, or the bundle itself for
+    // `read_unity_asset_bundle_at`; blocks are decoded with the default decompressor
+    pub fn decompress_block(&self, source: &[u8], index: usize) -> Result<Vec<u8>, UnityPackError> {
+        self.decompress_block_with_options(source, index, &ParseOptions::default())
+    }
+
+    // like `decompress_block`, but goes through the decryptor and decompressor of `options`,
+    // which should be the ones the bundle was parsed with
+    pub fn decompress_block_with_options(
+        &self,
+        source: &[u8],
+        index: usize,
+        options: &ParseOptions,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        let sb = self
+            .storage_blocks
+            .get(index)
+            .ok_or(UnityPackError::BlockOutOfRange(index))?;
+        let start = self.blocks_offset
+            + self.storage_blocks[..index]
+                .iter()
+                .map(|sb| sb.compressed_size as usize)
+                .sum::<usize>();
+        let bytes = start
+            .checked_add(sb.compressed_size as usize)
+            .and_then(|end| source.get(start..end))
+            .ok_or(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: source.len(),
+                stage: Some(ParseStage::Blocks),
+            })?;
+        let mut block = Vec::new();
+        decompress_storage_block(bytes, index, sb, options, &mut block)?;
+        Ok(block)
+    }
+
+    // the contiguous storage blocks whose uncompressed bytes intersect `range`
+    #[must_use]
+    pub fn blocks_covering(&self, range: Range<u64>) -> &[UnityStorageBlock] {
+        if range.start >= range.end {
+            return &[];
+        }
+        let ranges = self.block_ranges();
+        let first = ranges.iter().position(|r| range.start < r.end);
+        let last = ranges.iter().rposition(|r| r.start < range.end);
+        match (first, last) {
+            (Some(first), Some(last)) if first <= last => &self.storage_blocks[first..=last],
+            _ => &[],
+        }
+    }
+
+    // storage blocks a read of the node at `path` has to decompress; a file crossing a
+    // block boundary counts every block it touches, however little of it sits in each,
+    // and an empty file spans none
+    #[must_use]
+    pub fn blocks_spanned_by(&self, path: &str) -> Option<usize> {
+        let node = self.directory_info.iter().find(|node| node.path == path)?;
+        Some(self.blocks_covering(node.data_range()?).len())
+    }
+
+    #[must_use]
+    pub fn node_compression_types(&self, node: &UnityNode) -> Vec<CompressionType> {
+        // a corrupt node covers no block
+        let node_range = match node.data_range() {
+            Some(node_range) => node_range,
+            None => return vec![],
+        };
+        let mut types = self
+            .block_ranges()
+            .iter()
+            .zip(self.storage_blocks.iter())
+            .filter(|(range, _)| range.start < node_range.end && node_range.start < range.end)
+            .filter_map(|(_, sb)| CompressionType::from_flags(sb.flags as u32).ok())
+            .collect::<Vec<_>>();
+        types.sort();
+        types.dedup();
+        types
+    }
+}
+
+impl Index<usize> for UnityAssetBundle {
+    type Output = UnityStreamFile;
+
+    fn index(&self, index: usize) -> &UnityStreamFile {
+        &self.stream_files[index]
+    }
+}
+
+// a bundle together with the file it was parsed from
+pub struct OwnedUnityAssetBundle {
+    bytes: Vec<u8>,
+    bundle: UnityAssetBundle,
+}
+
+impl OwnedUnityAssetBundle {
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[must_use]
+    pub fn blocks_info_bytes(&self) -> &[u8] {
+        &self.bytes[self.bundle.blocks_info_range()]
+    }
+
+    pub fn decompress_block(&self, index: usize) -> Result<Vec<u8>, UnityPackError> {
+        self.bundle.decompress_block(&self.bytes, index)
+    }
+
+    pub fn lazy(&self) -> Result<LazyUnityAssetBundle<'_>, UnityPackError> {
+        let (_, lazy_bundle) = read_lazy_unity_asset_bundle(&self.bytes)?;
+        Ok(lazy_bundle)
+    }
+
+    #[must_use]
+    pub fn into_parts(self) -> (Vec<u8>, UnityAssetBundle) {
+        (self.bytes, self.bundle)
+    }
+}
+
+impl Deref for OwnedUnityAssetBundle {
+    type Target = UnityAssetBundle;
+
+    fn deref(&self) -> &UnityAssetBundle {
+        &self.bundle
+    }
+}
+
+pub fn read_unity_asset_bundle(input: &[u8]) -> IResult<&[u8], UnityAssetBundle, UnityPackError> {
+    read_unity_asset_bundle_with_options(input, &ParseOptions::default())
+}
+
+pub fn read_unity_asset_bundle_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UnityAssetBundle, UnityPackError> {
+    let input_len = input.len();
+    read_bundle(input, options).map_err(|e| e.map(|e| e.locate(input_len, 0)))
+}
+
+// for bundles embedded in a larger file; error offsets stay relative to `input`, while
+// `blocks_info_range` is relative to the start of the bundle
+pub fn read_unity_asset_bundle_at(
+    input: &[u8],
+    offset: usize,
+) -> IResult<&[u8], UnityAssetBundle, UnityPackError> {
+    let bundle = input
+        .get(offset..)
+        .ok_or(nom::Err::Error(UnityPackError::Parse {
+            kind: nom::error::ErrorKind::Eof,
+            offset: input.len(),
+            stage: None,
+        }))?;
+    let bundle_len = bundle.len();
+    read_bundle(bundle, &ParseOptions::default())
+        .map_err(|e| e.map(|e| e.locate(bundle_len, offset)))
+}
+
+const UNITYFS_SIGNATURE: &[u8] = b"UnityFS\0";
+
+#[must_use]
+pub fn find_unityfs_offsets(input: &[u8]) -> Vec<usize> {
+    input
+        .windows(UNITYFS_SIGNATURE.len())
+        .enumerate()
+        .filter(|(_, window)| *window == UNITYFS_SIGNATURE)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+fn read_bundle<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UnityAssetBundle, UnityPackError> {
+    let input_len = input.len();
+    let (input, _) = skip_bom(input, options)?;
+    let bundle_start = input_len - input.len();
+    let (input, unity_asset_bundle_header) = read_unity_asset_bundle_header(input, options)?;
     let (input, unity_container_header) = read_unity_container_header(input)?;
-    let (
+    let blocks_info_base = input_len - input.len();
+    let (input, blocks_info) = read_blocks_info_and_directory(
+        input,
+        &unity_asset_bundle_header,
+        &unity_container_header,
+        options,
+    )?;
+    let blocks_offset = input_len - input.len();
+    let (input, stream_files) = read_files(
         input,
-        ((unity_asset_bundle_header, unity_container_header), (storage_blocks, directory_info)),
-    ) = read_blocks_info_and_directory(input, unity_asset_bundle_header, unity_container_header)?;
-    let (input, (stream_files, storage_blocks, directory_info)) =
-        read_files(input, storage_blocks, directory_info)?;
+        &blocks_info.storage_blocks,
+        &blocks_info.nodes,
+        options,
+    )?;
+    let blocks_end = input_len - input.len() - bundle_start;
+    let custom_data_size =
+        custom_data_size(&unity_container_header, blocks_end).map_err(nom::Err::Failure)?;
+    let (input, custom_data) =
+        take(custom_data_size)(input).map_err(|e| staged(e, ParseStage::Blocks))?;
+    // a blocks info at the end was already read, but is still part of this bundle
+    let trailing_blocks_info_size = if unity_container_header.flags & BLOCKS_INFO_AT_THE_END != 0 {
+        usize_from_i64(unity_container_header.size)
+            .map_err(nom::Err::Failure)?
+            .saturating_sub(blocks_end)
+    } else {
+        0
+    };
+    let (input, _) =
+        take(trailing_blocks_info_size)(input).map_err(|e| staged(e, ParseStage::BlocksInfo))?;
 
     Ok((
         input,
         UnityAssetBundle {
             header: unity_asset_bundle_header,
             container_header: unity_container_header,
-            storage_blocks,
-            directory_info,
+            storage_blocks: blocks_info.storage_blocks,
+            directory_info: blocks_info.nodes,
             stream_files,
+            blocks_info_range: blocks_info_base + blocks_info.range.start
+                ..blocks_info_base + blocks_info.range.end,
+            blocks_offset,
+            custom_data: (!custom_data.is_empty()).then(|| custom_data.to_vec()),
         },
     ))
 }
 
-fn read_unity_asset_bundle_header(input: &[u8]) -> IResult<&[u8], UnityAssetBundleHeader> {
+// the container size covers the whole bundle, so whatever it declares past the end of
+// the last storage block is custom data; a size ending at or before that point means
+// there is none, bytes past the declared size are not part of the bundle, and input
+// ending inside the declared region is a truncated file. When the blocks info is at the
+// end it fills that space instead
+pub(crate) fn custom_data_size(
+    container_header: &UnityContainerHeader,
+    blocks_end: usize,
+) -> Result<usize, UnityPackError> {
+    if container_header.flags & BLOCKS_INFO_AT_THE_END != 0 {
+        return Ok(0);
+    }
+    Ok(usize_from_i64(container_header.size)?.saturating_sub(blocks_end))
+}
+
+pub(crate) const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+pub(crate) fn skip_bom<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    match input.strip_prefix(BOM) {
+        Some(rest) if options.lenient => Ok((rest, ())),
+        Some(_) => Err(nom::Err::Failure(UnityPackError::UnexpectedBom)),
+        None => Ok((input, ())),
+    }
+}
+
+// format versions are single digits so far, anything past this is not a real version
+const MAX_PLAUSIBLE_VERSION: u32 = 0xFFFF;
+
+pub(crate) fn read_unity_asset_bundle_header<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UnityAssetBundleHeader, UnityPackError> {
+    read_header(input, options).map_err(|e| staged(e, ParseStage::Header))
+}
+
+fn read_header<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UnityAssetBundleHeader, UnityPackError> {
     let (input, signature) = read_string_to_null(input)?;
-    let (input, version) = be_u32(input)?;
-    let (input, unity_version) = read_string_to_null(input)?;
-    let (input, unity_revision) = read_string_to_null(input)?;
+    if BundleFormat::from_signature(&signature).is_none() {
+        return Err(nom::Err::Error(UnityPackError::UnknownSignature(signature)));
+    }
+    let (input, mut version) = be_u32(input)?;
+    let version_swapped = options.swapped_version
+        && version > MAX_PLAUSIBLE_VERSION
+        && version.swap_bytes() <= MAX_PLAUSIBLE_VERSION;
+    if version_swapped {
+        version = version.swap_bytes();
+    }
+    let (input, raw_unity_version) = read_cstring(input, MAX_VERSION_STRING_LENGTH)?;
+    let (input, raw_unity_revision) = read_cstring(input, MAX_VERSION_STRING_LENGTH)?;
 
     Ok((
         input,
         UnityAssetBundleHeader {
             signature,
             version,
-            unity_version,
-            unity_revision,
+            unity_version: trim_version(&raw_unity_version),
+            unity_revision: trim_version(&raw_unity_revision),
+            raw_unity_version,
+            raw_unity_revision,
+            version_swapped,
         },
     ))
 }
 
-fn read_unity_container_header(input: &[u8]) -> IResult<&[u8], UnityContainerHeader> {
+fn trim_version(raw: &str) -> String {
+    raw.trim_end_matches(|c: char| c.is_whitespace() || c.is_control())
+        .to_string()
+}
+
+pub(crate) fn read_unity_container_header(
+    input: &[u8],
+) -> IResult<&[u8], UnityContainerHeader, UnityPackError> {
+    read_container_header(input).map_err(|e| staged(e, ParseStage::ContainerHeader))
+}
+
+fn read_container_header(input: &[u8]) -> IResult<&[u8], UnityContainerHeader, UnityPackError> {
     let (input, size) = be_i64(input)?;
+    // a corrupt, negative total size would otherwise pass every `size >= n` check
+    if size < 0 {
+        return Err(nom::Err::Failure(UnityPackError::OffsetOverflow(size)));
+    }
     let (input, compressed_blocks_info_size) = be_u32(input)?;
     let (input, uncompressed_blocks_info_size) = be_u32(input)?;
     let (input, flags) = be_u32(input)?;
@@ -113,67 +919,236 @@ fn read_unity_container_header(input: &[u8]) -> IResult<&[u8], UnityContainerHea
     ))
 }
 
-fn read_blocks_info_and_directory(
-    input: &[u8],
-    header: UnityAssetBundleHeader,
-    container_header: UnityContainerHeader,
-) -> IResult<
-    &[u8],
-    (
-        (UnityAssetBundleHeader, UnityContainerHeader),
-        (Vec<UnityStorageBlock>, Vec<UnityNode>),
-    ),
-> {
-    if header.version >= 7 {
-        // 16bytes align
-        todo!()
-    }
-
-    let (input, compressed_blocks_info_bytes) = if (container_header.flags & 0x80) != 0 {
-        // kArchiveBlocksInfoAtTheEnd
-        let bytes = &input[(input.len() - container_header.compressed_blocks_info_size as usize)..];
-        (input, bytes)
-    } else {
-        // kArchiveBlocksAndDirectoryInfoCombined
-        take(container_header.compressed_blocks_info_size)(input)?
+const BLOCKS_AND_DIRECTORY_INFO_COMBINED: u32 = 0x40;
+const BLOCKS_INFO_AT_THE_END: u32 = 0x80;
+const BLOCKS_INFO_AT_THE_END_COMBINED: u32 =
+    BLOCKS_AND_DIRECTORY_INFO_COMBINED | BLOCKS_INFO_AT_THE_END;
+const BLOCKS_INFO_NEED_PADDING_AT_START: u32 = 0x200;
+// the compression type, the layout bits above, kArchiveOldWebPluginCompatibility (0x100)
+// and kArchiveBlocksInfoNeedPaddingAtStart (0x200)
+pub(crate) const KNOWN_CONTAINER_FLAGS: u32 = 0x3FF;
+
+// zero bytes between the container header and the blocks info
+pub(crate) fn blocks_info_padding(
+    header: &UnityAssetBundleHeader,
+    options: &ParseOptions,
+) -> usize {
+    if header.version < 7 || options.alignment <= 1 {
+        return 0;
+    }
+    let headers_size = headers_size(header);
+    (options.alignment - headers_size % options.alignment) % options.alignment
+}
+
+// the bundle header and the container header
+fn headers_size(header: &UnityAssetBundleHeader) -> usize {
+    header.signature.len()
+        + 1
+        + 4
+        + header.raw_unity_version.len()
+        + 1
+        + header.raw_unity_revision.len()
+        + 1
+        + 20
+}
+
+// zero bytes before the first storage block, which kArchiveBlocksInfoNeedPaddingAtStart
+// puts on a 16 byte boundary; `blocks_start` is where the blocks would start without it,
+// relative to the start of the bundle
+pub(crate) fn blocks_padding(
+    container_header: &UnityContainerHeader,
+    blocks_start: usize,
+) -> usize {
+    if container_header.flags & BLOCKS_INFO_NEED_PADDING_AT_START == 0 {
+        return 0;
+    }
+    (16 - blocks_start % 16) % 16
+}
+
+pub(crate) struct BlocksInfo {
+    pub(crate) storage_blocks: Vec<UnityStorageBlock>,
+    pub(crate) nodes: Vec<UnityNode>,
+    // where the compressed blocks info sits, relative to the input handed to
+    // `read_blocks_info_and_directory`
+    pub(crate) range: Range<usize>,
+    // skipped between the blocks info, or the headers when it is at the end, and the blocks
+    pub(crate) blocks_padding: usize,
+}
+
+pub(crate) fn read_blocks_info_and_directory<'a>(
+    input: &'a [u8],
+    header: &UnityAssetBundleHeader,
+    container_header: &UnityContainerHeader,
+    options: &ParseOptions,
+) -> IResult<&'a [u8], BlocksInfo, UnityPackError> {
+    read_blocks_info(input, header, container_header, options)
+        .map_err(|e| staged(e, ParseStage::BlocksInfo))
+}
+
+fn read_blocks_info<'a>(
+    input: &'a [u8],
+    header: &UnityAssetBundleHeader,
+    container_header: &UnityContainerHeader,
+    options: &ParseOptions,
+) -> IResult<&'a [u8], BlocksInfo, UnityPackError> {
+    let input_len = input.len();
+    if header.format() == Some(BundleFormat::UnityArchive) {
+        // the header matches UnityFS, but the layout after it does not
+        return Err(nom::Err::Failure(UnityPackError::UnsupportedBundleFormat(
+            BundleFormat::UnityArchive,
+        )));
+    }
+    let unknown_flags = container_header.flags & !KNOWN_CONTAINER_FLAGS;
+    if options.strict && unknown_flags != 0 {
+        return Err(nom::Err::Failure(UnityPackError::UnknownFlags(
+            unknown_flags,
+        )));
+    }
+    let (input, _) = take(blocks_info_padding(header, options))(input)?;
+
+    let layout =
+        container_header.flags & (BLOCKS_AND_DIRECTORY_INFO_COMBINED | BLOCKS_INFO_AT_THE_END);
+    let (input, compressed_blocks_info_bytes, blocks_info_remaining) = match layout {
+        BLOCKS_AND_DIRECTORY_INFO_COMBINED => {
+            let remaining = input.len();
+            let (input, bytes) = take(container_header.compressed_blocks_info_size)(input)?;
+            (input, bytes, remaining)
+        }
+        BLOCKS_INFO_AT_THE_END_COMBINED => {
+            // it ends where the bundle does, which need not be the end of the input
+            let end = usize_from_i64(container_header.size)
+                .ok()
+                .and_then(|size| size.checked_sub(headers_size(header) + input_len - input.len()))
+                .filter(|end| *end <= input.len());
+            let start = end
+                .and_then(|end| {
+                    end.checked_sub(container_header.compressed_blocks_info_size as usize)
+                })
+                .ok_or(nom::Err::Error(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Eof,
+                    offset: input.len(),
+                    stage: None,
+                }))?;
+            let remaining = input.len() - start;
+            (
+                input,
+                &input[start..start + container_header.compressed_blocks_info_size as usize],
+                remaining,
+            )
+        }
+        // a directory stored apart from the blocks info is not a UnityFS layout
+        _ => {
+            return Err(nom::Err::Failure(
+                UnityPackError::UnsupportedContainerFlags(container_header.flags),
+            ))
+        }
     };
+    let padding = blocks_padding(
+        container_header,
+        headers_size(header) + input_len - input.len(),
+    );
+    let (input, _) = take(padding)(input)?;
     let block_info = decompress(
         compressed_blocks_info_bytes,
         container_header.uncompressed_blocks_info_size as usize,
         container_header.flags,
-    );
-    // let block_info = block_info.as_slice();
-    let (_block_info, (storage_blocks, nodes)) = read_block_infos(&block_info).unwrap();
+        options,
+    )
+    .map_err(nom::Err::Failure)?;
+    // relative to the input handed to this function
+    let start = input_len - blocks_info_remaining;
+    let blocks_info_range = start..start + compressed_blocks_info_bytes.len();
+    // an archive without blocks info has neither storage blocks nor nodes
+    if block_info.is_empty() {
+        return Ok((
+            input,
+            BlocksInfo {
+                storage_blocks: vec![],
+                nodes: vec![],
+                range: blocks_info_range,
+                blocks_padding: padding,
+            },
+        ));
+    }
+    let (_block_info, (storage_blocks, nodes)) = read_block_infos(&block_info, options)
+        .map_err(|e| e.map(|e| e.at(blocks_info_remaining)))?;
+
+    Ok((
+        input,
+        BlocksInfo {
+            storage_blocks,
+            nodes,
+            range: blocks_info_range,
+            blocks_padding: padding,
+        },
+    ))
+}
 
-    Ok((input, ((header, container_header), (storage_blocks, nodes))))
+pub(crate) fn decompress(
+    compressed_bytes: &[u8],
+    uncompressed_size: usize,
+    flags: u32,
+    options: &ParseOptions,
+) -> Result<Vec<u8>, UnityPackError> {
+    let kind = CompressionType::from_flags(flags)?;
+    // LZ4 rejects an empty output buffer, and there is nothing to decode anyway
+    if uncompressed_size == 0 {
+        return Ok(vec![]);
+    }
+    options
+        .decompressor
+        .decompress(compressed_bytes, uncompressed_size, kind)
 }
 
-fn decompress(compressed_bytes: &[u8], uncompressed_size: usize, flags: u32) -> Vec<u8> {
-    match flags & 0x3F {
-        //kArchiveCompressionTypeMask
-        1 => todo!(), // LZMA
-        2 | 3 => {
-            let decoded = lz4_flex::block::decompress(compressed_bytes, uncompressed_size).unwrap();
-            decoded
-        } // LZ4, LZ4HC
-        _ => (compressed_bytes.to_owned().to_vec()), // None
+pub(crate) fn decompress_storage_block(
+    compressed_bytes: &[u8],
+    index: usize,
+    sb: &UnityStorageBlock,
+    options: &ParseOptions,
+    out: &mut Vec<u8>,
+) -> Result<(), UnityPackError> {
+    let decrypted;
+    let compressed_bytes = match options.decryptor {
+        Some(decryptor) => {
+            let mut bytes = compressed_bytes.to_vec();
+            decryptor.decrypt(&mut bytes, index)?;
+            decrypted = bytes;
+            &decrypted[..]
+        }
+        None => compressed_bytes,
+    };
+    let kind = CompressionType::from_flags(sb.flags as u32)?;
+    if sb.uncompressed_size == 0 {
+        return Ok(());
     }
+    options
+        .decompressor
+        .decompress_into(compressed_bytes, sb.uncompressed_size as usize, kind, out)
 }
 
-fn read_block_infos(block_info: &[u8]) -> IResult<&[u8], (Vec<UnityStorageBlock>, Vec<UnityNode>)> {
+fn read_block_infos<'a>(
+    block_info: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], (Vec<UnityStorageBlock>, Vec<UnityNode>), UnityPackError> {
     let (block_info, _data_hash) = take(16usize)(block_info)?;
 
+    // every UnityFS format version, 6 through 8, lists the storage blocks before the nodes
     let (block_info, blocks_info_count) = be_i32(block_info)?;
     let (block_info, storage_blocks) =
-        count(read_storage_block, blocks_info_count as usize)(block_info)?;
+        bounded_count(read_storage_block, blocks_info_count as usize)(block_info)?;
+    // fail before any block is decompressed, and also when bodies are skipped
+    for sb in storage_blocks.iter() {
+        CompressionType::from_flags(sb.flags as u32).map_err(nom::Err::Failure)?;
+    }
 
     let (block_info, nodes_count) = be_i32(block_info)?;
-    let (block_info, nodes) = count(read_node, nodes_count as usize)(block_info)?;
+    let (block_info, nodes) =
+        bounded_count(|i| read_node(i, options), nodes_count as usize)(block_info)?;
 
     Ok((block_info, (storage_blocks, nodes)))
 }
 
-fn read_storage_block(input: &[u8]) -> IResult<&[u8], UnityStorageBlock> {
+fn read_storage_block(input: &[u8]) -> IResult<&[u8], UnityStorageBlock, UnityPackError> {
     let (input, uncompressed_size) = be_u32(input)?;
     let (input, compressed_size) = be_u32(input)?;
     let (input, flags) = be_u16(input)?;
@@ -188,11 +1163,24 @@ fn read_storage_block(input: &[u8]) -> IResult<&[u8], UnityStorageBlock> {
     ))
 }
 
-fn read_node(input: &[u8]) -> IResult<&[u8], UnityNode> {
+// well beyond any path Unity writes, but keeps a corrupt directory from running away
+const MAX_NODE_PATH_LENGTH: usize = 4096;
+
+fn read_node<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UnityNode, UnityPackError> {
     let (input, offset) = be_i64(input)?;
     let (input, size) = be_i64(input)?;
     let (input, flags) = be_u32(input)?;
-    let (input, path) = read_string_to_null(input)?;
+    // node entries are packed back to back in every version, version 7 only pads
+    // before the blocks info
+    let (input, path) = read_cstring_bytes(input, MAX_NODE_PATH_LENGTH)?;
+    let path = decode_path(path, options).map_err(nom::Err::Failure)?;
+    let path = match options.path_style {
+        PathStyle::Raw => path,
+        style => style.apply(&path).to_string(),
+    };
 
     Ok((
         input,
@@ -205,54 +1193,100 @@ fn read_node(input: &[u8]) -> IResult<&[u8], UnityNode> {
     ))
 }
 
-fn read_files(
-    input: &[u8],
-    storage_blocks: Vec<UnityStorageBlock>,
-    directory_info: Vec<UnityNode>,
-) -> IResult<&[u8], (Vec<UnityStreamFile>, Vec<UnityStorageBlock>, Vec<UnityNode>)> {
-    let (input, (decompressed, storage_blocks)) =
-        decompress_by_storage_blocks(input, storage_blocks)?;
-    let (_decompressed, (stream_files, directory_info)) =
-        read_stream_files(&decompressed, directory_info).unwrap();
-    Ok((input, (stream_files, storage_blocks, directory_info)))
+fn decode_path(path: &[u8], _options: &ParseOptions) -> Result<String, UnityPackError> {
+    let error = match std::str::from_utf8(path) {
+        Ok(path) => return Ok(path.to_string()),
+        Err(e) => e,
+    };
+    #[cfg(feature = "encoding")]
+    if let Some(encoding) = _options.path_encoding {
+        if let Some(path) = encoding.decode_without_bom_handling_and_without_replacement(path) {
+            return Ok(path.into_owned());
+        }
+    }
+    Err(error.into())
 }
 
-fn decompress_by_storage_blocks(
-    input: &[u8],
-    storage_blocks: Vec<UnityStorageBlock>,
-) -> IResult<&[u8], (Vec<u8>, Vec<UnityStorageBlock>)> {
+pub(crate) fn read_files<'a>(
+    input: &'a [u8],
+    storage_blocks: &[UnityStorageBlock],
+    directory_info: &[UnityNode],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Vec<UnityStreamFile>, UnityPackError> {
+    read_storage_blocks(input, storage_blocks, directory_info, options)
+        .map_err(|e| staged(e, ParseStage::Blocks))
+}
+
+fn read_storage_blocks<'a>(
+    input: &'a [u8],
+    storage_blocks: &[UnityStorageBlock],
+    directory_info: &[UnityNode],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Vec<UnityStreamFile>, UnityPackError> {
+    let blocks_remaining = input.len();
+    if options.skip_bodies {
+        let compressed_size = storage_blocks
+            .iter()
+            .map(|sb| sb.compressed_size as usize)
+            .sum::<usize>();
+        let (input, _) = take(compressed_size)(input)?;
+        let uncompressed_size = storage_blocks
+            .iter()
+            .map(|sb| sb.uncompressed_size as u64)
+            .sum::<u64>();
+        if directory_info.iter().any(|di| {
+            di.data_range()
+                .is_none_or(|range| range.end > uncompressed_size)
+        }) {
+            return Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: blocks_remaining,
+                stage: None,
+            }));
+        }
+        return Ok((input, vec![]));
+    }
+    let (input, decompressed) = decompress_by_storage_blocks(input, storage_blocks, options)?;
+    let (_decompressed, stream_files) = read_stream_files(&decompressed, directory_info)
+        .map_err(|e| e.map(|e| e.at(blocks_remaining)))?;
+    Ok((input, stream_files))
+}
+
+pub(crate) const MAX_PREALLOCATED_BYTES: usize = 256 << 20;
+
+fn decompress_by_storage_blocks<'a>(
+    input: &'a [u8],
+    storage_blocks: &[UnityStorageBlock],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Vec<u8>, UnityPackError> {
     let mut input = input;
-    let mut decompresseds = Vec::with_capacity(storage_blocks.len());
-    let mut dec = vec![];
-    for sb in storage_blocks.iter() {
-        let compressed_bytes: &[u8];
+    let total_size = storage_blocks
+        .iter()
+        .map(|sb| sb.uncompressed_size as usize)
+        .sum::<usize>();
+    // sizes come from the blocks info, so cap the up-front reservation
+    let mut dec = Vec::with_capacity(total_size.min(MAX_PREALLOCATED_BYTES));
+    for (index, sb) in storage_blocks.iter().enumerate() {
         let result = take(sb.compressed_size)(input)?;
         input = result.0;
-        compressed_bytes = result.1;
-        let decompressed = decompress(
-            compressed_bytes,
-            sb.uncompressed_size as usize,
-            sb.flags as u32,
-        );
-        decompresseds.push(decompressed);
-    }
-    for mut d in decompresseds {
-        dec.append(&mut d);
+        let compressed_bytes: &[u8] = result.1;
+        decompress_storage_block(compressed_bytes, index, sb, options, &mut dec)
+            .map_err(nom::Err::Failure)?;
     }
-    Ok((input, (dec, storage_blocks)))
+    Ok((input, dec))
 }
 
-fn read_stream_files(
-    input: &[u8],
-    directory_info: Vec<UnityNode>,
-) -> IResult<&[u8], (Vec<UnityStreamFile>, Vec<UnityNode>)> {
+fn read_stream_files<'a>(
+    input: &'a [u8],
+    directory_info: &[UnityNode],
+) -> IResult<&'a [u8], Vec<UnityStreamFile>, UnityPackError> {
     let mut input = input;
     let mut stream_files = vec![];
     for di in directory_info.iter() {
-        let body: &[u8];
-        let result = take(di.size as usize)(input)?;
+        let size = usize_from_i64(di.size).map_err(nom::Err::Failure)?;
+        let result = take(size)(input)?;
         input = result.0;
-        body = result.1;
+        let body: &[u8] = result.1;
         let sf = UnityStreamFile {
             path: di.path.clone(),
             file_name: di.path.clone(),
@@ -261,22 +1295,92 @@ fn read_stream_files(
         stream_files.push(sf);
     }
 
-    Ok((input, (stream_files, directory_info)))
+    Ok((input, stream_files))
 }
 
 #[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
 mod tests {
-    use std::{io::Read, path::Path};
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
-    use crate::asset_bundle::{read_unity_asset_bundle, UnityNode, UnityStorageBlock};
+    use crate::asset_bundle::{
+        blocks_info_padding, find_unityfs_offsets, peek_header, read_unity_asset_bundle,
+        read_unity_asset_bundle_at, read_unity_asset_bundle_header,
+        read_unity_asset_bundle_with_options, sniff_bundle_format, BundleFormat, ParseOptions,
+        PathStyle, UnityAssetBundle, UnityNode, UnityStorageBlock, UnityStreamFile,
+    };
+    use crate::compression::{CompressionType, Decompressor, Decryptor};
+    use crate::error::{ParseStage, UnityPackError};
+    use crate::test_fixtures::read_file;
+
+    // UnityFS v6 bundle with uncompressed blocks info and a single uncompressed storage block
+    fn build_uncompressed_bundle(files: &[(&str, &[u8], u32)], block_flags: u16) -> Vec<u8> {
+        build_bundle_with_container_flags(files, block_flags, 0x40)
+    }
+
+    fn build_bundle_with_container_flags(
+        files: &[(&str, &[u8], u32)],
+        block_flags: u16,
+        container_flags: u32,
+    ) -> Vec<u8> {
+        let data: Vec<u8> = files
+            .iter()
+            .flat_map(|(_, body, _)| body.to_vec())
+            .collect();
 
-    fn read_file<P: AsRef<Path>>(file_path: P) -> Vec<u8> {
-        let mut file = std::fs::File::open(file_path).expect("file open failed");
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).expect("file read failed");
-        buf
+        let mut block_info = vec![0u8; 16];
+        block_info.extend_from_slice(&1i32.to_be_bytes());
+        block_info.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        block_info.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        block_info.extend_from_slice(&block_flags.to_be_bytes());
+        block_info.extend_from_slice(&(files.len() as i32).to_be_bytes());
+        let mut offset = 0i64;
+        for (path, body, flags) in files {
+            block_info.extend_from_slice(&offset.to_be_bytes());
+            block_info.extend_from_slice(&(body.len() as i64).to_be_bytes());
+            block_info.extend_from_slice(&flags.to_be_bytes());
+            block_info.extend_from_slice(path.as_bytes());
+            block_info.push(0);
+            offset += body.len() as i64;
+        }
+
+        let mut bundle = b"UnityFS\0".to_vec();
+        bundle.extend_from_slice(&6u32.to_be_bytes());
+        bundle.extend_from_slice(b"5.x.x\0");
+        bundle.extend_from_slice(b"2019.4.1f1\0");
+        // kArchiveBlocksInfoNeedPaddingAtStart puts the first block on a 16 byte boundary
+        let blocks_start = if (container_flags & 0x80) != 0 {
+            bundle.len() + 20
+        } else {
+            bundle.len() + 20 + block_info.len()
+        };
+        let padding = if (container_flags & 0x200) != 0 {
+            (16 - blocks_start % 16) % 16
+        } else {
+            0
+        };
+        let size = bundle.len() + 20 + block_info.len() + padding + data.len();
+        bundle.extend_from_slice(&(size as i64).to_be_bytes());
+        bundle.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(&container_flags.to_be_bytes());
+        if (container_flags & 0x80) != 0 {
+            bundle.resize(bundle.len() + padding, 0);
+            bundle.extend_from_slice(&data);
+            bundle.extend_from_slice(&block_info);
+        } else {
+            bundle.extend_from_slice(&block_info);
+            bundle.resize(bundle.len() + padding, 0);
+            bundle.extend_from_slice(&data);
+        }
+        bundle
     }
 
+    #[cfg(feature = "lz4")]
     #[test]
     fn test_read_unity_asset_bundle_header() {
         let file = read_file("./item_icon_00000");
@@ -333,4 +1437,1298 @@ mod tests {
             unity_asset_bundle.stream_files[1].path
         );
     }
+
+    struct PassthroughDecompressor {
+        calls: AtomicUsize,
+    }
+
+    impl Decompressor for PassthroughDecompressor {
+        fn decompress(
+            &self,
+            input: &[u8],
+            _out_size: usize,
+            kind: CompressionType,
+        ) -> Result<Vec<u8>, UnityPackError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            match kind {
+                CompressionType::None => Ok(input.to_vec()),
+                _ => Err(UnityPackError::Decompress("passthrough only".into())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_unity_asset_bundle_with_custom_decompressor() {
+        let file = build_uncompressed_bundle(
+            &[("CAB-test", b"hello", 4), ("CAB-test.resS", b"world", 0)],
+            0,
+        );
+        let decompressor = PassthroughDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            ..Default::default()
+        };
+        let unity_asset_bundle = read_unity_asset_bundle_with_options(&file, &options)
+            .unwrap()
+            .1;
+        // blocks info + one storage block
+        assert_eq!(2, decompressor.calls.load(Ordering::Relaxed));
+        assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+        assert_eq!(b"world".to_vec(), unity_asset_bundle.stream_files[1].body);
+
+        let file = read_file("./item_icon_00000");
+        assert!(matches!(
+            read_unity_asset_bundle_with_options(&file, &options),
+            Err(nom::Err::Failure(UnityPackError::Decompress(_)))
+        ));
+    }
+
+    #[test]
+    fn test_path_style() {
+        let path = "archive:/CAB-test/CAB-test.resS";
+        let file = build_uncompressed_bundle(&[(path, b"hello", 0)], 0);
+        for (path_style, expected) in [
+            (PathStyle::Raw, path),
+            (PathStyle::Stripped, "CAB-test/CAB-test.resS"),
+            (PathStyle::FileNameOnly, "CAB-test.resS"),
+        ] {
+            let options = ParseOptions {
+                path_style,
+                ..Default::default()
+            };
+            let unity_asset_bundle = read_unity_asset_bundle_with_options(&file, &options)
+                .unwrap()
+                .1;
+            assert_eq!(expected, unity_asset_bundle.directory_info[0].path);
+            assert_eq!(expected, unity_asset_bundle.stream_files[0].path);
+            assert_eq!(expected, unity_asset_bundle.stream_files[0].file_name);
+        }
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(path, unity_asset_bundle.directory_info[0].path);
+        // paths without the prefix are left alone
+        assert_eq!("CAB-test", PathStyle::Stripped.apply("CAB-test"));
+        assert_eq!("CAB-test", PathStyle::FileNameOnly.apply("CAB-test"));
+    }
+
+    #[test]
+    fn test_skip_bodies() {
+        let file = build_uncompressed_bundle(
+            &[("CAB-test", b"hello", 4), ("CAB-test.resS", b"world", 0)],
+            0,
+        );
+        let decompressor = PassthroughDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            skip_bodies: true,
+            ..Default::default()
+        };
+        let (rest, unity_asset_bundle) =
+            read_unity_asset_bundle_with_options(&file, &options).unwrap();
+        assert!(rest.is_empty());
+        // only the blocks info went through the decompressor
+        assert_eq!(1, decompressor.calls.load(Ordering::Relaxed));
+        assert_eq!(2, unity_asset_bundle.directory_info.len());
+        assert_eq!(1, unity_asset_bundle.storage_blocks.len());
+        assert!(unity_asset_bundle.stream_files.is_empty());
+
+        // missing storage blocks are still noticed
+        assert!(read_unity_asset_bundle_with_options(&file[..file.len() - 1], &options).is_err());
+
+        // and so is a node whose range overflows
+        let mut file = file;
+        file[83..91].copy_from_slice(&(i64::MAX - 2).to_be_bytes());
+        assert!(matches!(
+            read_unity_asset_bundle_with_options(&file, &options),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
+
+    #[cfg(all(feature = "lz4", feature = "lzma"))]
+    #[test]
+    fn test_read_mixed_compression() {
+        use crate::compression::compress;
+
+        let data = b"hello world".repeat(64);
+        let mixes = [
+            (CompressionType::Lz4, CompressionType::Lzma),
+            (CompressionType::Lzma, CompressionType::Lz4Hc),
+        ];
+        for (blocks_info_compression, block_compression) in mixes {
+            let block = compress(&data, block_compression).unwrap();
+            let mut block_info = vec![0u8; 16];
+            block_info.extend_from_slice(&1i32.to_be_bytes());
+            block_info.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            block_info.extend_from_slice(&(block.len() as u32).to_be_bytes());
+            // kStorageBlockStreamed rides along in the upper bits
+            block_info
+                .extend_from_slice(&(block_compression.to_flags() as u16 | 0x40).to_be_bytes());
+            block_info.extend_from_slice(&1i32.to_be_bytes());
+            block_info.extend_from_slice(&0i64.to_be_bytes());
+            block_info.extend_from_slice(&(data.len() as i64).to_be_bytes());
+            block_info.extend_from_slice(&4u32.to_be_bytes());
+            block_info.extend_from_slice(b"CAB-mixed\0");
+            let compressed_block_info = compress(&block_info, blocks_info_compression).unwrap();
+
+            let mut file = b"UnityFS\0".to_vec();
+            file.extend_from_slice(&6u32.to_be_bytes());
+            file.extend_from_slice(b"5.x.x\0");
+            file.extend_from_slice(b"2019.4.1f1\0");
+            let size = file.len() + 20 + compressed_block_info.len() + block.len();
+            file.extend_from_slice(&(size as i64).to_be_bytes());
+            file.extend_from_slice(&(compressed_block_info.len() as u32).to_be_bytes());
+            file.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+            file.extend_from_slice(&(0x40 | blocks_info_compression.to_flags()).to_be_bytes());
+            file.extend_from_slice(&compressed_block_info);
+            file.extend_from_slice(&block);
+
+            let (rest, unity_asset_bundle) = read_unity_asset_bundle(&file).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(data, unity_asset_bundle.stream_files[0].body);
+            assert_eq!(
+                vec![block_compression],
+                unity_asset_bundle.node_compression_types(&unity_asset_bundle.directory_info[0])
+            );
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_file_sizes() {
+        let file = read_file("./item_icon_00000");
+        let options = ParseOptions {
+            skip_bodies: true,
+            ..Default::default()
+        };
+        let unity_asset_bundle = read_unity_asset_bundle_with_options(&file, &options)
+            .unwrap()
+            .1;
+        assert_eq!(
+            vec![
+                ("CAB-5813386f0ea15049abeb5a688d9031d3".to_string(), 4512),
+                (
+                    "CAB-5813386f0ea15049abeb5a688d9031d3.resS".to_string(),
+                    65536
+                ),
+            ],
+            unity_asset_bundle.file_sizes()
+        );
+    }
+
+    #[test]
+    fn test_blocks_info_alignment() {
+        let file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 0);
+        // the 49 bytes of headers padded to 56, as a build aligning to 8 bytes would write
+        let mut aligned = file[..49].to_vec();
+        aligned[8..12].copy_from_slice(&7u32.to_be_bytes());
+        aligned.extend_from_slice(&[0; 7]);
+        aligned.extend_from_slice(&file[49..]);
+
+        let options = ParseOptions {
+            alignment: 8,
+            ..Default::default()
+        };
+        let (rest, unity_asset_bundle) =
+            read_unity_asset_bundle_with_options(&aligned, &options).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+        assert_eq!(56, unity_asset_bundle.blocks_info_range().start);
+        assert!(read_unity_asset_bundle(&aligned).is_err());
+    }
+
+    #[test]
+    fn test_version_7_multi_node_directory() {
+        // odd path lengths, so any padding after a path would shift every later node
+        let files: [(&str, &[u8], u32); 3] = [
+            ("CAB-a", b"first", 4),
+            ("CAB-a.resS", b"second!", 0),
+            ("CAB-bcd.resource", b"third", 0),
+        ];
+        let file = build_uncompressed_bundle(&files, 0);
+        // the 49 bytes of headers padded to 64, as Unity writes version 7 bundles
+        let mut aligned = file[..49].to_vec();
+        aligned[8..12].copy_from_slice(&7u32.to_be_bytes());
+        aligned.extend_from_slice(&[0; 15]);
+        aligned.extend_from_slice(&file[49..]);
+
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle(&aligned).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(64, unity_asset_bundle.blocks_info_range().start);
+        let mut offset = 0;
+        for ((path, body, flags), (node, stream_file)) in files.iter().zip(
+            unity_asset_bundle
+                .directory_info
+                .iter()
+                .zip(unity_asset_bundle.stream_files.iter()),
+        ) {
+            assert_eq!(
+                &UnityNode {
+                    offset,
+                    size: body.len() as i64,
+                    flags: *flags,
+                    path: path.to_string(),
+                },
+                node
+            );
+            assert_eq!(body.to_vec(), stream_file.body);
+            offset += body.len() as i64;
+        }
+        assert_eq!(3, unity_asset_bundle.stream_files.len());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_multi_block_bundle() {
+        use crate::compression::compress;
+
+        let data = (0..1000u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut blocks = vec![];
+        let mut block_info = vec![0u8; 16];
+        block_info.extend_from_slice(&3i32.to_be_bytes());
+        for chunk in data.chunks(1500) {
+            let block = compress(chunk, CompressionType::Lz4).unwrap();
+            block_info.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            block_info.extend_from_slice(&(block.len() as u32).to_be_bytes());
+            block_info.extend_from_slice(&2u16.to_be_bytes());
+            blocks.extend_from_slice(&block);
+        }
+        block_info.extend_from_slice(&2i32.to_be_bytes());
+        for (offset, size, path) in [(0i64, 10i64, "CAB-a\0"), (10, 3990, "CAB-b\0")] {
+            block_info.extend_from_slice(&offset.to_be_bytes());
+            block_info.extend_from_slice(&size.to_be_bytes());
+            block_info.extend_from_slice(&4u32.to_be_bytes());
+            block_info.extend_from_slice(path.as_bytes());
+        }
+
+        let mut file = b"UnityFS\0".to_vec();
+        file.extend_from_slice(&6u32.to_be_bytes());
+        file.extend_from_slice(b"5.x.x\0");
+        file.extend_from_slice(b"2019.4.1f1\0");
+        let size = file.len() + 20 + block_info.len() + blocks.len();
+        file.extend_from_slice(&(size as i64).to_be_bytes());
+        file.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+        file.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+        file.extend_from_slice(&0x40u32.to_be_bytes());
+        file.extend_from_slice(&block_info);
+        file.extend_from_slice(&blocks);
+
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(3, unity_asset_bundle.storage_blocks.len());
+        assert_eq!(&data[..10], &unity_asset_bundle.stream_files[0].body[..]);
+        assert_eq!(&data[10..], &unity_asset_bundle.stream_files[1].body[..]);
+    }
+
+    #[test]
+    fn test_read_negative_node_size() {
+        let mut file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 0);
+        // the node's size follows its offset, 34 bytes into the blocks info at 49
+        file[49 + 34 + 8..49 + 34 + 16].copy_from_slice(&(-1i64).to_be_bytes());
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::OffsetOverflow(-1)))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_little_endian_version() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut swapped = file.clone();
+        swapped[8..12].copy_from_slice(&6u32.to_le_bytes());
+        assert!(read_unity_asset_bundle(&swapped).is_err());
+
+        let options = ParseOptions {
+            swapped_version: true,
+            ..Default::default()
+        };
+        let swapped_bundle = read_unity_asset_bundle_with_options(&swapped, &options)
+            .unwrap()
+            .1;
+        assert_eq!(6, swapped_bundle.header.version);
+        assert!(swapped_bundle.header.version_swapped);
+        assert_eq!(unity_asset_bundle.stream_files, swapped_bundle.stream_files);
+
+        let standard_bundle = read_unity_asset_bundle_with_options(&file, &options)
+            .unwrap()
+            .1;
+        assert!(!standard_bundle.header.version_swapped);
+    }
+
+    #[test]
+    fn test_read_negative_bundle_size() {
+        let mut file = read_file("./item_icon_00000");
+        // the container header starts right after the 29 bytes of bundle header
+        file[29..37].copy_from_slice(&(-4465i64).to_be_bytes());
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::OffsetOverflow(-4465)))
+        ));
+    }
+
+    #[test]
+    fn test_read_lzham_block_is_unsupported() {
+        let file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 4);
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::UnsupportedCompression(4)))
+        ));
+    }
+
+    struct XorDecryptor(u8);
+
+    impl Decryptor for XorDecryptor {
+        fn decrypt(&self, block: &mut [u8], index: usize) -> Result<(), UnityPackError> {
+            for byte in block.iter_mut() {
+                *byte ^= self.0 ^ index as u8;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_encrypted_blocks() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut encrypted = file.clone();
+        for byte in encrypted[unity_asset_bundle.blocks_offset..].iter_mut() {
+            *byte ^= 0x5A;
+        }
+        assert!(read_unity_asset_bundle(&encrypted).is_err());
+
+        let decryptor = XorDecryptor(0x5A);
+        let options = ParseOptions {
+            decryptor: Some(&decryptor),
+            ..Default::default()
+        };
+        let (rest, decrypted_bundle) =
+            read_unity_asset_bundle_with_options(&encrypted, &options).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            unity_asset_bundle.stream_files,
+            decrypted_bundle.stream_files
+        );
+    }
+
+    #[test]
+    fn test_read_block_with_unknown_compression() {
+        let file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 0x0D);
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::UnsupportedCompression(
+                0x0D
+            )))
+        ));
+        let options = ParseOptions {
+            skip_bodies: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            read_unity_asset_bundle_with_options(&file, &options),
+            Err(nom::Err::Failure(UnityPackError::UnsupportedCompression(
+                0x0D
+            )))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_node_compression_types() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(vec![0..70048], unity_asset_bundle.block_ranges());
+        for node in unity_asset_bundle.directory_info.iter() {
+            assert_eq!(
+                vec![CompressionType::Lz4Hc],
+                unity_asset_bundle.node_compression_types(node)
+            );
+        }
+
+        let node = UnityNode {
+            offset: i64::MAX,
+            size: 1,
+            ..Default::default()
+        };
+        assert!(unity_asset_bundle.node_compression_types(&node).is_empty());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_unity3d_extension() {
+        // nothing keys off the extension, a renamed copy opens the same
+        let path = std::env::temp_dir().join("unitypack_item_icon_00000.unity3d");
+        std::fs::copy("./item_icon_00000", &path).unwrap();
+        let unity_asset_bundle = UnityAssetBundle::open(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = UnityAssetBundle::open("./item_icon_00000").unwrap();
+        let unity_asset_bundle = unity_asset_bundle.unwrap();
+        assert_eq!(expected.header, unity_asset_bundle.header);
+        assert_eq!(expected.directory_info, unity_asset_bundle.directory_info);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+    }
+
+    #[cfg(all(feature = "bytes", feature = "lz4"))]
+    #[test]
+    fn test_into_bytes() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let stream_file = unity_asset_bundle.stream_files.remove(1);
+        let body_ptr = stream_file.body.as_ptr();
+        let body = stream_file.into_bytes();
+        let shared = body.clone();
+        assert_eq!(body_ptr, body.as_ptr());
+        assert_eq!(body.as_ptr(), shared.as_ptr());
+        assert_eq!(65536, shared.len());
+    }
+
+    #[cfg(all(feature = "sha2", feature = "lz4"))]
+    #[test]
+    fn test_file_digests() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let digests = unity_asset_bundle
+            .file_digests()
+            .into_iter()
+            .map(|(path, digest)| {
+                let hex = digest
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                (path, hex)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "CAB-5813386f0ea15049abeb5a688d9031d3",
+                    "a08da2765b96f08ce2213a01c6e4532f62bfcdefb0291e094d886aa4b87cce93".to_string()
+                ),
+                (
+                    "CAB-5813386f0ea15049abeb5a688d9031d3.resS",
+                    "3cc0acab59f0fa2f980d1dedb912b649bc89a73536d9e0f3c5ded304c2a0f05e".to_string()
+                ),
+            ],
+            digests
+        );
+    }
+
+    #[test]
+    fn test_read_signature_with_digits() {
+        let mut file = read_file("./item_icon_00000");
+        file.splice(7..7, b"2-beta".iter().copied());
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Error(UnityPackError::UnknownSignature(signature)))
+                if signature == "UnityFS2-beta"
+        ));
+        assert_eq!(None, sniff_bundle_format(&file));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_peek_header() {
+        let file = read_file("./item_icon_00000");
+        let header = peek_header(&file[..40]).unwrap();
+        assert_eq!("UnityFS", header.signature);
+        assert_eq!(6, header.version);
+        assert_eq!("5.x.x", header.unity_version);
+        assert_eq!("2019.4.1f1", header.unity_revision);
+        assert_eq!(read_unity_asset_bundle(&file).unwrap().1.header, header);
+        assert!(matches!(
+            peek_header(&file[..20]),
+            Err(UnityPackError::Parse { offset: 18, .. })
+        ));
+        assert!(matches!(
+            peek_header(b"NotABundle\0"),
+            Err(UnityPackError::UnknownSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_sniff_unity_archive() {
+        let file = read_file("./item_icon_00000");
+        assert_eq!(Some(BundleFormat::UnityFS), sniff_bundle_format(&file));
+        assert_eq!(None, sniff_bundle_format(b"NotABundle\0"));
+
+        let mut file = b"UnityArchive\0".to_vec();
+        file.extend_from_slice(&6u32.to_be_bytes());
+        file.extend_from_slice(b"5.x.x\0");
+        file.extend_from_slice(b"2019.4.1f1\0");
+        let size = file.len() + 20;
+        file.extend_from_slice(&(size as i64).to_be_bytes());
+        file.extend_from_slice(&0u32.to_be_bytes());
+        file.extend_from_slice(&0u32.to_be_bytes());
+        file.extend_from_slice(&0x40u32.to_be_bytes());
+        assert_eq!(Some(BundleFormat::UnityArchive), sniff_bundle_format(&file));
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::UnsupportedBundleFormat(
+                BundleFormat::UnityArchive
+            )))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_embedded_bundle() {
+        let file = read_file("./item_icon_00000");
+        let mut embedded = b"PK\x03\x04 not a bundle ".to_vec();
+        let offset = embedded.len();
+        embedded.extend_from_slice(&file);
+        embedded.extend_from_slice(b"trailer");
+
+        assert_eq!(vec![offset], find_unityfs_offsets(&embedded));
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle_at(&embedded, offset).unwrap();
+        assert_eq!(b"trailer", rest);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+        assert_eq!(
+            expected.blocks_info_range(),
+            unity_asset_bundle.blocks_info_range()
+        );
+
+        // errors point into the surrounding buffer
+        assert!(matches!(
+            read_unity_asset_bundle_at(&embedded[..offset + 60], offset),
+            Err(nom::Err::Error(UnityPackError::Parse { offset: o, .. })) if o == offset + 49
+        ));
+        assert!(read_unity_asset_bundle_at(&embedded, embedded.len() + 1).is_err());
+        assert!(find_unityfs_offsets(b"UnityFS").is_empty());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_custom_data() {
+        // item_icon_00000 with 24 bytes appended and the container size grown to match
+        let file = read_file("./item_icon_00000_custom_data");
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle(&file).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            Some(&b"pipeline:build=20261014\n"[..]),
+            unity_asset_bundle.custom_data()
+        );
+        let expected = read_unity_asset_bundle(&read_file("./item_icon_00000"))
+            .unwrap()
+            .1;
+        assert_eq!(None, expected.custom_data());
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+
+        let options = ParseOptions {
+            skip_bodies: true,
+            ..Default::default()
+        };
+        let (_, unity_asset_bundle) =
+            read_unity_asset_bundle_with_options(&file, &options).unwrap();
+        assert_eq!(Some(24), unity_asset_bundle.custom_data().map(<[u8]>::len));
+
+        // bytes past the declared size still belong to whatever follows the bundle
+        let mut appended = file.clone();
+        appended.extend_from_slice(b"trailer");
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle(&appended).unwrap();
+        assert_eq!(b"trailer", rest);
+        assert_eq!(Some(24), unity_asset_bundle.custom_data().map(<[u8]>::len));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_truncated_custom_data() {
+        let file = read_file("./item_icon_00000_custom_data");
+        assert!(matches!(
+            read_unity_asset_bundle(&file[..file.len() - 1]),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: 4465,
+                stage: Some(ParseStage::Blocks),
+            }))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_decompress_block() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let block = unity_asset_bundle.decompress_block(&file, 0).unwrap();
+        assert_eq!(70048, block.len());
+        assert_eq!(unity_asset_bundle[0].body[..], block[..4512]);
+        assert_eq!(unity_asset_bundle[1].body[..], block[4512..]);
+        assert!(matches!(
+            unity_asset_bundle.decompress_block(&file, 1),
+            Err(UnityPackError::BlockOutOfRange(1))
+        ));
+        assert!(matches!(
+            unity_asset_bundle.decompress_block(&file[..1000], 0),
+            Err(UnityPackError::Parse { offset: 1000, .. })
+        ));
+
+        // the blocks come before the blocks info here
+        let file = build_bundle_with_container_flags(&[("CAB-test", b"hello", 4)], 0, 0xC0);
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            b"hello".to_vec(),
+            unity_asset_bundle.decompress_block(&file, 0).unwrap()
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_decompress_block_with_options() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut encrypted = file.clone();
+        for byte in encrypted[unity_asset_bundle.blocks_offset..].iter_mut() {
+            *byte ^= 0x5A;
+        }
+        let decryptor = XorDecryptor(0x5A);
+        let options = ParseOptions {
+            decryptor: Some(&decryptor),
+            ..Default::default()
+        };
+        assert_eq!(
+            unity_asset_bundle.decompress_block(&file, 0).unwrap(),
+            unity_asset_bundle
+                .decompress_block_with_options(&encrypted, 0, &options)
+                .unwrap()
+        );
+
+        let file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 0);
+        let decompressor = PassthroughDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            ..Default::default()
+        };
+        let unity_asset_bundle = read_unity_asset_bundle_with_options(&file, &options)
+            .unwrap()
+            .1;
+        let calls = decompressor.calls.load(Ordering::Relaxed);
+        assert_eq!(
+            b"hello".to_vec(),
+            unity_asset_bundle
+                .decompress_block_with_options(&file, 0, &options)
+                .unwrap()
+        );
+        assert_eq!(calls + 1, decompressor.calls.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_blocks_covering() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            &unity_asset_bundle.storage_blocks[..],
+            unity_asset_bundle.blocks_covering(0..1)
+        );
+
+        unity_asset_bundle.storage_blocks = [10, 20, 30]
+            .iter()
+            .map(|&size| UnityStorageBlock {
+                uncompressed_size: size,
+                compressed_size: size,
+                flags: 0,
+            })
+            .collect();
+        let blocks = &unity_asset_bundle.storage_blocks;
+        // 0..10, 10..30, 30..60
+        assert_eq!(&blocks[1..2], unity_asset_bundle.blocks_covering(12..20));
+        assert_eq!(&blocks[1..2], unity_asset_bundle.blocks_covering(10..30));
+        assert_eq!(&blocks[0..2], unity_asset_bundle.blocks_covering(9..11));
+        assert_eq!(&blocks[..], unity_asset_bundle.blocks_covering(5..59));
+        assert_eq!(&blocks[2..], unity_asset_bundle.blocks_covering(30..100));
+        assert!(unity_asset_bundle.blocks_covering(60..70).is_empty());
+        assert!(unity_asset_bundle.blocks_covering(15..15).is_empty());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_blocks_spanned_by() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the sample keeps both files in its single block
+        let paths = unity_asset_bundle
+            .directory_info
+            .iter()
+            .map(|node| node.path.clone())
+            .collect::<Vec<_>>();
+        for path in paths.iter() {
+            assert_eq!(Some(1), unity_asset_bundle.blocks_spanned_by(path));
+        }
+        assert_eq!(None, unity_asset_bundle.blocks_spanned_by("CAB-missing"));
+
+        // 0..4000, 4000..8000, 8000..70048: the CAB at 0..4512 and the .resS at
+        // 4512..70048 both cross a boundary
+        unity_asset_bundle.storage_blocks = [4000, 4000, 62048]
+            .iter()
+            .map(|&size| UnityStorageBlock {
+                uncompressed_size: size,
+                compressed_size: size,
+                flags: 0,
+            })
+            .collect();
+        assert_eq!(Some(2), unity_asset_bundle.blocks_spanned_by(&paths[0]));
+        assert_eq!(Some(2), unity_asset_bundle.blocks_spanned_by(&paths[1]));
+
+        unity_asset_bundle.directory_info[0].size = 0;
+        assert_eq!(Some(0), unity_asset_bundle.blocks_spanned_by(&paths[0]));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_display_unity_asset_bundle() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            "UnityFS v6 (5.x.x / 2019.4.1f1) (1 blocks, 2 files)",
+            unity_asset_bundle.to_string()
+        );
+        assert_eq!(
+            "UnityFS v6 (5.x.x / 2019.4.1f1)",
+            unity_asset_bundle.header.to_string()
+        );
+        assert_eq!(
+            "4465 bytes (blocks info 85/153 bytes, flags=0x43)",
+            unity_asset_bundle.container_header.to_string()
+        );
+        assert_eq!(
+            "4331/70048 bytes (flags=0x3)",
+            unity_asset_bundle.storage_blocks[0].to_string()
+        );
+        assert_eq!(
+            "CAB-5813386f0ea15049abeb5a688d9031d3 (4512 bytes, flags=4)",
+            unity_asset_bundle.directory_info[0].to_string()
+        );
+        assert_eq!(
+            "CAB-5813386f0ea15049abeb5a688d9031d3.resS (65536 bytes)",
+            unity_asset_bundle.stream_files[1].to_string()
+        );
+    }
+
+    #[test]
+    fn test_read_header_with_empty_revision() {
+        let mut header = b"UnityFS\0".to_vec();
+        header.extend_from_slice(&6u32.to_be_bytes());
+        header.extend_from_slice(b"5.x.x\0\0");
+        header.extend_from_slice(&4465i64.to_be_bytes());
+        let (rest, header) =
+            read_unity_asset_bundle_header(&header, &ParseOptions::default()).unwrap();
+        assert_eq!("5.x.x", header.unity_version);
+        assert_eq!("", header.unity_revision);
+        assert_eq!(&4465i64.to_be_bytes(), rest);
+    }
+
+    #[test]
+    fn test_read_header_with_carriage_return() {
+        let mut header = b"UnityFS\0".to_vec();
+        header.extend_from_slice(&7u32.to_be_bytes());
+        header.extend_from_slice(b"5.x.x\0");
+        header.extend_from_slice(b"2019.4.1f1\r \0");
+        let (rest, header) =
+            read_unity_asset_bundle_header(&header, &ParseOptions::default()).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!("2019.4.1f1", header.unity_revision);
+        assert_eq!("2019.4.1f1\r ", header.raw_unity_revision);
+        assert_eq!("5.x.x", header.raw_unity_version);
+        // the padding still follows the 51 bytes of headers as stored
+        assert_eq!(13, blocks_info_padding(&header, &ParseOptions::default()));
+    }
+
+    #[test]
+    fn test_read_header_with_missing_terminator() {
+        let mut header = b"UnityFS\0".to_vec();
+        header.extend_from_slice(&6u32.to_be_bytes());
+        header.extend_from_slice(b"5.x.x\0");
+        header.extend_from_slice(&[b'1'; 128]);
+        header.push(0);
+        assert!(matches!(
+            read_unity_asset_bundle(&header),
+            Err(nom::Err::Error(UnityPackError::Parse { offset: 18, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_reports_absolute_offset() {
+        let file = read_file("./item_icon_00000");
+        // the blocks info starts right after the 49 bytes of headers
+        assert!(matches!(
+            read_unity_asset_bundle(&file[..60]),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: 49,
+                stage: Some(ParseStage::BlocksInfo),
+            }))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_bundle_with_bom() {
+        let file = read_file("./item_icon_00000");
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+        let mut bom_file = b"\xEF\xBB\xBF".to_vec();
+        bom_file.extend_from_slice(&file);
+
+        assert!(matches!(
+            read_unity_asset_bundle(&bom_file),
+            Err(nom::Err::Failure(UnityPackError::UnexpectedBom))
+        ));
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let unity_asset_bundle = read_unity_asset_bundle_with_options(&bom_file, &options)
+            .unwrap()
+            .1;
+        assert_eq!(expected.header, unity_asset_bundle.header);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+
+        // the BOM must be complete, anything else still fails on the signature
+        assert!(matches!(
+            read_unity_asset_bundle_with_options(&bom_file[1..], &options),
+            Err(nom::Err::Error(UnityPackError::UnknownSignature(_)))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_resource_streams() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert!(unity_asset_bundle.directory_info[0].is_serialized_file());
+        assert!(unity_asset_bundle.directory_info[1].is_resource());
+
+        let resources = unity_asset_bundle.resource_streams();
+        assert_eq!(1, resources.len());
+        assert_eq!(
+            "CAB-5813386f0ea15049abeb5a688d9031d3.resS",
+            resources[0].path
+        );
+        assert_eq!(
+            Some(resources[0]),
+            unity_asset_bundle.resource_by_name("CAB-5813386f0ea15049abeb5a688d9031d3.resS")
+        );
+        assert_eq!(
+            Some(resources[0]),
+            unity_asset_bundle.resource_by_name(
+                "archive:/CAB-5813386f0ea15049abeb5a688d9031d3/CAB-5813386f0ea15049abeb5a688d9031d3.resS"
+            )
+        );
+        // serialized files are not resources
+        assert_eq!(
+            None,
+            unity_asset_bundle.resource_by_name("CAB-5813386f0ea15049abeb5a688d9031d3")
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_from_bytes_owned() {
+        let file = read_file("./item_icon_00000");
+        let owned = UnityAssetBundle::from_bytes_owned(file.clone()).unwrap();
+        assert_eq!(2, owned.stream_files.len());
+        assert_eq!(4512, owned[0].body.len());
+        assert_eq!(file.as_slice(), owned.bytes());
+        assert_eq!(&file[49..134], owned.blocks_info_bytes());
+        // the compressed blocks stay readable without handing the file back in
+        let block = owned.decompress_block(0).unwrap();
+        assert_eq!(owned[0].body, block[..4512]);
+        assert_eq!(
+            owned[1].body,
+            owned
+                .lazy()
+                .unwrap()
+                .get_file_cached(
+                    &owned[1].path,
+                    &mut crate::block_cache::BlockCache::with_max_blocks(1)
+                )
+                .unwrap()
+        );
+        let (bytes, unity_asset_bundle) = owned.into_parts();
+        assert_eq!(file, bytes);
+        assert_eq!(2, unity_asset_bundle.stream_files.len());
+        assert!(matches!(
+            UnityAssetBundle::from_bytes_owned(b"UnityFS\0".to_vec()),
+            Err(UnityPackError::Parse { .. })
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_open() {
+        let unity_asset_bundle = UnityAssetBundle::open("./item_icon_00000").unwrap();
+        assert_eq!(2, unity_asset_bundle.stream_files.len());
+        assert_eq!(4512, unity_asset_bundle[0].body.len());
+        assert!(matches!(
+            UnityAssetBundle::open("./does_not_exist"),
+            Err(UnityPackError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[cfg(all(feature = "mmap", feature = "lz4"))]
+    #[test]
+    fn test_open_mmap() {
+        for path in ["./item_icon_00000", "./item_icon_00000_custom_data"] {
+            let mapped = UnityAssetBundle::open_mmap(path).unwrap();
+            let read = UnityAssetBundle::open(path).unwrap();
+            assert_eq!(read.header, mapped.header);
+            assert_eq!(read.container_header, mapped.container_header);
+            assert_eq!(read.storage_blocks, mapped.storage_blocks);
+            assert_eq!(read.directory_info, mapped.directory_info);
+            assert_eq!(read.stream_files, mapped.stream_files);
+            assert_eq!(read.blocks_info_range, mapped.blocks_info_range);
+            assert_eq!(read.custom_data, mapped.custom_data);
+        }
+        assert!(matches!(
+            UnityAssetBundle::open_mmap("./does_not_exist"),
+            Err(UnityPackError::Io(_))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_total_compressed_size() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // everything after the 49 bytes of headers
+        assert_eq!(
+            (file.len() - unity_asset_bundle.blocks_info_range().start) as u64,
+            unity_asset_bundle.total_compressed_size()
+        );
+        assert_eq!(4416, unity_asset_bundle.total_compressed_size());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_serialized_files() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_files = unity_asset_bundle.serialized_files();
+        assert_eq!(vec![&unity_asset_bundle.stream_files[0]], serialized_files);
+        assert_eq!(
+            "CAB-5813386f0ea15049abeb5a688d9031d3",
+            serialized_files[0].path
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_object_class_histogram() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // one AssetBundle and one Texture2D; the .resS node is not a serialized file
+        let expected = [(142, 1), (28, 1)]
+            .iter()
+            .copied()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(expected, unity_asset_bundle.object_class_histogram());
+
+        let file = read_file("./anm_chara_tear_animator");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let expected = [(91, 1), (142, 1), (74, 1)]
+            .iter()
+            .copied()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(expected, unity_asset_bundle.object_class_histogram());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_sort_stream_files_by_path() {
+        let file = read_file("./item_icon_00000");
+        let mut files = read_unity_asset_bundle(&file).unwrap().1.stream_files;
+        files.reverse();
+        files.push(UnityStreamFile {
+            path: "CAB-0".to_string(),
+            file_name: "CAB-0".to_string(),
+            body: vec![0xFF; 8],
+        });
+        files.sort();
+        assert_eq!(
+            vec![
+                "CAB-0",
+                "CAB-5813386f0ea15049abeb5a688d9031d3",
+                "CAB-5813386f0ea15049abeb5a688d9031d3.resS"
+            ],
+            files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_try_parse_serialized_files() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let body = &unity_asset_bundle.stream_files[0].body;
+        let file = build_uncompressed_bundle(
+            &[
+                ("CAB-good", body, 4),
+                ("CAB-corrupt", &body[..30], 4),
+                ("CAB-good.resS", b"pixels", 0),
+            ],
+            0,
+        );
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let results = unity_asset_bundle.try_parse_serialized_files();
+        assert_eq!(2, results.len());
+        assert_eq!("CAB-good", results[0].0);
+        assert_eq!(2, results[0].1.as_ref().unwrap().objects.len());
+        assert_eq!("CAB-corrupt", results[1].0);
+        assert!(matches!(results[1].1, Err(UnityPackError::Parse { .. })));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_serialized_file_versions() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            vec!["2019.4.1f1"],
+            unity_asset_bundle.serialized_file_versions()
+        );
+
+        let body = &unity_asset_bundle.stream_files[0].body;
+        // the version string starts right after the 20-byte header of a version 21 file
+        let mut rebuilt = body.clone();
+        rebuilt[20..30].copy_from_slice(b"2020.3.0f1");
+        let file = build_uncompressed_bundle(
+            &[
+                ("CAB-a", body, 4),
+                ("CAB-b", &rebuilt, 4),
+                ("CAB-c", body, 4),
+                ("CAB-d", &body[..30], 4),
+            ],
+            0,
+        );
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            vec!["2019.4.1f1", "2020.3.0f1"],
+            unity_asset_bundle.serialized_file_versions()
+        );
+    }
+
+    #[test]
+    fn test_read_bundle_without_blocks_info() {
+        let mut file = b"UnityFS\0".to_vec();
+        file.extend_from_slice(&6u32.to_be_bytes());
+        file.extend_from_slice(b"5.x.x\0");
+        file.extend_from_slice(b"2019.4.1f1\0");
+        let size = file.len() + 20;
+        file.extend_from_slice(&(size as i64).to_be_bytes());
+        file.extend_from_slice(&0u32.to_be_bytes());
+        file.extend_from_slice(&0u32.to_be_bytes());
+        // LZ4HC blocks info, combined with the directory
+        file.extend_from_slice(&0x43u32.to_be_bytes());
+
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle(&file).unwrap();
+        assert!(rest.is_empty());
+        assert!(unity_asset_bundle.storage_blocks.is_empty());
+        assert!(unity_asset_bundle.directory_info.is_empty());
+        assert!(unity_asset_bundle.stream_files.is_empty());
+        assert_eq!(Ok(()), unity_asset_bundle.validate());
+
+        // an empty LZ4 storage block decodes to nothing instead of failing
+        let file = build_uncompressed_bundle(&[("CAB-empty", b"", 4)], 2);
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            vec![UnityStorageBlock {
+                compressed_size: 0,
+                uncompressed_size: 0,
+                flags: 2
+            }],
+            unity_asset_bundle.storage_blocks
+        );
+        assert!(unity_asset_bundle.stream_files[0].body.is_empty());
+    }
+
+    #[test]
+    fn test_blocks_info_layout_flags() {
+        let files: &[(&str, &[u8], u32)] = &[("CAB-test", b"hello", 4)];
+        for container_flags in [0x40, 0xC0] {
+            let file = build_bundle_with_container_flags(files, 0, container_flags);
+            let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+            assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+        }
+        for container_flags in [0x00, 0x80] {
+            let file = build_bundle_with_container_flags(files, 0, container_flags);
+            assert!(matches!(
+                read_unity_asset_bundle(&file),
+                Err(nom::Err::Failure(UnityPackError::UnsupportedContainerFlags(flags)))
+                    if flags == container_flags
+            ));
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_unknown_container_flags() {
+        let file = build_bundle_with_container_flags(&[("CAB-test", b"hello", 4)], 0, 0x1040);
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            read_unity_asset_bundle_with_options(&file, &options),
+            Err(nom::Err::Failure(UnityPackError::UnknownFlags(0x1000)))
+        ));
+        let file = read_file("./item_icon_00000");
+        assert!(read_unity_asset_bundle_with_options(&file, &options).is_ok());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_blocks_padding() {
+        // item_icon_00000 with kArchiveBlocksInfoNeedPaddingAtStart set and 10 zero bytes
+        // moving the first block from 134 to 144
+        let file = read_file("./item_icon_00000_padded");
+        let expected = read_unity_asset_bundle(&read_file("./item_icon_00000"))
+            .unwrap()
+            .1;
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let (rest, unity_asset_bundle) =
+            read_unity_asset_bundle_with_options(&file, &options).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(144, unity_asset_bundle.blocks_offset);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+        assert!(matches!(
+            read_unity_asset_bundle(&file[..140]),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: 134,
+                stage: Some(ParseStage::BlocksInfo),
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_blocks_padding_with_blocks_info_at_the_end() {
+        let files: &[(&str, &[u8], u32)] =
+            &[("CAB-test", b"hello", 4), ("CAB-test.resS", b"world", 0)];
+        for container_flags in [0x240, 0x2C0] {
+            let file = build_bundle_with_container_flags(files, 0, container_flags);
+            let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+            assert_eq!(0, unity_asset_bundle.blocks_offset % 16);
+            assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+            assert_eq!(b"world".to_vec(), unity_asset_bundle.stream_files[1].body);
+        }
+    }
+
+    #[test]
+    fn test_blocks_info_at_the_end_followed_by_junk() {
+        let mut file = build_bundle_with_container_flags(&[("CAB-test", b"hello", 4)], 0, 0xC0);
+        let bundle_len = file.len();
+        file.extend_from_slice(&[0; 64]);
+        let (rest, unity_asset_bundle) = read_unity_asset_bundle(&file).unwrap();
+        assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+        assert_eq!(bundle_len, unity_asset_bundle.blocks_info_range().end);
+        assert_eq!(&[0; 64][..], rest);
+    }
+
+    #[test]
+    fn test_concatenated_blocks_info_at_the_end() {
+        let mut file = build_bundle_with_container_flags(&[("CAB-first", b"hello", 4)], 0, 0xC0);
+        file.extend(build_bundle_with_container_flags(
+            &[("CAB-second", b"world!", 4)],
+            0,
+            0xC0,
+        ));
+        let offsets = find_unityfs_offsets(&file);
+        assert_eq!(2, offsets.len());
+        let bundles: Vec<_> = offsets
+            .iter()
+            .map(|offset| read_unity_asset_bundle_at(&file, *offset).unwrap().1)
+            .collect();
+        assert_eq!("CAB-first", bundles[0].stream_files[0].path);
+        assert_eq!(b"hello".to_vec(), bundles[0].stream_files[0].body);
+        assert_eq!("CAB-second", bundles[1].stream_files[0].path);
+        assert_eq!(b"world!".to_vec(), bundles[1].stream_files[0].body);
+    }
+
+    #[test]
+    fn test_oversized_blocks_info_at_the_end() {
+        let mut file = build_bundle_with_container_flags(&[("CAB-test", b"hello", 4)], 0, 0xC0);
+        // compressed_blocks_info_size comes right after the 64-bit bundle size
+        file[37..41].copy_from_slice(&0x10000u32.to_be_bytes());
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: 49,
+                stage: Some(ParseStage::BlocksInfo),
+            }))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_blocks_info_range() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the 85 bytes right after the 49 bytes of headers
+        assert_eq!(49..134, unity_asset_bundle.blocks_info_range());
+
+        let file = build_bundle_with_container_flags(&[("CAB-test", b"hello", 4)], 0, 0xC0);
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let range = unity_asset_bundle.blocks_info_range();
+        assert_eq!(file.len(), range.end);
+        assert_eq!(
+            unity_asset_bundle
+                .container_header
+                .compressed_blocks_info_size as usize,
+            range.len()
+        );
+    }
+
+    #[test]
+    fn test_read_huge_counts() {
+        let file = build_uncompressed_bundle(&[("CAB-test", b"hello", 4)], 0);
+        // the blocks info starts after 49 bytes of headers with a 16 byte hash
+        for count_offset in [49 + 16, 49 + 20 + 10] {
+            let mut file = file.clone();
+            file[count_offset..count_offset + 4].copy_from_slice(&i32::MAX.to_be_bytes());
+            assert!(read_unity_asset_bundle(&file).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_non_utf8_path() {
+        let mut file = build_uncompressed_bundle(&[("CAB-abcdef", b"hello", 4)], 0);
+        // swap in "テスト" encoded as Shift_JIS, the blocks info is stored uncompressed
+        let position = file.windows(10).position(|w| w == b"CAB-abcdef").unwrap();
+        file[position + 4..position + 10].copy_from_slice(b"\x83\x65\x83\x58\x83\x67");
+
+        assert!(matches!(
+            read_unity_asset_bundle(&file),
+            Err(nom::Err::Failure(UnityPackError::Utf8(_)))
+        ));
+
+        #[cfg(feature = "encoding")]
+        {
+            let options = ParseOptions {
+                path_encoding: Some(encoding_rs::SHIFT_JIS),
+                ..Default::default()
+            };
+            let unity_asset_bundle = read_unity_asset_bundle_with_options(&file, &options)
+                .unwrap()
+                .1;
+            assert_eq!("CAB-テスト", unity_asset_bundle.directory_info[0].path);
+            assert_eq!(b"hello".to_vec(), unity_asset_bundle.stream_files[0].body);
+        }
+    }
 }
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheLimit {
+    Blocks(usize),
+    Bytes(usize),
+}
+
+// least recently used blocks are evicted first
+#[derive(Debug)]
+pub struct BlockCache {
+    limit: CacheLimit,
+    entries: VecDeque<(usize, Vec<u8>)>,
+    cached_bytes: usize,
+    hits: usize,
+    misses: usize,
+}
+
+impl BlockCache {
+    pub fn new(limit: CacheLimit) -> Self {
+        BlockCache {
+            limit,
+            entries: VecDeque::new(),
+            cached_bytes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn with_max_blocks(max_blocks: usize) -> Self {
+        BlockCache::new(CacheLimit::Blocks(max_blocks))
+    }
+
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        BlockCache::new(CacheLimit::Bytes(max_bytes))
+    }
+
+    pub fn limit(&self) -> CacheLimit {
+        self.limit
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn cached_bytes(&self) -> usize {
+        self.cached_bytes
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cached_bytes = 0;
+    }
+
+    pub(crate) fn get(&mut self, index: usize) -> Option<&[u8]> {
+        let position = match self.entries.iter().position(|(i, _)| *i == index) {
+            Some(position) => position,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        self.hits += 1;
+        let entry = self.entries.remove(position).unwrap();
+        self.entries.push_back(entry);
+        self.entries.back().map(|(_, block)| block.as_slice())
+    }
+
+    pub(crate) fn insert(&mut self, index: usize, block: Vec<u8>) {
+        if !self.fits(1, block.len()) {
+            return;
+        }
+        if let Some(position) = self.entries.iter().position(|(i, _)| *i == index) {
+            let (_, old) = self.entries.remove(position).unwrap();
+            self.cached_bytes -= old.len();
+        }
+        while !self.fits(self.entries.len() + 1, self.cached_bytes + block.len()) {
+            let (_, evicted) = self.entries.pop_front().unwrap();
+            self.cached_bytes -= evicted.len();
+        }
+        self.cached_bytes += block.len();
+        self.entries.push_back((index, block));
+    }
+
+    fn fits(&self, blocks: usize, bytes: usize) -> bool {
+        match self.limit {
+            CacheLimit::Blocks(max_blocks) => blocks <= max_blocks,
+            CacheLimit::Bytes(max_bytes) => bytes <= max_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_cache::BlockCache;
+
+    #[test]
+    fn test_evicts_least_recently_used_block() {
+        let mut cache = BlockCache::with_max_blocks(2);
+        cache.insert(0, vec![0; 4]);
+        cache.insert(1, vec![1; 4]);
+        assert!(cache.get(0).is_some());
+        cache.insert(2, vec![2; 4]);
+        assert_eq!(Some(&[0u8; 4][..]), cache.get(0));
+        assert_eq!(None, cache.get(1));
+        assert_eq!(Some(&[2u8; 4][..]), cache.get(2));
+        assert_eq!((3, 1), (cache.hits(), cache.misses()));
+    }
+
+    #[test]
+    fn test_byte_limit() {
+        let mut cache = BlockCache::with_max_bytes(10);
+        cache.insert(0, vec![0; 6]);
+        cache.insert(1, vec![1; 6]);
+        assert_eq!(1, cache.len());
+        assert_eq!(6, cache.cached_bytes());
+        // blocks larger than the whole cache are never kept
+        cache.insert(2, vec![2; 11]);
+        assert_eq!(None, cache.get(2));
+        assert!(cache.get(1).is_some());
+    }
+}
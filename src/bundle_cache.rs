@@ -0,0 +1,99 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use lru::LruCache;
+
+use crate::asset_bundle::UnityAssetBundle;
+use crate::error::UnityPackError;
+
+// parsed bundles keyed by path, reparsed when the file's mtime changes
+pub struct BundleCache {
+    entries: LruCache<PathBuf, (SystemTime, Arc<UnityAssetBundle>)>,
+}
+
+impl BundleCache {
+    // a capacity of 0 is treated as 1
+    pub fn new(capacity: usize) -> Self {
+        BundleCache {
+            entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn get<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Arc<UnityAssetBundle>, UnityPackError> {
+        let path = path.as_ref();
+        let modified = std::fs::metadata(path)?.modified()?;
+        if let Some((cached_modified, bundle)) = self.entries.get(path) {
+            if *cached_modified == modified {
+                return Ok(Arc::clone(bundle));
+            }
+        }
+        let bundle = Arc::new(UnityAssetBundle::open(path)?);
+        self.entries
+            .put(path.to_path_buf(), (modified, Arc::clone(&bundle)));
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::bundle_cache::BundleCache;
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_get_reuses_unchanged_bundle() {
+        let dir = std::env::temp_dir().join("unitypack_bundle_cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("item_icon_00000");
+        std::fs::copy("./item_icon_00000", &path).unwrap();
+
+        let mut cache = BundleCache::new(4);
+        let first = cache.get(&path).unwrap();
+        let second = cache.get(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(1, cache.len());
+
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let third = cache.get(&path).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(first.stream_files, third.stream_files);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_missing_file() {
+        let mut cache = BundleCache::new(4);
+        assert!(cache.get("./missing_bundle").is_err());
+        assert!(cache.is_empty());
+    }
+}
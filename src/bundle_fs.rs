@@ -0,0 +1,103 @@
+use crate::asset_bundle::{UnityAssetBundle, UnityStreamFile, ARCHIVE_PREFIX};
+use crate::error::UnityPackError;
+
+// a read-only directory view over the stream files of a parsed bundle
+#[derive(Debug, Clone, Copy)]
+pub struct BundleFs<'a> {
+    bundle: &'a UnityAssetBundle,
+}
+
+impl<'a> BundleFs<'a> {
+    pub fn new(bundle: &'a UnityAssetBundle) -> Self {
+        BundleFs { bundle }
+    }
+
+    #[must_use]
+    pub fn list(&self) -> Vec<&'a str> {
+        self.bundle
+            .stream_files
+            .iter()
+            .map(|sf| sf.path.as_str())
+            .collect()
+    }
+
+    #[must_use]
+    pub fn exists(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    pub fn read(&self, path: &str) -> Result<&'a [u8], UnityPackError> {
+        self.find(path)
+            .map(|sf| sf.body.as_slice())
+            .ok_or_else(|| UnityPackError::FileNotFound(path.to_string()))
+    }
+
+    // archive:/CAB-xxxx/CAB-xxxx.resS names the file CAB-xxxx.resS inside the bundle CAB-xxxx
+    fn find(&self, path: &str) -> Option<&'a UnityStreamFile> {
+        let path = normalize(path);
+        let stream_files = &self.bundle.stream_files;
+        stream_files.iter().find(|sf| sf.path == path).or_else(|| {
+            let file_name = path.rsplit('/').next()?;
+            stream_files.iter().find(|sf| sf.path == file_name)
+        })
+    }
+}
+
+impl UnityAssetBundle {
+    #[must_use]
+    pub fn fs(&self) -> BundleFs<'_> {
+        BundleFs::new(self)
+    }
+}
+
+fn normalize(path: &str) -> &str {
+    let path = path.strip_prefix(ARCHIVE_PREFIX).unwrap_or(path);
+    path.trim_start_matches('/')
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::error::UnityPackError;
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_bundle_fs() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let fs = unity_asset_bundle.fs();
+        assert_eq!(
+            vec![
+                "CAB-5813386f0ea15049abeb5a688d9031d3",
+                "CAB-5813386f0ea15049abeb5a688d9031d3.resS"
+            ],
+            fs.list()
+        );
+
+        let resource = &unity_asset_bundle.stream_files[1].body[..];
+        assert_eq!(
+            resource,
+            fs.read("CAB-5813386f0ea15049abeb5a688d9031d3.resS")
+                .unwrap()
+        );
+        assert_eq!(
+            resource,
+            fs.read("archive:/CAB-5813386f0ea15049abeb5a688d9031d3/CAB-5813386f0ea15049abeb5a688d9031d3.resS")
+                .unwrap()
+        );
+        assert!(fs.exists("/CAB-5813386f0ea15049abeb5a688d9031d3"));
+    }
+
+    #[test]
+    fn test_bundle_fs_missing_path() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let fs = unity_asset_bundle.fs();
+        assert!(!fs.exists("archive:/CAB-missing/CAB-missing.resS"));
+        assert!(matches!(
+            fs.read("CAB-missing"),
+            Err(UnityPackError::FileNotFound(path)) if path == "CAB-missing"
+        ));
+    }
+}
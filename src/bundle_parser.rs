@@ -0,0 +1,496 @@
+use std::{io::Read, ops::Range};
+
+use nom::{
+    bytes::streaming::take, combinator::recognize, number::streaming::be_u32, sequence::tuple,
+    IResult,
+};
+
+use crate::asset_bundle::{
+    blocks_info_padding, blocks_padding, custom_data_size, read_blocks_info_and_directory,
+    read_files, read_unity_asset_bundle_header, read_unity_container_header, skip_bom,
+    ParseOptions, UnityAssetBundle, UnityAssetBundleHeader, UnityContainerHeader, UnityNode,
+    UnityStorageBlock, BOM,
+};
+use crate::error::{ParseStage, UnityPackError};
+use crate::{read_cstring_bytes, usize_from_i64, MAX_SIGNATURE_LENGTH, MAX_VERSION_STRING_LENGTH};
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ParseStatus {
+    NeedMore,
+    Done(UnityAssetBundle),
+}
+
+enum Stage {
+    Header,
+    Container(UnityAssetBundleHeader),
+    BlocksInfo(UnityAssetBundleHeader, UnityContainerHeader),
+    Blocks(
+        UnityAssetBundleHeader,
+        UnityContainerHeader,
+        Vec<UnityStorageBlock>,
+        Vec<UnityNode>,
+        Range<usize>,
+    ),
+    Done,
+}
+
+pub struct BundleParser<'a> {
+    options: ParseOptions<'a>,
+    buffer: Vec<u8>,
+    consumed: usize,
+    // past a skipped BOM, where the container size starts counting
+    bundle_start: usize,
+    stage: Stage,
+    forward_only: bool,
+}
+
+impl Default for BundleParser<'_> {
+    fn default() -> Self {
+        BundleParser::with_options(ParseOptions::default())
+    }
+}
+
+impl<'a> BundleParser<'a> {
+    pub fn new() -> Self {
+        BundleParser::default()
+    }
+
+    pub fn with_options(options: ParseOptions<'a>) -> Self {
+        BundleParser {
+            options,
+            buffer: vec![],
+            consumed: 0,
+            bundle_start: 0,
+            stage: Stage::Header,
+            forward_only: false,
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<ParseStatus, UnityPackError> {
+        self.buffer.extend_from_slice(chunk);
+        loop {
+            let input = &self.buffer[self.consumed..];
+            match std::mem::replace(&mut self.stage, Stage::Done) {
+                Stage::Header
+                    if self.consumed == 0 && input.len() < BOM.len() && BOM.starts_with(input) =>
+                {
+                    self.stage = Stage::Header;
+                    return Ok(ParseStatus::NeedMore);
+                }
+                Stage::Header if self.consumed == 0 && input.starts_with(BOM) => {
+                    skip_bom(input, &self.options).map_err(|e| locate(e, input, 0))?;
+                    self.consumed += BOM.len();
+                    self.bundle_start = self.consumed;
+                    self.stage = Stage::Header;
+                }
+                Stage::Header => match streaming(header_bytes(input)).map_err(|e| {
+                    e.locate(input.len(), self.consumed)
+                        .in_stage(ParseStage::Header)
+                })? {
+                    None => {
+                        self.stage = Stage::Header;
+                        return Ok(ParseStatus::NeedMore);
+                    }
+                    Some((_, bytes)) => {
+                        let (_, header) = read_unity_asset_bundle_header(bytes, &self.options)
+                            .map_err(|e| locate(e, bytes, self.consumed))?;
+                        self.consumed += bytes.len();
+                        self.stage = Stage::Container(header);
+                    }
+                },
+                Stage::Container(header) => match streaming(take(20usize)(input))? {
+                    None => {
+                        self.stage = Stage::Container(header);
+                        return Ok(ParseStatus::NeedMore);
+                    }
+                    Some((_, bytes)) => {
+                        let (_, container_header) = read_unity_container_header(bytes)
+                            .map_err(|e| locate(e, bytes, self.consumed))?;
+                        self.consumed += bytes.len();
+                        self.stage = Stage::BlocksInfo(header, container_header);
+                    }
+                },
+                Stage::BlocksInfo(_, container_header)
+                    if self.forward_only && (container_header.flags & 0x80) != 0 =>
+                {
+                    return Err(UnityPackError::BlocksInfoAtEnd);
+                }
+                Stage::BlocksInfo(header, container_header) => {
+                    let needed = if (container_header.flags & 0x80) != 0 {
+                        // kArchiveBlocksInfoAtTheEnd needs everything up to the end of the file
+                        usize_from_i64(container_header.size)?
+                            .saturating_sub(self.consumed - self.bundle_start)
+                    } else {
+                        let blocks_info_end = blocks_info_padding(&header, &self.options)
+                            + container_header.compressed_blocks_info_size as usize;
+                        blocks_info_end
+                            + blocks_padding(
+                                &container_header,
+                                self.consumed - self.bundle_start + blocks_info_end,
+                            )
+                    };
+                    match streaming(take(needed)(input))? {
+                        None => {
+                            self.stage = Stage::BlocksInfo(header, container_header);
+                            return Ok(ParseStatus::NeedMore);
+                        }
+                        Some((_, bytes)) => {
+                            let (rest, blocks_info) = read_blocks_info_and_directory(
+                                bytes,
+                                &header,
+                                &container_header,
+                                &self.options,
+                            )
+                            .map_err(|e| locate(e, bytes, self.consumed))?;
+                            let blocks_info_range = self.consumed + blocks_info.range.start
+                                ..self.consumed + blocks_info.range.end;
+                            self.consumed += bytes.len() - rest.len();
+                            self.stage = Stage::Blocks(
+                                header,
+                                container_header,
+                                blocks_info.storage_blocks,
+                                blocks_info.nodes,
+                                blocks_info_range,
+                            );
+                        }
+                    }
+                }
+                Stage::Blocks(
+                    header,
+                    container_header,
+                    storage_blocks,
+                    nodes,
+                    blocks_info_range,
+                ) => {
+                    let blocks_size = storage_blocks
+                        .iter()
+                        .map(|sb| sb.compressed_size as usize)
+                        .sum::<usize>();
+                    let custom_data_size = custom_data_size(
+                        &container_header,
+                        self.consumed + blocks_size - self.bundle_start,
+                    )?;
+                    match streaming(take(blocks_size + custom_data_size)(input))? {
+                        None => {
+                            self.stage = Stage::Blocks(
+                                header,
+                                container_header,
+                                storage_blocks,
+                                nodes,
+                                blocks_info_range,
+                            );
+                            return Ok(ParseStatus::NeedMore);
+                        }
+                        Some((_, bytes)) => {
+                            let blocks_offset = self.consumed;
+                            let (bytes, custom_data) = bytes.split_at(blocks_size);
+                            let (_, stream_files) =
+                                read_files(bytes, &storage_blocks, &nodes, &self.options)
+                                    .map_err(|e| locate(e, bytes, self.consumed))?;
+                            self.consumed += bytes.len() + custom_data.len();
+                            return Ok(ParseStatus::Done(UnityAssetBundle {
+                                header,
+                                container_header,
+                                storage_blocks,
+                                directory_info: nodes,
+                                stream_files,
+                                blocks_info_range,
+                                blocks_offset,
+                                custom_data: (!custom_data.is_empty())
+                                    .then(|| custom_data.to_vec()),
+                            }));
+                        }
+                    }
+                }
+                Stage::Done => {
+                    return Err(UnityPackError::Parse {
+                        kind: nom::error::ErrorKind::Eof,
+                        offset: self.consumed,
+                        stage: None,
+                    })
+                }
+            }
+        }
+    }
+}
+
+// reads strictly forward, so bundles that keep the blocks info at the end are rejected
+pub fn read_unity_asset_bundle_from_reader_forward<R: Read>(
+    reader: R,
+) -> Result<UnityAssetBundle, UnityPackError> {
+    read_unity_asset_bundle_from_reader_forward_with_options(reader, ParseOptions::default())
+}
+
+pub fn read_unity_asset_bundle_from_reader_forward_with_options<R: Read>(
+    mut reader: R,
+    options: ParseOptions,
+) -> Result<UnityAssetBundle, UnityPackError> {
+    let mut parser = BundleParser::with_options(options);
+    parser.forward_only = true;
+    let mut chunk = vec![0u8; 0x10000];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => {
+                return Err(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Eof,
+                    offset: parser.buffer.len(),
+                    stage: None,
+                })
+            }
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if let ParseStatus::Done(unity_asset_bundle) = parser.feed(&chunk[..read])? {
+            return Ok(unity_asset_bundle);
+        }
+    }
+}
+
+fn header_bytes(input: &[u8]) -> IResult<&[u8], &[u8], UnityPackError> {
+    recognize(tuple((
+        |i| streaming_cstring(i, MAX_SIGNATURE_LENGTH),
+        be_u32,
+        |i| streaming_cstring(i, MAX_VERSION_STRING_LENGTH),
+        |i| streaming_cstring(i, MAX_VERSION_STRING_LENGTH),
+    )))(input)
+}
+
+// waits for more input only while the terminator could still be within `max_len` bytes
+fn streaming_cstring(input: &[u8], max_len: usize) -> IResult<&[u8], &[u8], UnityPackError> {
+    match read_cstring_bytes(input, max_len) {
+        Err(nom::Err::Error(_)) if input.len() <= max_len => {
+            Err(nom::Err::Incomplete(nom::Needed::new(1)))
+        }
+        result => result,
+    }
+}
+
+fn locate(e: nom::Err<UnityPackError>, input: &[u8], base: usize) -> UnityPackError {
+    UnityPackError::from(e).locate(input.len(), base)
+}
+
+fn streaming<O>(
+    result: IResult<&[u8], O, UnityPackError>,
+) -> Result<Option<(&[u8], O)>, UnityPackError> {
+    match result {
+        Ok(result) => Ok(Some(result)),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e),
+    }
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use std::io::Read;
+
+    use crate::asset_bundle::{read_unity_asset_bundle, ParseOptions};
+    use crate::bundle_parser::{
+        read_unity_asset_bundle_from_reader_forward, BundleParser, ParseStatus,
+    };
+    use crate::error::{ParseStage, UnityPackError};
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_bundle_parser_feed_one_byte_at_a_time() {
+        let file = read_file("./item_icon_00000");
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+
+        let mut parser = BundleParser::new();
+        let (last, rest) = file.split_last().unwrap();
+        for byte in rest {
+            assert!(matches!(
+                parser.feed(&[*byte]).unwrap(),
+                ParseStatus::NeedMore
+            ));
+        }
+        let unity_asset_bundle = match parser.feed(&[*last]).unwrap() {
+            ParseStatus::Done(unity_asset_bundle) => unity_asset_bundle,
+            ParseStatus::NeedMore => panic!("bundle should be complete"),
+        };
+        assert_eq!(expected.header, unity_asset_bundle.header);
+        assert_eq!(
+            expected.container_header,
+            unity_asset_bundle.container_header
+        );
+        assert_eq!(expected.storage_blocks, unity_asset_bundle.storage_blocks);
+        assert_eq!(expected.directory_info, unity_asset_bundle.directory_info);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+        assert_eq!(
+            expected.blocks_info_range(),
+            unity_asset_bundle.blocks_info_range()
+        );
+    }
+
+    #[test]
+    fn test_bundle_parser_blocks_padding() {
+        let file = read_file("./item_icon_00000_padded");
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+        let mut parser = BundleParser::new();
+        // the padding is needed before the first block can be read
+        assert!(matches!(
+            parser.feed(&file[..140]).unwrap(),
+            ParseStatus::NeedMore
+        ));
+        let unity_asset_bundle = match parser.feed(&file[140..]).unwrap() {
+            ParseStatus::Done(unity_asset_bundle) => unity_asset_bundle,
+            ParseStatus::NeedMore => panic!("bundle should be complete"),
+        };
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+        assert_eq!(144, unity_asset_bundle.blocks_offset);
+    }
+
+    #[test]
+    fn test_bundle_parser_reads_custom_data() {
+        let file = read_file("./item_icon_00000_custom_data");
+        let mut parser = BundleParser::new();
+        // the storage blocks alone are not enough while custom data is still due
+        assert!(matches!(
+            parser.feed(&file[..4465]).unwrap(),
+            ParseStatus::NeedMore
+        ));
+        let unity_asset_bundle = match parser.feed(&file[4465..]).unwrap() {
+            ParseStatus::Done(unity_asset_bundle) => unity_asset_bundle,
+            ParseStatus::NeedMore => panic!("bundle should be complete"),
+        };
+        assert_eq!(
+            Some(&b"pipeline:build=20261014\n"[..]),
+            unity_asset_bundle.custom_data()
+        );
+    }
+
+    #[test]
+    fn test_bundle_parser_skips_bom_when_lenient() {
+        let file = read_file("./item_icon_00000");
+        let mut bom_file = b"\xEF\xBB\xBF".to_vec();
+        bom_file.extend_from_slice(&file);
+
+        let mut parser = BundleParser::new();
+        assert!(matches!(
+            parser.feed(&bom_file[..2]),
+            Ok(ParseStatus::NeedMore)
+        ));
+        assert!(matches!(
+            parser.feed(&bom_file[2..]),
+            Err(UnityPackError::UnexpectedBom)
+        ));
+
+        let mut parser = BundleParser::with_options(ParseOptions {
+            lenient: true,
+            ..Default::default()
+        });
+        assert!(matches!(
+            parser.feed(&bom_file[..2]),
+            Ok(ParseStatus::NeedMore)
+        ));
+        assert!(matches!(
+            parser.feed(&bom_file[2..]),
+            Ok(ParseStatus::Done(_))
+        ));
+    }
+
+    #[test]
+    fn test_bundle_parser_bounds_header_strings() {
+        let mut parser = BundleParser::new();
+        assert!(matches!(
+            parser.feed(&[b'U'; 32]).unwrap(),
+            ParseStatus::NeedMore
+        ));
+        assert!(matches!(
+            parser.feed(b"U"),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::TakeUntil,
+                offset: 0,
+                stage: Some(ParseStage::Header),
+            })
+        ));
+
+        let mut header = b"UnityFS\0".to_vec();
+        header.extend_from_slice(&6u32.to_be_bytes());
+        header.extend_from_slice(&[b'5'; 64]);
+        let mut parser = BundleParser::new();
+        assert!(matches!(
+            parser.feed(&header).unwrap(),
+            ParseStatus::NeedMore
+        ));
+        assert!(matches!(
+            parser.feed(b"5"),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::TakeUntil,
+                offset: 12,
+                stage: Some(ParseStage::Header),
+            })
+        ));
+    }
+
+    #[test]
+    fn test_bundle_parser_blocks_info_at_the_end_after_bom() {
+        let file = read_file("./item_icon_00000");
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+        // the same bundle with kArchiveBlocksInfoAtTheEnd, behind a BOM
+        let mut bom_file = b"\xEF\xBB\xBF".to_vec();
+        bom_file.extend_from_slice(&file[..49]);
+        bom_file.extend_from_slice(&file[134..]);
+        bom_file.extend_from_slice(&file[49..134]);
+        bom_file[3 + 48] |= 0x80;
+
+        let mut parser = BundleParser::with_options(ParseOptions {
+            lenient: true,
+            ..Default::default()
+        });
+        let (last, rest) = bom_file.split_last().unwrap();
+        for byte in rest {
+            assert!(matches!(
+                parser.feed(&[*byte]).unwrap(),
+                ParseStatus::NeedMore
+            ));
+        }
+        let unity_asset_bundle = match parser.feed(&[*last]).unwrap() {
+            ParseStatus::Done(unity_asset_bundle) => unity_asset_bundle,
+            ParseStatus::NeedMore => panic!("bundle should be complete"),
+        };
+        assert_eq!(expected.directory_info, unity_asset_bundle.directory_info);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+    }
+
+    // hands out a few bytes per call and offers no Seek
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_read_from_reader_forward() {
+        let file = read_file("./item_icon_00000");
+        let expected = read_unity_asset_bundle(&file).unwrap().1;
+        let unity_asset_bundle =
+            read_unity_asset_bundle_from_reader_forward(Trickle(&file)).unwrap();
+        assert_eq!(expected.header, unity_asset_bundle.header);
+        assert_eq!(expected.stream_files, unity_asset_bundle.stream_files);
+
+        assert!(matches!(
+            read_unity_asset_bundle_from_reader_forward(Trickle(&file[..100])),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: 100,
+                stage: None,
+            })
+        ));
+
+        // flip on kArchiveBlocksInfoAtTheEnd
+        let mut at_end = file.clone();
+        at_end[48] |= 0x80;
+        assert!(matches!(
+            read_unity_asset_bundle_from_reader_forward(Trickle(&at_end)),
+            Err(UnityPackError::BlocksInfoAtEnd)
+        ));
+    }
+}
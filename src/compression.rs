@@ -0,0 +1,358 @@
+use std::str::FromStr;
+
+use crate::error::UnityPackError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompressionType {
+    None,
+    Lzma,
+    Lz4,
+    Lz4Hc,
+    Lzham,
+}
+
+impl CompressionType {
+    pub fn from_flags(flags: u32) -> Result<CompressionType, UnityPackError> {
+        // kArchiveCompressionTypeMask
+        match flags & 0x3F {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lzma),
+            2 => Ok(CompressionType::Lz4),
+            3 => Ok(CompressionType::Lz4Hc),
+            4 => Ok(CompressionType::Lzham),
+            other => Err(UnityPackError::UnsupportedCompression(other)),
+        }
+    }
+
+    #[must_use]
+    pub fn to_flags(self) -> u32 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Lzma => 1,
+            CompressionType::Lz4 => 2,
+            CompressionType::Lz4Hc => 3,
+            CompressionType::Lzham => 4,
+        }
+    }
+}
+
+impl FromStr for CompressionType {
+    type Err = UnityPackError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(CompressionType::None),
+            "lzma" => Ok(CompressionType::Lzma),
+            "lz4" => Ok(CompressionType::Lz4),
+            "lz4hc" => Ok(CompressionType::Lz4Hc),
+            "lzham" => Ok(CompressionType::Lzham),
+            _ => Err(UnityPackError::UnknownCompressionName(s.to_string())),
+        }
+    }
+}
+
+// shared across threads by `extract_all_parallel`
+pub trait Decompressor: Sync {
+    fn decompress(
+        &self,
+        input: &[u8],
+        out_size: usize,
+        kind: CompressionType,
+    ) -> Result<Vec<u8>, UnityPackError>;
+
+    // appends the decoded block to `out`, so a single buffer can collect every block of a
+    // bundle without a temporary allocation per block
+    fn decompress_into(
+        &self,
+        input: &[u8],
+        out_size: usize,
+        kind: CompressionType,
+        out: &mut Vec<u8>,
+    ) -> Result<(), UnityPackError> {
+        out.extend_from_slice(&self.decompress(input, out_size, kind)?);
+        Ok(())
+    }
+}
+
+// some games encrypt the compressed bytes of every storage block with their own scheme;
+// the key is theirs, so callers plug the cipher in through `ParseOptions::decryptor`
+pub trait Decryptor: Sync {
+    // `index` is the position of the block in the blocks info, which ciphers often mix in
+    fn decrypt(&self, block: &mut [u8], index: usize) -> Result<(), UnityPackError>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultDecompressor;
+
+impl Decompressor for DefaultDecompressor {
+    fn decompress(
+        &self,
+        input: &[u8],
+        out_size: usize,
+        kind: CompressionType,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        let mut out = Vec::with_capacity(out_size);
+        self.decompress_into(input, out_size, kind, &mut out)?;
+        Ok(out)
+    }
+
+    fn decompress_into(
+        &self,
+        input: &[u8],
+        out_size: usize,
+        kind: CompressionType,
+        out: &mut Vec<u8>,
+    ) -> Result<(), UnityPackError> {
+        match kind {
+            CompressionType::None => {
+                out.extend_from_slice(input);
+                Ok(())
+            }
+            CompressionType::Lzma => decompress_lzma(input, out_size, out),
+            CompressionType::Lz4 | CompressionType::Lz4Hc => {
+                decompress_lz4(input, out_size, kind, out)
+            }
+            // no pure-Rust LZHAM decoder is available
+            CompressionType::Lzham => Err(UnityPackError::UnsupportedCompression(4)),
+        }
+    }
+}
+
+#[cfg(feature = "lz4")]
+fn decompress_lz4(
+    input: &[u8],
+    out_size: usize,
+    _kind: CompressionType,
+    out: &mut Vec<u8>,
+) -> Result<(), UnityPackError> {
+    let start = out.len();
+    out.resize(start + out_size, 0);
+    // decode into a slice of its own, earlier blocks must not act as a dictionary
+    let result = lz4_flex::block::decompress_into(input, &mut out[start..], 0);
+    match result {
+        // corrupt input can decode cleanly yet stop short, which would shift every
+        // offset that follows
+        Ok(written) if written != out_size => {
+            out.truncate(start);
+            Err(UnityPackError::DecompressedSizeMismatch {
+                expected: out_size,
+                actual: written,
+            })
+        }
+        Ok(_) => Ok(()),
+        Err(e) => {
+            out.truncate(start);
+            Err(UnityPackError::Decompress(Box::new(e)))
+        }
+    }
+}
+
+#[cfg(not(feature = "lz4"))]
+fn decompress_lz4(
+    _input: &[u8],
+    _out_size: usize,
+    kind: CompressionType,
+    _out: &mut Vec<u8>,
+) -> Result<(), UnityPackError> {
+    Err(UnityPackError::CodecDisabled {
+        compression: kind,
+        feature: "lz4",
+    })
+}
+
+#[cfg(feature = "lzma")]
+fn decompress_lzma(input: &[u8], out_size: usize, out: &mut Vec<u8>) -> Result<(), UnityPackError> {
+    // Unity stores the 5 bytes of properties without the uncompressed size
+    let options = lzma_rs::decompress::Options {
+        unpacked_size: lzma_rs::decompress::UnpackedSize::UseProvided(Some(out_size as u64)),
+        ..Default::default()
+    };
+    let start = out.len();
+    out.reserve(out_size);
+    let mut reader = input;
+    lzma_rs::lzma_decompress_with_options(&mut reader, out, &options).map_err(|e| {
+        out.truncate(start);
+        UnityPackError::Decompress(Box::new(e))
+    })
+}
+
+#[cfg(not(feature = "lzma"))]
+fn decompress_lzma(
+    _input: &[u8],
+    _out_size: usize,
+    _out: &mut Vec<u8>,
+) -> Result<(), UnityPackError> {
+    Err(UnityPackError::CodecDisabled {
+        compression: CompressionType::Lzma,
+        feature: "lzma",
+    })
+}
+
+pub fn compress(input: &[u8], kind: CompressionType) -> Result<Vec<u8>, UnityPackError> {
+    match kind {
+        CompressionType::None => Ok(input.to_vec()),
+        CompressionType::Lzma => compress_lzma(input),
+        // LZ4HC output is plain LZ4 block data, so the fast compressor is valid for both
+        CompressionType::Lz4 | CompressionType::Lz4Hc => compress_lz4(input, kind),
+        CompressionType::Lzham => Err(UnityPackError::UnsupportedCompression(4)),
+    }
+}
+
+#[cfg(feature = "lz4")]
+fn compress_lz4(input: &[u8], _kind: CompressionType) -> Result<Vec<u8>, UnityPackError> {
+    Ok(lz4_flex::block::compress(input))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn compress_lz4(_input: &[u8], kind: CompressionType) -> Result<Vec<u8>, UnityPackError> {
+    Err(UnityPackError::CodecDisabled {
+        compression: kind,
+        feature: "lz4",
+    })
+}
+
+#[cfg(feature = "lzma")]
+fn compress_lzma(input: &[u8]) -> Result<Vec<u8>, UnityPackError> {
+    let options = lzma_rs::compress::Options {
+        unpacked_size: lzma_rs::compress::UnpackedSize::SkipWritingToHeader,
+    };
+    let mut reader = input;
+    let mut encoded = vec![];
+    lzma_rs::lzma_compress_with_options(&mut reader, &mut encoded, &options)
+        .map_err(UnityPackError::Io)?;
+    Ok(encoded)
+}
+
+#[cfg(not(feature = "lzma"))]
+fn compress_lzma(_input: &[u8]) -> Result<Vec<u8>, UnityPackError> {
+    Err(UnityPackError::CodecDisabled {
+        compression: CompressionType::Lzma,
+        feature: "lzma",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{compress, CompressionType, Decompressor, DefaultDecompressor};
+    use crate::error::UnityPackError;
+
+    #[test]
+    fn test_parse_compression_type() {
+        assert_eq!(Ok(CompressionType::None), "none".parse().map_err(drop));
+        assert_eq!(Ok(CompressionType::Lzma), "lzma".parse().map_err(drop));
+        assert_eq!(Ok(CompressionType::Lz4), "lz4".parse().map_err(drop));
+        assert_eq!(Ok(CompressionType::Lz4Hc), "LZ4HC".parse().map_err(drop));
+        assert_eq!(Ok(CompressionType::Lzham), "LzHam".parse().map_err(drop));
+    }
+
+    #[test]
+    fn test_parse_unknown_compression_type() {
+        assert!(matches!(
+            "zstd".parse::<CompressionType>(),
+            Err(UnityPackError::UnknownCompressionName(name)) if name == "zstd"
+        ));
+    }
+
+    #[cfg(all(feature = "lz4", feature = "lzma"))]
+    #[test]
+    fn test_compress_round_trip() {
+        let data = b"UnityFS UnityFS UnityFS UnityFS".repeat(64);
+        for kind in [
+            CompressionType::None,
+            CompressionType::Lzma,
+            CompressionType::Lz4,
+            CompressionType::Lz4Hc,
+        ] {
+            let compressed = compress(&data, kind).unwrap();
+            assert_eq!(
+                data,
+                DefaultDecompressor
+                    .decompress(&compressed, data.len(), kind)
+                    .unwrap()
+            );
+            assert_eq!(
+                Ok(kind),
+                CompressionType::from_flags(kind.to_flags()).map_err(drop)
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_into_appends() {
+        let data = b"UnityFS UnityFS UnityFS UnityFS".repeat(64);
+        // every codec compiled into this build
+        let kinds = [
+            CompressionType::None,
+            #[cfg(feature = "lzma")]
+            CompressionType::Lzma,
+            #[cfg(feature = "lz4")]
+            CompressionType::Lz4,
+            #[cfg(feature = "lz4")]
+            CompressionType::Lz4Hc,
+        ];
+        let mut out = b"head".to_vec();
+        for kind in kinds.iter().copied() {
+            let compressed = compress(&data, kind).unwrap();
+            DefaultDecompressor
+                .decompress_into(&compressed, data.len(), kind, &mut out)
+                .unwrap();
+        }
+        assert_eq!(b"head", &out[..4]);
+        assert!(out[4..].chunks(data.len()).all(|block| block == &data[..]));
+        assert_eq!(4 + kinds.len() * data.len(), out.len());
+
+        // a block that fails to decode leaves the earlier ones as they were
+        let len = out.len();
+        assert!(DefaultDecompressor
+            .decompress_into(&[0xFF; 8], 64, CompressionType::Lz4, &mut out)
+            .is_err());
+        assert_eq!(len, out.len());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_decompress_lz4_short_block() {
+        // a block that lost its tail still ends on a complete sequence, so the decoder
+        // itself reports success
+        let data = b"UnityFS UnityFS UnityFS UnityFS".repeat(64);
+        let truncated = compress(&data[..data.len() / 2], CompressionType::Lz4).unwrap();
+        let mut out = b"head".to_vec();
+        let error = DefaultDecompressor
+            .decompress_into(&truncated, data.len(), CompressionType::Lz4, &mut out)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            UnityPackError::DecompressedSizeMismatch { expected, actual }
+                if expected == data.len() && actual == data.len() / 2
+        ));
+        assert_eq!(
+            format!(
+                "block decompressed to {} bytes, but the blocks info declares {}",
+                data.len() / 2,
+                data.len()
+            ),
+            error.to_string()
+        );
+        assert_eq!(b"head".to_vec(), out);
+    }
+
+    #[cfg(not(feature = "lzma"))]
+    #[test]
+    fn test_disabled_codec() {
+        let error = DefaultDecompressor
+            .decompress(&[0; 5], 16, CompressionType::Lzma)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            UnityPackError::CodecDisabled {
+                compression: CompressionType::Lzma,
+                feature: "lzma"
+            }
+        ));
+        assert_eq!(
+            "Lzma support is not compiled in, enable the `lzma` feature",
+            error.to_string()
+        );
+        assert!(compress(&[0; 16], CompressionType::Lzma).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::asset_bundle::{read_unity_asset_bundle, UnityAssetBundle};
+use crate::error::UnityPackError;
+
+// zlib's CRC-32; unity's bundle CRC is taken over the decompressed data, not the file
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[must_use]
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+impl UnityAssetBundle {
+    // over the node bodies laid out as the decompressed blocks hold them
+    pub fn crc32(&self) -> Result<u32, UnityPackError> {
+        Ok(crc32(&self.node_data()?))
+    }
+}
+
+// whether `bytes` parse as a bundle whose CRC equals `expected_crc`
+#[must_use]
+pub fn verify_bundle_against_crc(bytes: &[u8], expected_crc: u32) -> bool {
+    check_bundle_crc(bytes, expected_crc).is_ok()
+}
+
+// on a mismatch the error holds the CRC that was computed
+pub fn check_bundle_crc(bytes: &[u8], expected_crc: u32) -> Result<(), UnityPackError> {
+    let (_, bundle) = read_unity_asset_bundle(bytes)?;
+    match bundle.crc32()? {
+        crc if crc == expected_crc => Ok(()),
+        crc => Err(UnityPackError::CrcMismatch {
+            expected: expected_crc,
+            actual: crc,
+        }),
+    }
+}
+
+#[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
+mod tests {
+    use crate::crc::{check_bundle_crc, crc32, verify_bundle_against_crc};
+    use crate::error::UnityPackError;
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0, crc32(b""));
+        // the standard check value
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_verify_bundle_against_crc() {
+        let file = read_file("./item_icon_00000");
+        // zlib.crc32 of the 70048 decompressed bytes; the sample comes without a
+        // manifest, so this is not a CRC Unity reported
+        assert!(verify_bundle_against_crc(&file, 869_500_985));
+        assert!(!verify_bundle_against_crc(&file, 869_500_986));
+        // the CRC of the file as stored is not the bundle CRC
+        assert!(!verify_bundle_against_crc(&file, crc32(&file)));
+        assert!(matches!(check_bundle_crc(&file, 869_500_985), Ok(())));
+        assert!(matches!(
+            check_bundle_crc(&file, 0),
+            Err(UnityPackError::CrcMismatch {
+                expected: 0,
+                actual: 869_500_985,
+            })
+        ));
+        assert!(matches!(
+            check_bundle_crc(&file[..100], 869_500_985),
+            Err(UnityPackError::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::asset_bundle::{UnityAssetBundle, UnityNode};
+use crate::compression::CompressionType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResizedNode {
+    pub path: String,
+    pub old_size: i64,
+    pub new_size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionChange {
+    pub path: String,
+    pub old: Vec<CompressionType>,
+    pub new: Vec<CompressionType>,
+}
+
+// header fields hold `(old, new)` when they differ
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BundleDiff {
+    pub version: Option<(u32, u32)>,
+    pub unity_version: Option<(String, String)>,
+    pub unity_revision: Option<(String, String)>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub resized: Vec<ResizedNode>,
+    pub compression_changed: Vec<CompressionChange>,
+}
+
+impl BundleDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == BundleDiff::default()
+    }
+}
+
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<(T, T)> {
+    if old == new {
+        None
+    } else {
+        Some((old.clone(), new.clone()))
+    }
+}
+
+fn find_node<'a>(bundle: &'a UnityAssetBundle, path: &str) -> Option<&'a UnityNode> {
+    bundle.directory_info.iter().find(|node| node.path == path)
+}
+
+// nodes are matched by path; `a` is the old bundle and `b` the new one
+#[must_use]
+pub fn diff_bundles(a: &UnityAssetBundle, b: &UnityAssetBundle) -> BundleDiff {
+    let mut diff = BundleDiff {
+        version: changed(&a.header.version, &b.header.version),
+        unity_version: changed(&a.header.unity_version, &b.header.unity_version),
+        unity_revision: changed(&a.header.unity_revision, &b.header.unity_revision),
+        ..Default::default()
+    };
+
+    for old in a.directory_info.iter() {
+        let new = match find_node(b, &old.path) {
+            Some(new) => new,
+            None => {
+                diff.removed.push(old.path.clone());
+                continue;
+            }
+        };
+        if old.size != new.size {
+            diff.resized.push(ResizedNode {
+                path: old.path.clone(),
+                old_size: old.size,
+                new_size: new.size,
+            });
+        }
+        let old_compression = a.node_compression_types(old);
+        let new_compression = b.node_compression_types(new);
+        if old_compression != new_compression {
+            diff.compression_changed.push(CompressionChange {
+                path: old.path.clone(),
+                old: old_compression,
+                new: new_compression,
+            });
+        }
+    }
+    diff.added = b
+        .directory_info
+        .iter()
+        .filter(|new| find_node(a, &new.path).is_none())
+        .map(|new| new.path.clone())
+        .collect();
+    diff
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::compression::CompressionType;
+    use crate::diff::{diff_bundles, CompressionChange, ResizedNode};
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_diff_bundles() {
+        let file = read_file("./item_icon_00000");
+        let a = read_unity_asset_bundle(&file).unwrap().1;
+        assert!(diff_bundles(&a, &a).is_empty());
+
+        let mut b = read_unity_asset_bundle(&file).unwrap().1;
+        b.header.unity_revision = "2019.4.2f1".to_string();
+        b.storage_blocks[0].flags = 0;
+        b.directory_info[0].size -= 12;
+        b.directory_info[1].path = "CAB-renamed.resS".to_string();
+
+        let diff = diff_bundles(&a, &b);
+        assert_eq!(None, diff.version);
+        assert_eq!(None, diff.unity_version);
+        assert_eq!(
+            Some(("2019.4.1f1".to_string(), "2019.4.2f1".to_string())),
+            diff.unity_revision
+        );
+        assert_eq!(vec!["CAB-renamed.resS"], diff.added);
+        assert_eq!(
+            vec!["CAB-5813386f0ea15049abeb5a688d9031d3.resS"],
+            diff.removed
+        );
+        assert_eq!(
+            vec![ResizedNode {
+                path: "CAB-5813386f0ea15049abeb5a688d9031d3".to_string(),
+                old_size: 4512,
+                new_size: 4500,
+            }],
+            diff.resized
+        );
+        assert_eq!(
+            vec![CompressionChange {
+                path: "CAB-5813386f0ea15049abeb5a688d9031d3".to_string(),
+                old: vec![CompressionType::Lz4Hc],
+                new: vec![CompressionType::None],
+            }],
+            diff.compression_changed
+        );
+    }
+}
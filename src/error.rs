@@ -0,0 +1,375 @@
+use std::{error::Error, fmt};
+
+use nom::error::{ErrorKind, ParseError};
+
+use crate::asset::HeaderInconsistency;
+use crate::asset_bundle::BundleFormat;
+use crate::compression::CompressionType;
+
+// the part of a bundle that was being read when a parse error hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStage {
+    Header,
+    ContainerHeader,
+    BlocksInfo,
+    Blocks,
+}
+
+impl fmt::Display for ParseStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseStage::Header => "bundle header",
+            ParseStage::ContainerHeader => "container header",
+            ParseStage::BlocksInfo => "blocks info",
+            ParseStage::Blocks => "storage blocks",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum UnityPackError {
+    Parse {
+        kind: ErrorKind,
+        offset: usize,
+        // `None` outside the bundle container, e.g. inside a serialized file
+        stage: Option<ParseStage>,
+    },
+    UnexpectedBom,
+    UnknownSignature(String),
+    UnsupportedBundleFormat(BundleFormat),
+    BlocksInfoAtEnd,
+    UnsupportedContainerFlags(u32),
+    UnknownFlags(u32),
+    UnsupportedCompression(u32),
+    CodecDisabled {
+        compression: CompressionType,
+        feature: &'static str,
+    },
+    UnknownCompressionName(String),
+    Decompress(Box<dyn Error + Send + Sync>),
+    DecompressedSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    StreamFileCountMismatch {
+        nodes: usize,
+        stream_files: usize,
+    },
+    StreamFileSizeMismatch {
+        path: String,
+        expected: i64,
+        actual: usize,
+    },
+    Manifest(Box<dyn Error + Send + Sync>),
+    Io(std::io::Error),
+    Utf8(std::str::Utf8Error),
+    FileNotFound(String),
+    ObjectNotFound(i64),
+    MissingTypeTree(i32),
+    BlockOutOfRange(usize),
+    OffsetOverflow(i64),
+    UnsupportedSerializedFileVersion(u32),
+    InconsistentSerializedFileHeader(HeaderInconsistency),
+    UnsupportedTextureFormat(i32),
+    TextureDecode(String),
+    TextureEncode(String),
+    CrcMismatch {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl UnityPackError {
+    // nom only hands the remaining input to `ParseError`, so while parsing `offset`
+    // holds the remaining length; the entry points turn it into an absolute offset
+    pub(crate) fn locate(self, input_len: usize, base: usize) -> Self {
+        match self {
+            UnityPackError::Parse {
+                kind,
+                offset,
+                stage,
+            } => UnityPackError::Parse {
+                kind,
+                offset: base + input_len.saturating_sub(offset),
+                stage,
+            },
+            e => e,
+        }
+    }
+
+    pub(crate) fn at(self, remaining: usize) -> Self {
+        match self {
+            UnityPackError::Parse { kind, stage, .. } => UnityPackError::Parse {
+                kind,
+                offset: remaining,
+                stage,
+            },
+            e => e,
+        }
+    }
+
+    // the innermost stage wins, so wrapping an already tagged error keeps its stage
+    pub(crate) fn in_stage(self, stage: ParseStage) -> Self {
+        match self {
+            UnityPackError::Parse {
+                kind,
+                offset,
+                stage: None,
+            } => UnityPackError::Parse {
+                kind,
+                offset,
+                stage: Some(stage),
+            },
+            e => e,
+        }
+    }
+}
+
+// running out of input counts as an end of file in `stage` too
+pub(crate) fn staged(e: nom::Err<UnityPackError>, stage: ParseStage) -> nom::Err<UnityPackError> {
+    match e {
+        nom::Err::Incomplete(_) => nom::Err::Error(UnityPackError::from(e).in_stage(stage)),
+        e => e.map(|e| e.in_stage(stage)),
+    }
+}
+
+impl fmt::Display for UnityPackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnityPackError::Parse {
+                kind,
+                offset,
+                stage: Some(stage),
+            } => write!(
+                f,
+                "parse error in the {} at byte {} ({})",
+                stage,
+                offset,
+                kind.description()
+            ),
+            UnityPackError::Parse {
+                kind,
+                offset,
+                stage: None,
+            } => write!(f, "parse error at byte {} ({})", offset, kind.description()),
+            UnityPackError::UnexpectedBom => write!(
+                f,
+                "bundle starts with a UTF-8 BOM, parse with `lenient` to skip it"
+            ),
+            UnityPackError::UnknownSignature(signature) => {
+                write!(f, "unknown bundle signature {:?}", signature)
+            }
+            UnityPackError::UnsupportedBundleFormat(format) => {
+                write!(f, "{:?} bundles are not supported", format)
+            }
+            UnityPackError::BlocksInfoAtEnd => write!(
+                f,
+                "blocks info is stored at the end of the bundle, which a forward-only reader cannot reach"
+            ),
+            UnityPackError::UnsupportedContainerFlags(flags) => {
+                write!(f, "unsupported container flags {:#x}", flags)
+            }
+            UnityPackError::UnknownFlags(mask) => {
+                write!(f, "container flags {:#x} are not understood by this crate", mask)
+            }
+            UnityPackError::UnsupportedCompression(n) => {
+                write!(f, "unsupported compression type {}", n)
+            }
+            UnityPackError::CodecDisabled {
+                compression,
+                feature,
+            } => write!(
+                f,
+                "{:?} support is not compiled in, enable the `{}` feature",
+                compression, feature
+            ),
+            UnityPackError::UnknownCompressionName(name) => {
+                write!(f, "unknown compression type name {:?}", name)
+            }
+            UnityPackError::Decompress(e) => write!(f, "decompression failed: {}", e),
+            UnityPackError::DecompressedSizeMismatch { expected, actual } => write!(
+                f,
+                "block decompressed to {} bytes, but the blocks info declares {}",
+                actual, expected
+            ),
+            UnityPackError::StreamFileCountMismatch {
+                nodes,
+                stream_files,
+            } => write!(
+                f,
+                "bundle has {} stream files for {} directory nodes",
+                stream_files, nodes
+            ),
+            UnityPackError::StreamFileSizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "stream file {} holds {} bytes, but its node declares {}",
+                path, actual, expected
+            ),
+            UnityPackError::Manifest(e) => write!(f, "invalid bundle manifest: {}", e),
+            UnityPackError::Io(e) => write!(f, "I/O error: {}", e),
+            UnityPackError::Utf8(e) => write!(f, "invalid UTF-8 string: {}", e),
+            UnityPackError::FileNotFound(path) => write!(f, "file not found in bundle: {}", path),
+            UnityPackError::ObjectNotFound(path_id) => {
+                write!(f, "no object with path id {} in the serialized file", path_id)
+            }
+            UnityPackError::MissingTypeTree(class_id) => write!(
+                f,
+                "the serialized file has no type tree for class {}",
+                class_id
+            ),
+            UnityPackError::BlockOutOfRange(index) => {
+                write!(f, "storage block {} does not exist", index)
+            }
+            UnityPackError::OffsetOverflow(value) => write!(
+                f,
+                "offset or size {} does not fit in usize on this target",
+                value
+            ),
+            UnityPackError::UnsupportedSerializedFileVersion(version) => {
+                write!(f, "unsupported serialized file version {}", version)
+            }
+            UnityPackError::InconsistentSerializedFileHeader(inconsistency) => {
+                write!(f, "inconsistent serialized file header: {}", inconsistency)
+            }
+            UnityPackError::UnsupportedTextureFormat(format) => {
+                write!(f, "unsupported texture format {}", format)
+            }
+            UnityPackError::TextureDecode(e) => write!(f, "texture decoding failed: {}", e),
+            UnityPackError::TextureEncode(e) => write!(f, "texture encoding failed: {}", e),
+            UnityPackError::CrcMismatch { expected, actual } => write!(
+                f,
+                "bundle CRC is {:#010x}, but {:#010x} was expected",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl Error for UnityPackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UnityPackError::Decompress(e) | UnityPackError::Manifest(e) => Some(e.as_ref()),
+            UnityPackError::Io(e) => Some(e),
+            UnityPackError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for UnityPackError {
+    fn from(e: std::io::Error) -> Self {
+        UnityPackError::Io(e)
+    }
+}
+
+impl From<std::str::Utf8Error> for UnityPackError {
+    fn from(e: std::str::Utf8Error) -> Self {
+        UnityPackError::Utf8(e)
+    }
+}
+
+impl ParseError<&[u8]> for UnityPackError {
+    fn from_error_kind(input: &[u8], kind: ErrorKind) -> Self {
+        UnityPackError::Parse {
+            kind,
+            offset: input.len(),
+            stage: None,
+        }
+    }
+
+    fn append(_input: &[u8], _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl From<nom::Err<UnityPackError>> for UnityPackError {
+    fn from(e: nom::Err<UnityPackError>) -> Self {
+        match e {
+            nom::Err::Incomplete(_) => UnityPackError::Parse {
+                kind: ErrorKind::Eof,
+                offset: 0,
+                stage: None,
+            },
+            nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use nom::error::{ErrorKind, ParseError};
+
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::error::{ParseStage, UnityPackError};
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_display_parse_error() {
+        let file = read_file("./item_icon_00000");
+        for (len, stage, message) in [
+            (
+                10,
+                ParseStage::Header,
+                "parse error in the bundle header at byte 10 (End of file)",
+            ),
+            (
+                40,
+                ParseStage::ContainerHeader,
+                "parse error in the container header at byte 40 (End of file)",
+            ),
+            (
+                60,
+                ParseStage::BlocksInfo,
+                "parse error in the blocks info at byte 49 (End of file)",
+            ),
+        ] {
+            let error = UnityPackError::from(read_unity_asset_bundle(&file[..len]).unwrap_err());
+            assert!(matches!(
+                error,
+                UnityPackError::Parse { stage: Some(s), .. } if s == stage
+            ));
+            assert_eq!(message, error.to_string());
+            assert!(error.source().is_none());
+        }
+
+        let error = UnityPackError::from_error_kind(&[], ErrorKind::Eof).locate(4, 0);
+        assert_eq!("parse error at byte 4 (End of file)", error.to_string());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_decompress_error_source() {
+        use crate::compression::{CompressionType, Decompressor, DefaultDecompressor};
+
+        let error = DefaultDecompressor
+            .decompress(&[0xFF; 8], 64, CompressionType::Lz4)
+            .unwrap_err();
+        assert!(error.to_string().starts_with("decompression failed: "));
+        let source = error.source().unwrap();
+        assert!(source.is::<lz4_flex::block::DecompressError>());
+        assert_eq!(
+            error.to_string()["decompression failed: ".len()..],
+            source.to_string()
+        );
+    }
+
+    #[test]
+    fn test_io_error_source() {
+        let error = UnityPackError::from(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "short read",
+        ));
+        assert_eq!("I/O error: short read", error.to_string());
+        let source = error.source().unwrap();
+        assert_eq!(
+            std::io::ErrorKind::UnexpectedEof,
+            source.downcast_ref::<std::io::Error>().unwrap().kind()
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+use crate::asset_bundle::{
+    blocks_info_padding, custom_data_size, read_blocks_info_and_directory,
+    read_unity_asset_bundle_header, read_unity_container_header, ParseOptions,
+};
+use crate::compression::CompressionType;
+use crate::error::{ParseStage, UnityPackError};
+
+// one line per field, "offset size name: value", up to the first field that fails to parse;
+// entries decoded from the compressed blocks info have no offset in the input
+#[must_use]
+pub fn dump_layout(input: &[u8]) -> String {
+    let mut dump = Layout::default();
+    if let Err(e) = dump_bundle(input, &mut dump) {
+        dump.lines.push(format!("error: {}", e));
+    }
+    dump.lines.join("\n")
+}
+
+#[derive(Default)]
+struct Layout {
+    lines: Vec<String>,
+    offset: usize,
+}
+
+impl Layout {
+    fn field(&mut self, name: &str, size: usize, value: impl Display) {
+        self.lines.push(format!(
+            "{:>8} {:>6}  {}: {}",
+            self.offset, size, name, value
+        ));
+        self.offset += size;
+    }
+
+    fn entry(&mut self, name: &str, value: impl Display) {
+        self.lines
+            .push(format!("{:>8} {:>6}  {}: {}", "-", "-", name, value));
+    }
+}
+
+fn dump_bundle(input: &[u8], dump: &mut Layout) -> Result<(), UnityPackError> {
+    let options = ParseOptions::default();
+    let locate = |e: nom::Err<UnityPackError>| UnityPackError::from(e).locate(input.len(), 0);
+
+    let (rest, header) = read_unity_asset_bundle_header(input, &options).map_err(locate)?;
+    dump.field(
+        "signature",
+        header.signature.len() + 1,
+        format!("{:?}", header.signature),
+    );
+    dump.field("version", 4, header.version);
+    dump.field(
+        "unity_version",
+        header.raw_unity_version.len() + 1,
+        format!("{:?}", header.raw_unity_version),
+    );
+    dump.field(
+        "unity_revision",
+        header.raw_unity_revision.len() + 1,
+        format!("{:?}", header.raw_unity_revision),
+    );
+
+    let (rest, container_header) = read_unity_container_header(rest).map_err(locate)?;
+    dump.field("size", 8, container_header.size);
+    dump.field(
+        "compressed_blocks_info_size",
+        4,
+        container_header.compressed_blocks_info_size,
+    );
+    dump.field(
+        "uncompressed_blocks_info_size",
+        4,
+        container_header.uncompressed_blocks_info_size,
+    );
+    dump.field(
+        "flags",
+        4,
+        format!(
+            "{:#x} ({})",
+            container_header.flags,
+            compression_name(container_header.flags)
+        ),
+    );
+    let padding = blocks_info_padding(&header, &options);
+    if padding > 0 {
+        dump.field("padding", padding, "");
+    }
+
+    let headers_size = dump.offset - padding;
+    let (rest, blocks_info) =
+        read_blocks_info_and_directory(rest, &header, &container_header, &options)
+            .map_err(locate)?;
+    let blocks_info_start = headers_size + blocks_info.range.start;
+    dump.lines.push(format!(
+        "{:>8} {:>6}  blocks_info",
+        blocks_info_start,
+        blocks_info.range.len()
+    ));
+
+    dump.offset = input.len() - rest.len() - blocks_info.blocks_padding;
+    if blocks_info.blocks_padding > 0 {
+        dump.field("blocks_padding", blocks_info.blocks_padding, "");
+    }
+    for (index, sb) in blocks_info.storage_blocks.iter().enumerate() {
+        let present = input.len() - dump.offset >= sb.compressed_size as usize;
+        dump.field(
+            &format!("block[{}]", index),
+            sb.compressed_size as usize,
+            format!(
+                "{} bytes uncompressed, {}",
+                sb.uncompressed_size,
+                compression_name(sb.flags as u32)
+            ),
+        );
+        if !present {
+            return Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: input.len(),
+                stage: Some(ParseStage::Blocks),
+            });
+        }
+    }
+    let custom_data_size = custom_data_size(&container_header, dump.offset)?;
+    if custom_data_size > 0 {
+        let present = input.len() - dump.offset >= custom_data_size;
+        dump.field("custom_data", custom_data_size, "");
+        if !present {
+            return Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                offset: input.len(),
+                stage: Some(ParseStage::Blocks),
+            });
+        }
+    }
+    for (index, node) in blocks_info.nodes.iter().enumerate() {
+        dump.entry(
+            &format!("node[{}]", index),
+            format!(
+                "offset {}, size {}, flags {:#x}, {:?}",
+                node.offset, node.size, node.flags, node.path
+            ),
+        );
+    }
+    Ok(())
+}
+
+fn compression_name(flags: u32) -> String {
+    match CompressionType::from_flags(flags) {
+        Ok(compression) => format!("{:?}", compression),
+        Err(_) => format!("unknown compression {}", flags & 0x3F),
+    }
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::layout::dump_layout;
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_dump_layout() {
+        let file = read_file("./item_icon_00000");
+        let dump = dump_layout(&file);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!("       0      8  signature: \"UnityFS\"", lines[0]);
+        assert_eq!("       8      4  version: 6", lines[1]);
+        assert!(lines.contains(&"      49     85  blocks_info"));
+        assert!(lines.contains(&"     134   4331  block[0]: 70048 bytes uncompressed, Lz4Hc"));
+        assert!(lines.last().unwrap().ends_with(
+            "offset 4512, size 65536, flags 0x0, \"CAB-5813386f0ea15049abeb5a688d9031d3.resS\""
+        ));
+        assert!(!dump.contains("error"));
+    }
+
+    #[test]
+    fn test_dump_layout_blocks_padding() {
+        let dump = dump_layout(&read_file("./item_icon_00000_padded"));
+        assert!(dump.contains("\n     134     10  blocks_padding: \n"));
+        assert!(dump.contains("\n     144   4331  block[0]: 70048 bytes uncompressed, Lz4Hc\n"));
+        assert!(!dump.contains("error"));
+    }
+
+    #[test]
+    fn test_dump_layout_custom_data() {
+        let file = read_file("./item_icon_00000_custom_data");
+        let dump = dump_layout(&file);
+        assert!(dump.contains("\n    4465     24  custom_data: \n"));
+        assert!(!dump.contains("error"));
+        assert!(!dump_layout(&read_file("./item_icon_00000")).contains("custom_data"));
+
+        let dump = dump_layout(&file[..4470]);
+        assert!(
+            dump.ends_with("error: parse error in the storage blocks at byte 4470 (End of file)")
+        );
+    }
+
+    #[test]
+    fn test_dump_layout_stops_at_failure() {
+        let file = read_file("./item_icon_00000");
+        let dump = dump_layout(&file[..40]);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(5, lines.len());
+        assert_eq!("      18     11  unity_revision: \"2019.4.1f1\"", lines[3]);
+        assert_eq!(
+            "error: parse error in the container header at byte 40 (End of file)",
+            lines[4]
+        );
+
+        let dump = dump_layout(&file[..1000]);
+        assert!(dump.contains("block[0]"));
+        assert!(
+            dump.ends_with("error: parse error in the storage blocks at byte 1000 (End of file)")
+        );
+    }
+}
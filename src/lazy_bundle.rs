@@ -0,0 +1,666 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    io::{self, Read},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use nom::{bytes::complete::take, IResult};
+
+use crate::asset_bundle::{
+    decompress_storage_block, read_blocks_info_and_directory, read_unity_asset_bundle_header,
+    read_unity_container_header, skip_bom, ParseOptions, PathStyle, UnityAssetBundleHeader,
+    UnityContainerHeader, UnityNode, UnityStorageBlock, MAX_PREALLOCATED_BYTES,
+};
+use crate::block_cache::BlockCache;
+use crate::error::{ParseStage, UnityPackError};
+use crate::usize_from_i64;
+
+// keeps the storage blocks compressed and only decompresses what a read touches
+pub struct LazyUnityAssetBundle<'a> {
+    pub header: UnityAssetBundleHeader,
+    pub container_header: UnityContainerHeader,
+    pub storage_blocks: Vec<UnityStorageBlock>,
+    pub directory_info: Vec<UnityNode>,
+    blocks: &'a [u8],
+    options: ParseOptions<'a>,
+}
+
+pub fn read_lazy_unity_asset_bundle(
+    input: &[u8],
+) -> IResult<&[u8], LazyUnityAssetBundle<'_>, UnityPackError> {
+    read_lazy_unity_asset_bundle_with_options(input, ParseOptions::default())
+}
+
+pub fn read_lazy_unity_asset_bundle_with_options<'a>(
+    input: &'a [u8],
+    options: ParseOptions<'a>,
+) -> IResult<&'a [u8], LazyUnityAssetBundle<'a>, UnityPackError> {
+    let input_len = input.len();
+    read_lazy_bundle(input, options).map_err(|e| e.map(|e| e.locate(input_len, 0)))
+}
+
+fn read_lazy_bundle<'a>(
+    input: &'a [u8],
+    options: ParseOptions<'a>,
+) -> IResult<&'a [u8], LazyUnityAssetBundle<'a>, UnityPackError> {
+    let (input, _) = skip_bom(input, &options)?;
+    let (input, header) = read_unity_asset_bundle_header(input, &options)?;
+    let (input, container_header) = read_unity_container_header(input)?;
+    let (input, blocks_info) =
+        read_blocks_info_and_directory(input, &header, &container_header, &options)?;
+    let blocks_size = blocks_info
+        .storage_blocks
+        .iter()
+        .map(|sb| sb.compressed_size as usize)
+        .sum::<usize>();
+    let (input, blocks) = take(blocks_size)(input)?;
+
+    Ok((
+        input,
+        LazyUnityAssetBundle {
+            header,
+            container_header,
+            storage_blocks: blocks_info.storage_blocks,
+            directory_info: blocks_info.nodes,
+            blocks,
+            options,
+        },
+    ))
+}
+
+impl LazyUnityAssetBundle<'_> {
+    pub fn get_file_cached(
+        &self,
+        path: &str,
+        cache: &mut BlockCache,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        let node = self
+            .directory_info
+            .iter()
+            .find(|di| di.path == path)
+            .ok_or_else(|| UnityPackError::FileNotFound(path.to_string()))?;
+        self.read_node_cached(node, cache)
+    }
+
+    // decompresses the blocks covering the file one at a time, as the reader reaches them
+    #[must_use]
+    pub fn file_reader(&self, path: &str) -> Option<NodeReader<'_>> {
+        let node = self.directory_info.iter().find(|di| di.path == path)?;
+        Some(NodeReader {
+            bundle: self,
+            blocks: self
+                .overlapping_blocks(node)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            block: vec![],
+            position: 0,
+            end: 0,
+            remaining: node.size,
+        })
+    }
+
+    pub fn extract_all<P: AsRef<Path>>(&self, dir: P) -> Result<(), UnityPackError> {
+        // nodes are laid out in order, so one block is all the cache needs
+        let mut cache = BlockCache::with_max_blocks(1);
+        let nodes = self.file_nodes().collect::<Vec<_>>();
+        let paths = extraction_paths(&nodes)?;
+        for (node, path) in nodes.into_iter().zip(paths) {
+            let body = self.read_node_cached(node, &mut cache)?;
+            write_node(dir.as_ref(), &path, &body)?;
+        }
+        Ok(())
+    }
+
+    // every block that any node touches is decompressed exactly once, in parallel, before
+    // the nodes are assembled and written concurrently
+    #[cfg(feature = "rayon")]
+    pub fn extract_all_parallel<P: AsRef<Path>>(&self, dir: P) -> Result<(), UnityPackError> {
+        use rayon::prelude::*;
+
+        let dir = dir.as_ref();
+        let nodes = self.file_nodes().collect::<Vec<_>>();
+        let paths = extraction_paths(&nodes)?;
+        let mut needed = vec![false; self.storage_blocks.len()];
+        for node in nodes.iter() {
+            for (index, ..) in self.overlapping_blocks(node) {
+                needed[index] = true;
+            }
+        }
+        let blocks = self
+            .storage_blocks
+            .par_iter()
+            .zip(self.compressed_ranges())
+            .enumerate()
+            .map(|(index, (sb, compressed))| {
+                if needed[index] {
+                    self.decompress_block(index, sb, compressed).map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        nodes.par_iter().zip(paths).try_for_each(|(node, path)| {
+            let mut body =
+                Vec::with_capacity(usize_from_i64(node.size)?.min(MAX_PREALLOCATED_BYTES));
+            for (index, _, range) in self.overlapping_blocks(node) {
+                if let Some(block) = &blocks[index] {
+                    body.extend_from_slice(&block[range]);
+                }
+            }
+            self.check_node_size(node, &body)?;
+            write_node(dir, &path, &body)
+        })
+    }
+
+    fn read_node_cached(
+        &self,
+        node: &UnityNode,
+        cache: &mut BlockCache,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        // the size is only checked once the blocks have been read
+        let mut body = Vec::with_capacity(usize_from_i64(node.size)?.min(MAX_PREALLOCATED_BYTES));
+        for (index, compressed, range) in self.overlapping_blocks(node) {
+            if let Some(block) = cache.get(index) {
+                body.extend_from_slice(&block[range]);
+                continue;
+            }
+            let block = self.decompress_block(index, &self.storage_blocks[index], compressed)?;
+            body.extend_from_slice(&block[range]);
+            cache.insert(index, block);
+        }
+        self.check_node_size(node, &body)?;
+        Ok(body)
+    }
+
+    // the blocks ran out before the node did
+    fn check_node_size(&self, node: &UnityNode, body: &[u8]) -> Result<(), UnityPackError> {
+        if i64::try_from(body.len()).ok() != Some(node.size) {
+            return Err(self.blocks_eof());
+        }
+        Ok(())
+    }
+
+    fn blocks_eof(&self) -> UnityPackError {
+        UnityPackError::Parse {
+            kind: nom::error::ErrorKind::Eof,
+            offset: self.blocks.len(),
+            stage: Some(ParseStage::Blocks),
+        }
+    }
+
+    fn file_nodes(&self) -> impl Iterator<Item = &UnityNode> + '_ {
+        self.directory_info
+            .iter()
+            .filter(|node| !node.is_directory() && !node.is_deleted())
+    }
+
+    fn compressed_ranges(&self) -> Vec<Range<usize>> {
+        let mut compressed_start = 0;
+        self.storage_blocks
+            .iter()
+            .map(|sb| {
+                let compressed = compressed_start..compressed_start + sb.compressed_size as usize;
+                compressed_start = compressed.end;
+                compressed
+            })
+            .collect()
+    }
+
+    // (block index, compressed range, range of the node within the decompressed block)
+    fn overlapping_blocks(
+        &self,
+        node: &UnityNode,
+    ) -> impl Iterator<Item = (usize, Range<usize>, Range<usize>)> + '_ {
+        // a node whose range overflows reads as empty and fails the size check
+        let node_range = node.data_range().unwrap_or(0..0);
+        let mut uncompressed_start = 0;
+        self.storage_blocks
+            .iter()
+            .zip(self.compressed_ranges())
+            .enumerate()
+            .filter_map(move |(index, (sb, compressed))| {
+                let uncompressed =
+                    uncompressed_start..uncompressed_start + sb.uncompressed_size as u64;
+                uncompressed_start = uncompressed.end;
+                if uncompressed.end <= node_range.start || node_range.end <= uncompressed.start {
+                    return None;
+                }
+                let start =
+                    (node_range.start.max(uncompressed.start) - uncompressed.start) as usize;
+                let end = (node_range.end.min(uncompressed.end) - uncompressed.start) as usize;
+                Some((index, compressed, start..end))
+            })
+    }
+
+    fn decompress_block(
+        &self,
+        index: usize,
+        sb: &UnityStorageBlock,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, UnityPackError> {
+        let bytes = self.blocks.get(range).ok_or_else(|| self.blocks_eof())?;
+        let mut block = Vec::new();
+        decompress_storage_block(bytes, index, sb, &self.options, &mut block)?;
+        if block.len() != sb.uncompressed_size as usize {
+            return Err(UnityPackError::DecompressedSizeMismatch {
+                expected: sb.uncompressed_size as usize,
+                actual: block.len(),
+            });
+        }
+        Ok(block)
+    }
+}
+
+pub struct NodeReader<'b> {
+    bundle: &'b LazyUnityAssetBundle<'b>,
+    blocks: std::vec::IntoIter<(usize, Range<usize>, Range<usize>)>,
+    block: Vec<u8>,
+    // the part of `block` that belongs to the file and has not been read yet
+    position: usize,
+    end: usize,
+    // bytes of the file the blocks still have to provide
+    remaining: i64,
+}
+
+impl Read for NodeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.end {
+            let (index, compressed, range) = match self.blocks.next() {
+                Some(block) => block,
+                None if self.remaining == 0 => return Ok(0),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        self.bundle.blocks_eof(),
+                    ))
+                }
+            };
+            self.block = self
+                .bundle
+                .decompress_block(index, &self.bundle.storage_blocks[index], compressed)
+                .map_err(io::Error::other)?;
+            self.position = range.start;
+            self.end = range.end;
+        }
+        let len = buf.len().min(self.end - self.position);
+        buf[..len].copy_from_slice(&self.block[self.position..self.position + len]);
+        self.position += len;
+        self.remaining -= len as i64;
+        Ok(len)
+    }
+}
+
+fn invalid_node_path(message: String) -> UnityPackError {
+    UnityPackError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        message,
+    ))
+}
+
+// the archive:/ prefix is dropped and only the normal components of the rest are kept,
+// so nothing lands outside the output directory while subdirectories are recreated
+fn relative_node_path(node: &UnityNode) -> Result<PathBuf, UnityPackError> {
+    let relative = Path::new(PathStyle::Stripped.apply(&node.path))
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect::<PathBuf>();
+    if relative.as_os_str().is_empty() {
+        return Err(invalid_node_path(format!(
+            "cannot extract node with path {:?}",
+            node.path
+        )));
+    }
+    Ok(relative)
+}
+
+// checked before anything is written: two nodes extracting to the same file, or one
+// node's file standing where another needs a directory, fail instead of overwriting
+fn extraction_paths(nodes: &[&UnityNode]) -> Result<Vec<PathBuf>, UnityPackError> {
+    let mut files = HashMap::new();
+    let mut directories = HashSet::new();
+    let mut paths = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let path = relative_node_path(node)?;
+        let clash = files.get(&path).copied().or_else(|| {
+            path.ancestors()
+                .skip(1)
+                .find_map(|ancestor| files.get(ancestor).copied())
+        });
+        if let Some(other) = clash {
+            return Err(invalid_node_path(format!(
+                "nodes {:?} and {:?} both extract to {:?}",
+                other, node.path, path
+            )));
+        }
+        if directories.contains(&path) {
+            return Err(invalid_node_path(format!(
+                "node {:?} extracts to {:?}, which other nodes need as a directory",
+                node.path, path
+            )));
+        }
+        directories.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        files.insert(path.clone(), node.path.as_str());
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn write_node(dir: &Path, relative: &Path, body: &[u8]) -> Result<(), UnityPackError> {
+    let path = dir.join(relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, body)?;
+    Ok(())
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use std::{
+        io::Read,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::asset_bundle::{read_unity_asset_bundle, ParseOptions};
+    use crate::block_cache::BlockCache;
+    use crate::compression::{CompressionType, Decompressor, DefaultDecompressor};
+    use crate::error::UnityPackError;
+    use crate::lazy_bundle::{
+        read_lazy_unity_asset_bundle, read_lazy_unity_asset_bundle_with_options,
+    };
+    use crate::test_fixtures::read_file;
+
+    struct CountingDecompressor {
+        calls: AtomicUsize,
+    }
+
+    impl Decompressor for CountingDecompressor {
+        fn decompress(
+            &self,
+            input: &[u8],
+            out_size: usize,
+            kind: CompressionType,
+        ) -> Result<Vec<u8>, UnityPackError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            DefaultDecompressor.decompress(input, out_size, kind)
+        }
+    }
+
+    #[test]
+    fn test_lazy_bundle_blocks_padding() {
+        let file = read_file("./item_icon_00000_padded");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+        let mut cache = BlockCache::with_max_blocks(1);
+        for stream_file in unity_asset_bundle.stream_files.iter() {
+            assert_eq!(
+                stream_file.body,
+                lazy_bundle
+                    .get_file_cached(&stream_file.path, &mut cache)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_file_cached() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let decompressor = CountingDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            ..Default::default()
+        };
+        let lazy_bundle = read_lazy_unity_asset_bundle_with_options(&file, options)
+            .unwrap()
+            .1;
+        // only the blocks info has been decompressed so far
+        assert_eq!(1, decompressor.calls.load(Ordering::Relaxed));
+
+        let mut cache = BlockCache::with_max_blocks(1);
+        for stream_file in unity_asset_bundle.stream_files.iter() {
+            assert_eq!(
+                stream_file.body,
+                lazy_bundle
+                    .get_file_cached(&stream_file.path, &mut cache)
+                    .unwrap()
+            );
+        }
+        // both files live in the single storage block
+        assert_eq!(2, decompressor.calls.load(Ordering::Relaxed));
+        assert_eq!((1, 1), (cache.hits(), cache.misses()));
+
+        let mut cache = BlockCache::with_max_blocks(0);
+        let path = &unity_asset_bundle.stream_files[0].path;
+        lazy_bundle.get_file_cached(path, &mut cache).unwrap();
+        lazy_bundle.get_file_cached(path, &mut cache).unwrap();
+        assert_eq!(4, decompressor.calls.load(Ordering::Relaxed));
+
+        assert!(matches!(
+            lazy_bundle.get_file_cached("CAB-missing", &mut cache),
+            Err(UnityPackError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_reader() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let decompressor = CountingDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            ..Default::default()
+        };
+        let lazy_bundle = read_lazy_unity_asset_bundle_with_options(&file, options)
+            .unwrap()
+            .1;
+        for stream_file in unity_asset_bundle.stream_files.iter() {
+            let mut reader = lazy_bundle.file_reader(&stream_file.path).unwrap();
+            let calls = decompressor.calls.load(Ordering::Relaxed);
+            let mut head = [0u8; 7];
+            reader.read_exact(&mut head).unwrap();
+            assert_eq!(calls + 1, decompressor.calls.load(Ordering::Relaxed));
+            let mut body = head.to_vec();
+            reader.read_to_end(&mut body).unwrap();
+            assert_eq!(stream_file.body, body);
+            assert_eq!(calls + 1, decompressor.calls.load(Ordering::Relaxed));
+        }
+        assert!(lazy_bundle.file_reader("CAB-missing").is_none());
+
+        let mut body = vec![];
+        unity_asset_bundle
+            .file_reader(&unity_asset_bundle.stream_files[1].path)
+            .unwrap()
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(unity_asset_bundle.stream_files[1].body, body);
+    }
+
+    #[test]
+    fn test_truncated_node() {
+        let file = read_file("./item_icon_00000");
+        let mut lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+        let path = lazy_bundle.directory_info[1].path.clone();
+        for (offset, size) in [(4512, 65537), (i64::MAX, 1)] {
+            lazy_bundle.directory_info[1].offset = offset;
+            lazy_bundle.directory_info[1].size = size;
+            let mut cache = BlockCache::with_max_blocks(1);
+            assert!(matches!(
+                lazy_bundle.get_file_cached(&path, &mut cache),
+                Err(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Eof,
+                    ..
+                })
+            ));
+            let error = lazy_bundle
+                .file_reader(&path)
+                .unwrap()
+                .read_to_end(&mut vec![])
+                .unwrap_err();
+            assert_eq!(std::io::ErrorKind::UnexpectedEof, error.kind());
+
+            let dir = std::env::temp_dir().join("unitypack_extract_truncated");
+            assert!(matches!(
+                lazy_bundle.extract_all(&dir),
+                Err(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Eof,
+                    ..
+                })
+            ));
+            #[cfg(feature = "rayon")]
+            assert!(matches!(
+                lazy_bundle.extract_all_parallel(&dir),
+                Err(UnityPackError::Parse {
+                    kind: nom::error::ErrorKind::Eof,
+                    ..
+                })
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn test_huge_node_size() {
+        let file = read_file("./item_icon_00000");
+        let mut lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+        let path = lazy_bundle.directory_info[1].path.clone();
+        lazy_bundle.directory_info[1].offset = 0;
+        lazy_bundle.directory_info[1].size = i64::MAX;
+        let mut cache = BlockCache::with_max_blocks(1);
+        assert!(matches!(
+            lazy_bundle.get_file_cached(&path, &mut cache),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                ..
+            })
+        ));
+        let dir = std::env::temp_dir().join("unitypack_extract_huge");
+        assert!(matches!(
+            lazy_bundle.extract_all(&dir),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                ..
+            })
+        ));
+        #[cfg(feature = "rayon")]
+        assert!(matches!(
+            lazy_bundle.extract_all_parallel(&dir),
+            Err(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                ..
+            })
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+        let dir = std::env::temp_dir().join("unitypack_extract_all");
+        lazy_bundle.extract_all(&dir).unwrap();
+        for stream_file in unity_asset_bundle.stream_files.iter() {
+            assert_eq!(stream_file.body, read_file(dir.join(&stream_file.path)));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // the sample bundle repacked with its nodes renamed to `paths`
+    fn bundle_with_node_paths(paths: [&str; 2]) -> Vec<u8> {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        for (node, path) in unity_asset_bundle.directory_info.iter_mut().zip(paths) {
+            node.path = path.to_string();
+        }
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        repacked
+    }
+
+    #[test]
+    fn test_extract_all_keeps_directories() {
+        let file = bundle_with_node_paths([
+            "archive:/CAB-test/sub/dir/CAB-test",
+            "archive:/CAB-test/CAB-test.resS",
+        ]);
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+        let dir = std::env::temp_dir().join("unitypack_extract_nested");
+        lazy_bundle.extract_all(&dir).unwrap();
+        assert_eq!(
+            unity_asset_bundle.stream_files[0].body,
+            read_file(dir.join("CAB-test/sub/dir/CAB-test"))
+        );
+        assert_eq!(
+            unity_asset_bundle.stream_files[1].body,
+            read_file(dir.join("CAB-test/CAB-test.resS"))
+        );
+        assert!(!dir.join("archive:").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_all_rejects_colliding_paths() {
+        for paths in [
+            ["archive:/CAB-test/CAB-test", "CAB-test/CAB-test"],
+            ["CAB-test", "CAB-test/CAB-test.resS"],
+            ["CAB-test/CAB-test.resS", "CAB-test"],
+        ] {
+            let file = bundle_with_node_paths(paths);
+            let lazy_bundle = read_lazy_unity_asset_bundle(&file).unwrap().1;
+            let dir = std::env::temp_dir().join("unitypack_extract_colliding");
+            assert!(matches!(
+                lazy_bundle.extract_all(&dir),
+                Err(UnityPackError::Io(e)) if e.kind() == std::io::ErrorKind::InvalidInput
+            ));
+            // nothing is written before the clash is found
+            assert!(!dir.exists());
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_extract_all_parallel() {
+        let file = read_file("./item_icon_00000");
+        let decompressor = CountingDecompressor {
+            calls: AtomicUsize::new(0),
+        };
+        let options = ParseOptions {
+            decompressor: &decompressor,
+            ..Default::default()
+        };
+        let lazy_bundle = read_lazy_unity_asset_bundle_with_options(&file, options)
+            .unwrap()
+            .1;
+        let serial = std::env::temp_dir().join("unitypack_extract_serial");
+        let parallel = std::env::temp_dir().join("unitypack_extract_parallel");
+        lazy_bundle.extract_all(&serial).unwrap();
+        let calls = decompressor.calls.load(Ordering::Relaxed);
+        lazy_bundle.extract_all_parallel(&parallel).unwrap();
+        // both files share the single storage block, which is only decompressed once
+        assert_eq!(calls + 1, decompressor.calls.load(Ordering::Relaxed));
+
+        for node in lazy_bundle.directory_info.iter() {
+            assert_eq!(
+                read_file(serial.join(&node.path)),
+                read_file(parallel.join(&node.path))
+            );
+        }
+        std::fs::remove_dir_all(&serial).unwrap();
+        std::fs::remove_dir_all(&parallel).unwrap();
+    }
+}
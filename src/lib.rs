@@ -1,6 +1,6 @@
 use nom::{
-    bytes::complete::tag,
-    character::complete::alpha1,
+    bytes::complete::{tag, take, take_until},
+    error::ParseError,
     multi::count,
     number::complete::{be_u32, be_u64, be_u8},
     IResult,
@@ -8,13 +8,100 @@ use nom::{
 
 pub mod asset;
 pub mod asset_bundle;
+pub mod block_cache;
+#[cfg(feature = "lru")]
+pub mod bundle_cache;
+pub mod bundle_fs;
+pub mod bundle_parser;
+pub mod compression;
+pub mod crc;
+pub mod diff;
+pub mod error;
+pub mod layout;
+pub mod lazy_bundle;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod objects;
+pub mod prelude;
+pub mod validation;
+pub mod writer;
 
-fn read_string_to_null(input: &[u8]) -> IResult<&[u8], String> {
-    let (input, signature) = alpha1(input)?;
+#[cfg(test)]
+// read_file is only used by the tests reading the LZ4HC sample bundles
+#[cfg_attr(not(feature = "lz4"), allow(dead_code))]
+pub(crate) mod test_fixtures;
+
+use error::UnityPackError;
+
+// counts come straight from the input, so never reserve more than this up front
+const MAX_PREALLOCATED_ITEMS: usize = 1024;
+
+// like `nom::multi::count`, without reserving capacity for the whole count
+pub(crate) fn bounded_count<'a, O, F>(
+    mut parser: F,
+    count: usize,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<O>, UnityPackError>
+where
+    F: FnMut(&'a [u8]) -> IResult<&'a [u8], O, UnityPackError>,
+{
+    move |mut input: &'a [u8]| {
+        let mut items = Vec::with_capacity(count.min(MAX_PREALLOCATED_ITEMS));
+        for _ in 0..count {
+            let (rest, item) = parser(input)?;
+            if rest.len() == input.len() {
+                // a parser that consumes nothing would spin through a huge count
+                return Err(nom::Err::Error(UnityPackError::from_error_kind(
+                    input,
+                    nom::error::ErrorKind::Count,
+                )));
+            }
+            input = rest;
+            items.push(item);
+        }
+        Ok((input, items))
+    }
+}
+
+// sizes and offsets are stored as 64-bit values, which may not fit a 32-bit usize
+pub(crate) fn usize_from_i64(value: i64) -> Result<usize, UnityPackError> {
+    use std::convert::TryFrom;
+    usize::try_from(value).map_err(|_| UnityPackError::OffsetOverflow(value))
+}
+
+// Unity version strings such as "2019.4.1f1" are far shorter than this
+pub(crate) const MAX_VERSION_STRING_LENGTH: usize = 64;
+
+// a missing terminator within `max_len` bytes is reported at the start of the string,
+// instead of scanning on into whatever follows it
+pub(crate) fn read_cstring_bytes(
+    input: &[u8],
+    max_len: usize,
+) -> IResult<&[u8], &[u8], UnityPackError> {
+    let bounded = &input[..input.len().min(max_len.saturating_add(1))];
+    let (_, bytes) =
+        take_until("\0")(bounded).map_err(|e| e.map(|e: UnityPackError| e.at(input.len())))?;
+    let (input, _) = take(bytes.len())(input)?;
     let (input, _) = tag(b"\0")(input)?;
-    Ok((input, std::str::from_utf8(signature).unwrap().to_string()))
+    Ok((input, bytes))
+}
+
+pub(crate) fn read_cstring(input: &[u8], max_len: usize) -> IResult<&[u8], String, UnityPackError> {
+    let (input, bytes) = read_cstring_bytes(input, max_len)?;
+    let string = std::str::from_utf8(bytes).map_err(|e| nom::Err::Failure(e.into()))?;
+    Ok((input, string.to_string()))
 }
 
+// the longest known signature is "UnityWebData1.0"
+pub(crate) const MAX_SIGNATURE_LENGTH: usize = 32;
+
+// signatures are checked against the known ones by the caller, so keep
+// whatever was read, even when it is not valid UTF-8
+fn read_string_to_null(input: &[u8]) -> IResult<&[u8], String, UnityPackError> {
+    let (input, bytes) = read_cstring_bytes(input, MAX_SIGNATURE_LENGTH)?;
+    Ok((input, String::from_utf8_lossy(bytes).into_owned()))
+}
+
+#[allow(dead_code, clippy::enum_variant_names)]
 enum FileType {
     AssetsFile,
     BundleFile,
@@ -22,7 +109,8 @@ enum FileType {
     ResourceFile,
 }
 
-fn check_file_type(input: &[u8]) -> IResult<&[u8], FileType> {
+#[allow(dead_code)]
+fn check_file_type(input: &[u8]) -> IResult<&[u8], FileType, UnityPackError> {
     let (input, signature) = read_string_to_null(input)?;
     let (input, file_type) = match signature.as_str() {
         "UnityWeb" | "UnityRaw" | "UnityArchive" | "UnityFS" => (input, FileType::BundleFile),
@@ -43,8 +131,8 @@ fn check_file_type(input: &[u8]) -> IResult<&[u8], FileType> {
     Ok((input, file_type))
 }
 
-fn check_serialized_file(input: &[u8]) -> IResult<&[u8], bool> {
-    let original_input = input.clone();
+fn check_serialized_file(input: &[u8]) -> IResult<&[u8], bool, UnityPackError> {
+    let original_input = input;
 
     if input.len() < 20 {
         return Ok((input, false));
@@ -67,7 +155,6 @@ fn check_serialized_file(input: &[u8]) -> IResult<&[u8], bool> {
         input = result.0;
         file_size = result.1 as u32;
         let result = be_u64(input)?;
-        input = result.0;
         data_offset = result.1 as u32;
     }
     let input = original_input;
@@ -80,3 +167,81 @@ fn check_serialized_file(input: &[u8]) -> IResult<&[u8], bool> {
     }
     Ok((input, true))
 }
+
+#[cfg(test)]
+mod tests {
+    use nom::number::complete::be_u32;
+
+    use crate::error::UnityPackError;
+    use crate::{bounded_count, read_cstring, usize_from_i64};
+
+    #[test]
+    fn test_bounded_count() {
+        let input = [0, 0, 0, 1, 0, 0, 0, 2, 9];
+        let (rest, items) = bounded_count(be_u32, 2)(&input).unwrap();
+        assert_eq!(vec![1, 2], items);
+        assert_eq!(&[9], rest);
+    }
+
+    #[test]
+    fn test_bounded_count_with_huge_count() {
+        // must fail on the missing input instead of reserving gigabytes first
+        let input = [0u8; 8];
+        assert!(matches!(
+            bounded_count(be_u32, 2_000_000_000)(&input),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Eof,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            bounded_count(|i| Ok((i, ())), 2_000_000_000)(&input),
+            Err(nom::Err::Error(UnityPackError::Parse {
+                kind: nom::error::ErrorKind::Count,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_usize_from_i64() {
+        assert_eq!(4096, usize_from_i64(4096).unwrap());
+        assert!(matches!(
+            usize_from_i64(-1),
+            Err(UnityPackError::OffsetOverflow(-1))
+        ));
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(1 << 32, usize_from_i64(1 << 32).unwrap());
+        #[cfg(target_pointer_width = "32")]
+        assert!(matches!(
+            usize_from_i64(1 << 32),
+            Err(UnityPackError::OffsetOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_cstring() {
+        let (rest, string) = read_cstring(b"5.x.x\0rest", 8).unwrap();
+        assert_eq!("5.x.x", string);
+        assert_eq!(b"rest", rest);
+        assert_eq!("", read_cstring(b"\0", 0).unwrap().1);
+    }
+
+    #[test]
+    fn test_read_cstring_without_terminator() {
+        for input in [&b"5.x.x"[..], &b"5.x.x.x.x\0"[..]] {
+            assert!(matches!(
+                read_cstring(input, 8),
+                Err(nom::Err::Error(UnityPackError::Parse { offset, .. })) if offset == input.len()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read_cstring_invalid_utf8() {
+        assert!(matches!(
+            read_cstring(b"\xFF\xFE\0", 8),
+            Err(nom::Err::Failure(UnityPackError::Utf8(_)))
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+use crate::error::UnityPackError;
+
+// the `.manifest` YAML the editor writes next to each bundle; only the fields needed to map
+// asset paths to their bundle are read, the rest is ignored
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct BundleManifest {
+    #[serde(rename = "CRC")]
+    pub crc: u32,
+    #[serde(rename = "Assets", default)]
+    pub assets: Vec<String>,
+    #[serde(rename = "Dependencies", default)]
+    pub dependencies: Vec<String>,
+}
+
+pub fn read_manifest(input: &str) -> Result<BundleManifest, UnityPackError> {
+    serde_yaml::from_str(input).map_err(|e| UnityPackError::Manifest(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::UnityPackError;
+    use crate::manifest::{read_manifest, BundleManifest};
+
+    const MANIFEST: &str = "ManifestFileVersion: 0
+CRC: 2817402881
+Hashes:
+  AssetFileHash:
+    serializedVersion: 2
+    Hash: 1d5a8a3fb1b17a6e2b0cfa4f0c9c9d3e
+  TypeTreeHash:
+    serializedVersion: 2
+    Hash: 6a3d1f60f3b2e8a6d4a383b34aac3de1
+HashAppended: 0
+ClassTypes:
+- Class: 28
+  Script: {instanceID: 0}
+SerializeReferenceClassIdentifiers: []
+Assets:
+- Assets/Item/item_icon_00000.png
+Dependencies:
+- /build/AssetBundles/shared/common
+";
+
+    #[test]
+    fn test_read_manifest() {
+        assert_eq!(
+            BundleManifest {
+                crc: 2817402881,
+                assets: vec!["Assets/Item/item_icon_00000.png".to_string()],
+                dependencies: vec!["/build/AssetBundles/shared/common".to_string()],
+            },
+            read_manifest(MANIFEST).unwrap()
+        );
+
+        let manifest = read_manifest("CRC: 0\nAssets: []\nDependencies: []\n").unwrap();
+        assert_eq!(BundleManifest::default(), manifest);
+    }
+
+    #[test]
+    fn test_read_manifest_without_crc() {
+        assert!(matches!(
+            read_manifest("Assets: []\n"),
+            Err(UnityPackError::Manifest(_))
+        ));
+    }
+}
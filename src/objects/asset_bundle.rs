@@ -0,0 +1,294 @@
+use std::convert::TryFrom;
+
+use nom::{
+    number::complete::{i32, u32},
+    IResult,
+};
+
+use crate::asset::{read_aligned_string, read_serialized_file, Endianness};
+use crate::asset_bundle::UnityAssetBundle;
+use crate::bounded_count;
+use crate::error::UnityPackError;
+use crate::objects::{align, read_bool, read_pptr, PPtr};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub preload_index: i32,
+    pub preload_size: i32,
+    pub asset: PPtr,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AssetBundleObject {
+    pub name: String,
+    pub preload_table: Vec<PPtr>,
+    // m_Container is a multimap, so keep every entry in file order
+    pub container: Vec<(String, AssetInfo)>,
+    pub main_asset: AssetInfo,
+    pub runtime_compatibility: u32,
+    pub asset_bundle_name: String,
+    pub dependencies: Vec<String>,
+    pub is_streamed_scene_asset_bundle: bool,
+    pub explicit_data_layout: i32,
+    pub path_flags: i32,
+    pub scene_hashes: Vec<(String, String)>,
+}
+
+impl AssetBundleObject {
+    // the PPtrs that have to be loaded together with the asset at `container[index]`
+    #[must_use]
+    pub fn preloads_of(&self, index: usize) -> Option<&[PPtr]> {
+        let info = &self.container.get(index)?.1;
+        let start = usize::try_from(info.preload_index).ok()?;
+        let end = start.checked_add(usize::try_from(info.preload_size).ok()?)?;
+        self.preload_table.get(start..end)
+    }
+
+    // scene bundles leave m_Container empty and list their scenes in m_SceneHashes
+    #[must_use]
+    pub fn asset_paths(&self) -> Vec<&str> {
+        if self.is_streamed_scene_asset_bundle {
+            self.scene_hashes
+                .iter()
+                .map(|(scene, _)| scene.as_str())
+                .collect()
+        } else {
+            self.container
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect()
+        }
+    }
+}
+
+const ASSET_BUNDLE_CLASS_ID: i32 = 142;
+
+impl UnityAssetBundle {
+    // the AssetBundle object of the first serialized file that has one
+    pub fn asset_bundle_object(&self) -> Result<Option<AssetBundleObject>, UnityPackError> {
+        for stream_file in self.serialized_files() {
+            let serialized_file = read_serialized_file(&stream_file.body)?;
+            if let Some((_, data)) = serialized_file
+                .objects_of_class(ASSET_BUNDLE_CLASS_ID)
+                .first()
+            {
+                let (_, object) = read_asset_bundle_object(data, serialized_file.endianness())?;
+                return Ok(Some(object));
+            }
+        }
+        Ok(None)
+    }
+
+    // m_AssetBundleName, the name assigned in the editor that manifests and Addressables
+    // refer to; unnamed bundles and unreadable objects give `None`, use
+    // `asset_bundle_object` to see the error
+    #[must_use]
+    pub fn asset_bundle_name(&self) -> Option<String> {
+        self.asset_bundle_object()
+            .ok()
+            .flatten()
+            .map(|object| object.asset_bundle_name)
+            .filter(|name| !name.is_empty())
+    }
+
+    // scene bundles hold BuildPlayer-* scene files instead of loose assets,
+    // so the objects of their serialized files are not addressable by path
+    pub fn is_streamed_scene_bundle(&self) -> Result<bool, UnityPackError> {
+        Ok(self
+            .asset_bundle_object()?
+            .is_some_and(|object| object.is_streamed_scene_asset_bundle))
+    }
+}
+
+// field layout of Unity 2019.x
+pub fn read_asset_bundle_object(
+    object: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], AssetBundleObject, UnityPackError> {
+    let (input, name) = read_aligned_string(object, endianness)?;
+    let (input, preload_count) = i32(endianness)(input)?;
+    let (input, preload_table) =
+        bounded_count(|i| read_pptr(i, endianness), preload_count as usize)(input)?;
+    let (input, container_count) = i32(endianness)(input)?;
+    let (input, container) = bounded_count(
+        |i| {
+            let (i, key) = read_aligned_string(i, endianness)?;
+            let (i, info) = read_asset_info(i, endianness)?;
+            Ok((i, (key, info)))
+        },
+        container_count as usize,
+    )(input)?;
+    let (input, main_asset) = read_asset_info(input, endianness)?;
+    let (input, runtime_compatibility) = u32(endianness)(input)?;
+    let (input, asset_bundle_name) = read_aligned_string(input, endianness)?;
+    let (input, dependency_count) = i32(endianness)(input)?;
+    let (input, dependencies) = bounded_count(
+        |i| read_aligned_string(i, endianness),
+        dependency_count as usize,
+    )(input)?;
+    let (input, is_streamed_scene_asset_bundle) = read_bool(input)?;
+    let (input, _) = align(object, input)?;
+    let (input, explicit_data_layout) = i32(endianness)(input)?;
+    let (input, path_flags) = i32(endianness)(input)?;
+    let (input, scene_hash_count) = i32(endianness)(input)?;
+    let (input, scene_hashes) = bounded_count(
+        |i| {
+            let (i, scene) = read_aligned_string(i, endianness)?;
+            let (i, hash) = read_aligned_string(i, endianness)?;
+            Ok((i, (scene, hash)))
+        },
+        scene_hash_count as usize,
+    )(input)?;
+
+    Ok((
+        input,
+        AssetBundleObject {
+            name,
+            preload_table,
+            container,
+            main_asset,
+            runtime_compatibility,
+            asset_bundle_name,
+            dependencies,
+            is_streamed_scene_asset_bundle,
+            explicit_data_layout,
+            path_flags,
+            scene_hashes,
+        },
+    ))
+}
+
+fn read_asset_info(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], AssetInfo, UnityPackError> {
+    let (input, preload_index) = i32(endianness)(input)?;
+    let (input, preload_size) = i32(endianness)(input)?;
+    let (input, asset) = read_pptr(input, endianness)?;
+    Ok((
+        input,
+        AssetInfo {
+            preload_index,
+            preload_size,
+            asset,
+        },
+    ))
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset::Endianness;
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::objects::asset_bundle::{read_asset_bundle_object, AssetBundleObject, AssetInfo};
+    use crate::objects::PPtr;
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_read_asset_bundle_object() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the AssetBundle object occupies 4096..4308 of the serialized file
+        let object = &unity_asset_bundle.stream_files[0].body[4096..4308];
+        let (rest, asset_bundle) = read_asset_bundle_object(object, Endianness::Little).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!("item/item_icon_00000.a", asset_bundle.name);
+        let texture = PPtr {
+            file_id: 0,
+            path_id: 7665299545025588016,
+        };
+        assert_eq!(vec![texture], asset_bundle.preload_table);
+        assert_eq!(1, asset_bundle.container.len());
+        assert_eq!(
+            "assets/_gallopresources/bundle/resources/item/item_icon_00000.png",
+            asset_bundle.container[0].0
+        );
+        assert_eq!(texture, asset_bundle.container[0].1.asset);
+        assert_eq!(Some(&[texture][..]), asset_bundle.preloads_of(0));
+        assert_eq!(7, asset_bundle.path_flags);
+    }
+
+    #[test]
+    fn test_preloads_of_corrupt_range() {
+        let mut asset_bundle = AssetBundleObject {
+            preload_table: vec![PPtr::default(); 2],
+            ..Default::default()
+        };
+        for (preload_index, preload_size) in [(-1, 1), (0, -1), (1, 2), (i32::MAX, i32::MAX)] {
+            let info = AssetInfo {
+                preload_index,
+                preload_size,
+                ..Default::default()
+            };
+            asset_bundle.container = vec![(String::new(), info)];
+            assert_eq!(None, asset_bundle.preloads_of(0));
+        }
+    }
+
+    #[test]
+    fn test_preload_table_covers_every_object() {
+        let file = read_file("./anm_chara_tear_animator");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the serialized file holds 3 objects, one of which is the AssetBundle at 32408..32684
+        let object = &unity_asset_bundle.stream_files[0].body[32408..32684];
+        let asset_bundle = read_asset_bundle_object(object, Endianness::Little)
+            .unwrap()
+            .1;
+        let mut path_ids = asset_bundle
+            .preload_table
+            .iter()
+            .map(|pptr| pptr.path_id)
+            .collect::<Vec<_>>();
+        path_ids.sort_unstable();
+        assert_eq!(vec![-571295540371429627, 2141629224424772712], path_ids);
+    }
+
+    #[test]
+    fn test_asset_bundle_object_of_bundle() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let asset_bundle = unity_asset_bundle.asset_bundle_object().unwrap().unwrap();
+        assert_eq!("item/item_icon_00000.a", asset_bundle.name);
+        assert!(!unity_asset_bundle.is_streamed_scene_bundle().unwrap());
+        assert_eq!(
+            vec!["assets/_gallopresources/bundle/resources/item/item_icon_00000.png"],
+            asset_bundle.asset_paths()
+        );
+    }
+
+    #[test]
+    fn test_asset_bundle_name() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(
+            Some("item/item_icon_00000.a"),
+            unity_asset_bundle.asset_bundle_name().as_deref()
+        );
+        let file = read_file("./anm_chara_tear_animator");
+        assert_eq!(
+            Some("3d/animator/anm_chara_tear_animator.a"),
+            read_unity_asset_bundle(&file)
+                .unwrap()
+                .1
+                .asset_bundle_name()
+                .as_deref()
+        );
+
+        // without a serialized file there is no AssetBundle object to read it from
+        unity_asset_bundle.directory_info[0].flags = 0;
+        assert_eq!(None, unity_asset_bundle.asset_bundle_name());
+    }
+
+    #[test]
+    fn test_streamed_scene_bundle() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // m_IsStreamedSceneAssetBundle is followed by padding, m_ExplicitDataLayout,
+        // m_PathFlags and an empty m_SceneHashes, so it sits 16 bytes before the object end
+        unity_asset_bundle.stream_files[0].body[4308 - 16] = 1;
+        let asset_bundle = unity_asset_bundle.asset_bundle_object().unwrap().unwrap();
+        assert!(asset_bundle.is_streamed_scene_asset_bundle);
+        assert!(unity_asset_bundle.is_streamed_scene_bundle().unwrap());
+        assert!(asset_bundle.asset_paths().is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use nom::{
+    number::complete::{i32, u16, u32},
+    IResult,
+};
+
+use crate::asset::{read_aligned_string, Endianness};
+use crate::bounded_count;
+use crate::error::UnityPackError;
+use crate::objects::{read_bool, read_pptr, PPtr};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GameObject {
+    // since 5.5 the component pairs no longer store the class id next to the PPtr
+    pub components: Vec<PPtr>,
+    pub layer: u32,
+    pub name: String,
+    pub tag: u16,
+    pub is_active: bool,
+}
+
+// field layout of Unity 2019.x
+pub fn read_game_object(
+    object: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], GameObject, UnityPackError> {
+    let (input, component_count) = i32(endianness)(object)?;
+    let (input, components) =
+        bounded_count(|i| read_pptr(i, endianness), component_count as usize)(input)?;
+    let (input, layer) = u32(endianness)(input)?;
+    let (input, name) = read_aligned_string(input, endianness)?;
+    let (input, tag) = u16(endianness)(input)?;
+    let (input, is_active) = read_bool(input)?;
+
+    Ok((
+        input,
+        GameObject {
+            components,
+            layer,
+            name,
+            tag,
+            is_active,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Endianness;
+    use crate::objects::game_object::read_game_object;
+    use crate::objects::PPtr;
+    use crate::test_fixtures::{push_i32, push_pptr, push_string, push_u32};
+
+    // none of the sample bundles holds a GameObject, so this one is laid out by hand:
+    // a Transform and a MeshRenderer, layer 5, "Cube", untagged and active
+    fn build_game_object() -> Vec<u8> {
+        let mut object = vec![];
+        push_i32(&mut object, 2);
+        push_pptr(&mut object, 0, -2);
+        push_pptr(&mut object, 0, 7);
+        push_u32(&mut object, 5);
+        push_string(&mut object, "Cube");
+        object.extend_from_slice(&0u16.to_le_bytes());
+        object.push(1);
+        object
+    }
+
+    #[test]
+    fn test_read_game_object() {
+        let object = build_game_object();
+        let (rest, game_object) = read_game_object(&object, Endianness::Little).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!("Cube", game_object.name);
+        assert_eq!(
+            vec![
+                PPtr {
+                    file_id: 0,
+                    path_id: -2
+                },
+                PPtr {
+                    file_id: 0,
+                    path_id: 7
+                }
+            ],
+            game_object.components
+        );
+        assert_eq!(5, game_object.layer);
+        assert_eq!(0, game_object.tag);
+        assert!(game_object.is_active);
+    }
+
+    #[test]
+    fn test_read_truncated_game_object() {
+        let object = build_game_object();
+        for len in 0..object.len() {
+            assert!(
+                read_game_object(&object[..len], Endianness::Little).is_err(),
+                "{} bytes",
+                len
+            );
+        }
+    }
+}
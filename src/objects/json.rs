@@ -0,0 +1,285 @@
+use std::convert::TryFrom;
+
+use nom::{
+    bytes::complete::take,
+    error::{ErrorKind, ParseError},
+    number::complete::{f32, f64, i16, i32, i64, i8, u16, u32, u64, u8},
+    IResult,
+};
+use serde_json::{Map, Value};
+
+use crate::asset::{Endianness, SerializedFile, TypeTree};
+use crate::bounded_count;
+use crate::error::UnityPackError;
+use crate::objects::{align, read_length};
+
+// kAlignBytes
+const ALIGN_BYTES: u32 = 0x4000;
+
+// walks an object with the type tree stored next to it in the serialized file, so any
+// class can be dumped without a parser of its own
+pub fn dump_object_json(
+    serialized: &SerializedFile,
+    path_id: i64,
+) -> Result<Value, UnityPackError> {
+    let (object, data) = serialized
+        .object_by_path_id(path_id)
+        .ok_or(UnityPackError::ObjectNotFound(path_id))?;
+    // before version 16 objects name their class instead of indexing the types
+    let serialized_type = if serialized.header.version < 16 {
+        serialized
+            .types
+            .iter()
+            .find(|t| t.class_id == object.class_id)
+    } else {
+        usize::try_from(object.type_id)
+            .ok()
+            .and_then(|index| serialized.types.get(index))
+    };
+    let tree = serialized_type
+        .and_then(|t| t.type_tree.as_ref())
+        .filter(|tree| !tree.nodes.is_empty())
+        .ok_or(UnityPackError::MissingTypeTree(object.class_id))?;
+    let reader = TreeReader {
+        tree,
+        object: data,
+        endianness: serialized.endianness(),
+    };
+    let (_, value) = reader
+        .read_node(data, 0)
+        .map_err(|e| UnityPackError::from(e).locate(data.len(), object.byte_start as usize))?;
+    Ok(value)
+}
+
+struct TreeReader<'t, 'o> {
+    tree: &'t TypeTree,
+    object: &'o [u8],
+    endianness: Endianness,
+}
+
+impl<'t, 'o> TreeReader<'t, 'o> {
+    // nodes are stored depth first, so a subtree runs until the next node at its level or above
+    fn subtree_end(&self, index: usize) -> usize {
+        let level = self.tree.nodes[index].level;
+        self.tree.nodes[index + 1..]
+            .iter()
+            .position(|node| node.level <= level)
+            .map_or(self.tree.nodes.len(), |position| index + 1 + position)
+    }
+
+    fn node_type(&self, input: &[u8], index: usize) -> Result<&'t str, nom::Err<UnityPackError>> {
+        self.tree
+            .nodes
+            .get(index)
+            .and_then(|node| self.tree.node_type(node))
+            .ok_or_else(|| {
+                nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Verify))
+            })
+    }
+
+    fn has_children(&self, index: usize) -> bool {
+        self.subtree_end(index) > index + 1
+    }
+
+    fn read_node(&self, input: &'o [u8], index: usize) -> IResult<&'o [u8], Value, UnityPackError> {
+        let endianness = self.endianness;
+        let (input, value) = match self.node_type(input, index)? {
+            "bool" => {
+                let (input, value) = u8(input)?;
+                (input, Value::Bool(value != 0))
+            }
+            "SInt8" => {
+                let (input, value) = i8(input)?;
+                (input, value.into())
+            }
+            "UInt8" | "char" => {
+                let (input, value) = u8(input)?;
+                (input, value.into())
+            }
+            "SInt16" | "short" => {
+                let (input, value) = i16(endianness)(input)?;
+                (input, value.into())
+            }
+            "UInt16" | "unsigned short" => {
+                let (input, value) = u16(endianness)(input)?;
+                (input, value.into())
+            }
+            "SInt32" | "int" => {
+                let (input, value) = i32(endianness)(input)?;
+                (input, value.into())
+            }
+            "UInt32" | "unsigned int" | "Type*" => {
+                let (input, value) = u32(endianness)(input)?;
+                (input, value.into())
+            }
+            "SInt64" | "long long" => {
+                let (input, value) = i64(endianness)(input)?;
+                (input, value.into())
+            }
+            "UInt64" | "unsigned long long" | "FileSize" => {
+                let (input, value) = u64(endianness)(input)?;
+                (input, value.into())
+            }
+            // JSON has no NaN or infinity, serde_json turns them into null
+            "float" => {
+                let (input, value) = f32(endianness)(input)?;
+                (input, f64::from(value).into())
+            }
+            "double" => {
+                let (input, value) = f64(endianness)(input)?;
+                (input, value.into())
+            }
+            "string" => {
+                let (rest, length) = read_length(input, endianness)?;
+                let (rest, bytes) = take(length)(rest)?;
+                let string = std::str::from_utf8(bytes).map_err(|_| {
+                    nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Char))
+                })?;
+                // the padding is flagged on the Array node inside the string
+                let rest = if self.has_children(index) {
+                    self.align_after(rest, index + 1)
+                } else {
+                    rest
+                };
+                (rest, Value::String(string.to_string()))
+            }
+            "TypelessData" => {
+                let (input, length) = read_length(input, endianness)?;
+                let (input, bytes) = take(length)(input)?;
+                (input, bytes.to_vec().into())
+            }
+            "Array" => self.read_array(input, index)?,
+            // vectors, maps and strings wrap an Array node that carries their contents
+            _ if self.has_children(index) && self.node_type(input, index + 1)? == "Array" => {
+                self.read_array(input, index + 1)?
+            }
+            _ => {
+                let mut fields = Map::new();
+                let mut input = input;
+                let end = self.subtree_end(index);
+                let mut child = index + 1;
+                while child < end {
+                    let (rest, value) = self.read_node(input, child)?;
+                    input = rest;
+                    let name = self
+                        .tree
+                        .node_name(&self.tree.nodes[child])
+                        .unwrap_or_default();
+                    fields.insert(name.to_string(), value);
+                    child = self.subtree_end(child);
+                }
+                (input, Value::Object(fields))
+            }
+        };
+        Ok((self.align_after(input, index), value))
+    }
+
+    // an Array node holds its size followed by the element subtree
+    fn read_array(
+        &self,
+        input: &'o [u8],
+        index: usize,
+    ) -> IResult<&'o [u8], Value, UnityPackError> {
+        let element = index + 2;
+        if element >= self.subtree_end(index) {
+            return Err(nom::Err::Error(UnityPackError::from_error_kind(
+                input,
+                ErrorKind::Verify,
+            )));
+        }
+        let (input, length) = read_length(input, self.endianness)?;
+        let (input, values) = bounded_count(|i| self.read_node(i, element), length)(input)?;
+        Ok((self.align_after(input, index), Value::Array(values)))
+    }
+
+    fn align_after(&self, input: &'o [u8], index: usize) -> &'o [u8] {
+        if self.tree.nodes[index].meta_flag & ALIGN_BYTES == 0 {
+            return input;
+        }
+        // the last field of an object may omit its padding
+        align(self.object, input).map_or(&input[input.len()..], |(rest, _)| rest)
+    }
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset::read_serialized_file;
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::error::UnityPackError;
+    use crate::objects::json::dump_object_json;
+    use crate::test_fixtures::read_file;
+
+    #[test]
+    fn test_dump_object_json() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        let texture = serialized_file.objects_of_class(28)[0].0;
+        let json = dump_object_json(&serialized_file, texture.path_id).unwrap();
+        assert_eq!("item_icon_00000", json["m_Name"]);
+        assert_eq!(256, json["m_Width"]);
+        assert_eq!(256, json["m_Height"]);
+        // DXT5
+        assert_eq!(12, json["m_TextureFormat"]);
+        assert_eq!(false, json["m_IsReadable"]);
+        assert_eq!(0.0, json["m_TextureSettings"]["m_MipBias"]);
+        assert_eq!(serde_json::json!([]), json["image data"]);
+        assert_eq!(65536, json["m_StreamData"]["size"]);
+        assert_eq!(
+            "archive:/CAB-5813386f0ea15049abeb5a688d9031d3/CAB-5813386f0ea15049abeb5a688d9031d3.resS",
+            json["m_StreamData"]["path"]
+        );
+
+        let asset_bundle = serialized_file.objects_of_class(142)[0].0;
+        let json = dump_object_json(&serialized_file, asset_bundle.path_id).unwrap();
+        assert_eq!("item/item_icon_00000.a", json["m_AssetBundleName"]);
+        assert_eq!(
+            serde_json::json!({"m_FileID": 0, "m_PathID": texture.path_id}),
+            json["m_PreloadTable"][0]
+        );
+        // maps come out as a list of pairs, their keys are not always strings
+        assert_eq!(
+            "assets/_gallopresources/bundle/resources/item/item_icon_00000.png",
+            json["m_Container"][0]["first"]
+        );
+        assert_eq!(1, json["m_Container"][0]["second"]["preloadSize"]);
+    }
+
+    #[test]
+    fn test_dump_object_json_nested_classes() {
+        let file = read_file("./anm_chara_tear_animator");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        let clip = serialized_file.objects_of_class(74)[0].0;
+        let json = dump_object_json(&serialized_file, clip.path_id).unwrap();
+        assert_eq!("anm_tear000_00", json["m_Name"]);
+        assert_eq!(60.0, json["m_SampleRate"]);
+        let controller = serialized_file.objects_of_class(91)[0].0;
+        let json = dump_object_json(&serialized_file, controller.path_id).unwrap();
+        assert_eq!("anm_chara_tear_animator", json["m_Name"]);
+        assert_eq!(888, json["m_ControllerSize"]);
+    }
+
+    #[test]
+    fn test_dump_object_json_errors() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        assert!(matches!(
+            dump_object_json(&serialized_file, 42),
+            Err(UnityPackError::ObjectNotFound(42))
+        ));
+        let texture = serialized_file.objects_of_class(28)[0].0;
+        for serialized_type in &mut serialized_file.types {
+            serialized_type.type_tree = None;
+        }
+        assert!(matches!(
+            dump_object_json(&serialized_file, texture.path_id),
+            Err(UnityPackError::MissingTypeTree(28))
+        ));
+    }
+}
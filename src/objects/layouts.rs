@@ -0,0 +1,1042 @@
+use std::convert::TryFrom;
+
+use nom::{
+    bytes::complete::take,
+    error::{ErrorKind, ParseError},
+    number::complete::{f32, i16, i32, i64, u16, u32, u64, u8},
+    IResult,
+};
+
+use crate::asset::Endianness;
+use crate::bounded_count;
+use crate::error::UnityPackError;
+use crate::objects::{align, read_length, read_pptr, PPtr};
+
+// hardcoded layouts let objects be read when the type trees were stripped from the build,
+// which is how AssetStudio handles such files
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    // strings are always padded to 4 bytes
+    String,
+    // an i32 length followed by raw bytes, like m_Script or image data
+    ByteArray,
+    PPtr,
+    Array(&'static FieldType),
+    Struct(&'static [Field]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    // kAlignBytes, padding to 4 bytes after the field
+    pub align: bool,
+}
+
+const fn field(name: &'static str, field_type: FieldType) -> Field {
+    Field {
+        name,
+        field_type,
+        align: false,
+    }
+}
+
+const fn aligned(name: &'static str, field_type: FieldType) -> Field {
+    Field {
+        name,
+        field_type,
+        align: true,
+    }
+}
+
+// major.minor of the Unity version, `end` is exclusive and `None` is open ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub start: (u32, u32),
+    pub end: Option<(u32, u32)>,
+}
+
+impl VersionRange {
+    #[must_use]
+    pub fn contains(&self, version: (u32, u32)) -> bool {
+        version >= self.start && self.end.is_none_or(|end| version < end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassLayout {
+    pub class_id: i32,
+    pub class_name: &'static str,
+    pub versions: VersionRange,
+    pub fields: &'static [Field],
+}
+
+pub const TEXTURE_2D_CLASS_ID: i32 = 28;
+pub const MESH_CLASS_ID: i32 = 43;
+pub const TEXT_ASSET_CLASS_ID: i32 = 49;
+pub const AUDIO_CLIP_CLASS_ID: i32 = 83;
+pub const SPRITE_CLASS_ID: i32 = 213;
+
+const VECTOR_2F: FieldType =
+    FieldType::Struct(&[field("x", FieldType::Float), field("y", FieldType::Float)]);
+
+const VECTOR_3F: FieldType = FieldType::Struct(&[
+    field("x", FieldType::Float),
+    field("y", FieldType::Float),
+    field("z", FieldType::Float),
+]);
+
+const VECTOR_4F: FieldType = FieldType::Struct(&[
+    field("x", FieldType::Float),
+    field("y", FieldType::Float),
+    field("z", FieldType::Float),
+    field("w", FieldType::Float),
+]);
+
+const QUATERNION_F: FieldType = VECTOR_4F;
+
+const RECT_F: FieldType = FieldType::Struct(&[
+    field("x", FieldType::Float),
+    field("y", FieldType::Float),
+    field("width", FieldType::Float),
+    field("height", FieldType::Float),
+]);
+
+const MATRIX_4X4_F: FieldType = FieldType::Struct(&[
+    field("e00", FieldType::Float),
+    field("e01", FieldType::Float),
+    field("e02", FieldType::Float),
+    field("e03", FieldType::Float),
+    field("e10", FieldType::Float),
+    field("e11", FieldType::Float),
+    field("e12", FieldType::Float),
+    field("e13", FieldType::Float),
+    field("e20", FieldType::Float),
+    field("e21", FieldType::Float),
+    field("e22", FieldType::Float),
+    field("e23", FieldType::Float),
+    field("e30", FieldType::Float),
+    field("e31", FieldType::Float),
+    field("e32", FieldType::Float),
+    field("e33", FieldType::Float),
+]);
+
+const AABB: FieldType =
+    FieldType::Struct(&[field("m_Center", VECTOR_3F), field("m_Extent", VECTOR_3F)]);
+
+const MIN_MAX_AABB: FieldType =
+    FieldType::Struct(&[field("m_Min", VECTOR_3F), field("m_Max", VECTOR_3F)]);
+
+// offset became 64-bit in 2020.1, which none of the layouts below reach
+const STREAMING_INFO: FieldType = FieldType::Struct(&[
+    field("offset", FieldType::UInt32),
+    field("size", FieldType::UInt32),
+    field("path", FieldType::String),
+]);
+
+const GL_TEXTURE_SETTINGS: FieldType = FieldType::Struct(&[
+    field("m_FilterMode", FieldType::Int32),
+    field("m_Aniso", FieldType::Int32),
+    field("m_MipBias", FieldType::Float),
+    field("m_WrapU", FieldType::Int32),
+    field("m_WrapV", FieldType::Int32),
+    field("m_WrapW", FieldType::Int32),
+]);
+
+const SUB_MESH: FieldType = FieldType::Struct(&[
+    field("firstByte", FieldType::UInt32),
+    field("indexCount", FieldType::UInt32),
+    field("topology", FieldType::Int32),
+    field("baseVertex", FieldType::UInt32),
+    field("firstVertex", FieldType::UInt32),
+    field("vertexCount", FieldType::UInt32),
+    field("localAABB", AABB),
+]);
+
+const CHANNEL_INFO: FieldType = FieldType::Struct(&[
+    field("stream", FieldType::UInt8),
+    field("offset", FieldType::UInt8),
+    field("format", FieldType::UInt8),
+    field("dimension", FieldType::UInt8),
+]);
+
+const VERTEX_DATA: FieldType = FieldType::Struct(&[
+    field("m_VertexCount", FieldType::UInt32),
+    field("m_Channels", FieldType::Array(&CHANNEL_INFO)),
+    aligned("m_DataSize", FieldType::ByteArray),
+]);
+
+const PACKED_FLOAT_VECTOR: FieldType = FieldType::Struct(&[
+    field("m_NumItems", FieldType::UInt32),
+    field("m_Range", FieldType::Float),
+    field("m_Start", FieldType::Float),
+    aligned("m_Data", FieldType::ByteArray),
+    aligned("m_BitSize", FieldType::UInt8),
+]);
+
+const PACKED_INT_VECTOR: FieldType = FieldType::Struct(&[
+    field("m_NumItems", FieldType::UInt32),
+    aligned("m_Data", FieldType::ByteArray),
+    aligned("m_BitSize", FieldType::UInt8),
+]);
+
+const TEXTURE_2D_2018_2: &[Field] = &[
+    field("m_Name", FieldType::String),
+    field("m_ForcedFallbackFormat", FieldType::Int32),
+    aligned("m_DownscaleFallback", FieldType::Bool),
+    field("m_Width", FieldType::Int32),
+    field("m_Height", FieldType::Int32),
+    field("m_CompleteImageSize", FieldType::Int32),
+    field("m_TextureFormat", FieldType::Int32),
+    field("m_MipCount", FieldType::Int32),
+    field("m_IsReadable", FieldType::Bool),
+    aligned("m_StreamingMipmaps", FieldType::Bool),
+    field("m_StreamingMipmapsPriority", FieldType::Int32),
+    field("m_ImageCount", FieldType::Int32),
+    field("m_TextureDimension", FieldType::Int32),
+    field("m_TextureSettings", GL_TEXTURE_SETTINGS),
+    field("m_LightmapFormat", FieldType::Int32),
+    field("m_ColorSpace", FieldType::Int32),
+    aligned("image data", FieldType::ByteArray),
+    field("m_StreamData", STREAMING_INFO),
+];
+
+// 2019.3 added m_IgnoreMasterTextureLimit
+const TEXTURE_2D_2019_3: &[Field] = &[
+    field("m_Name", FieldType::String),
+    field("m_ForcedFallbackFormat", FieldType::Int32),
+    aligned("m_DownscaleFallback", FieldType::Bool),
+    field("m_Width", FieldType::Int32),
+    field("m_Height", FieldType::Int32),
+    field("m_CompleteImageSize", FieldType::Int32),
+    field("m_TextureFormat", FieldType::Int32),
+    field("m_MipCount", FieldType::Int32),
+    field("m_IsReadable", FieldType::Bool),
+    field("m_IgnoreMasterTextureLimit", FieldType::Bool),
+    aligned("m_StreamingMipmaps", FieldType::Bool),
+    field("m_StreamingMipmapsPriority", FieldType::Int32),
+    field("m_ImageCount", FieldType::Int32),
+    field("m_TextureDimension", FieldType::Int32),
+    field("m_TextureSettings", GL_TEXTURE_SETTINGS),
+    field("m_LightmapFormat", FieldType::Int32),
+    field("m_ColorSpace", FieldType::Int32),
+    aligned("image data", FieldType::ByteArray),
+    field("m_StreamData", STREAMING_INFO),
+];
+
+const TEXT_ASSET: &[Field] = &[
+    field("m_Name", FieldType::String),
+    aligned("m_Script", FieldType::ByteArray),
+];
+
+const AUDIO_CLIP_5: &[Field] = &[
+    field("m_Name", FieldType::String),
+    field("m_LoadType", FieldType::Int32),
+    field("m_Channels", FieldType::Int32),
+    field("m_Frequency", FieldType::Int32),
+    field("m_BitsPerSample", FieldType::Int32),
+    field("m_Length", FieldType::Float),
+    aligned("m_IsTrackerFormat", FieldType::Bool),
+    field("m_SubsoundIndex", FieldType::Int32),
+    field("m_PreloadAudioData", FieldType::Bool),
+    field("m_LoadInBackground", FieldType::Bool),
+    aligned("m_Legacy3D", FieldType::Bool),
+    field(
+        "m_Resource",
+        FieldType::Struct(&[
+            field("m_Source", FieldType::String),
+            field("m_Offset", FieldType::UInt64),
+            field("m_Size", FieldType::UInt64),
+        ]),
+    ),
+    field("m_CompressionFormat", FieldType::Int32),
+];
+
+const SPRITE_BONE: FieldType = FieldType::Struct(&[
+    field("name", FieldType::String),
+    field("position", VECTOR_3F),
+    field("rotation", QUATERNION_F),
+    field("length", FieldType::Float),
+    field("parentId", FieldType::Int32),
+]);
+
+const SPRITE_RENDER_DATA_2019: FieldType = FieldType::Struct(&[
+    field("texture", FieldType::PPtr),
+    field("alphaTexture", FieldType::PPtr),
+    field(
+        "secondaryTextures",
+        FieldType::Array(&FieldType::Struct(&[
+            field("texture", FieldType::PPtr),
+            field("name", FieldType::String),
+        ])),
+    ),
+    field("m_SubMeshes", FieldType::Array(&SUB_MESH)),
+    aligned("m_IndexBuffer", FieldType::ByteArray),
+    field("m_VertexData", VERTEX_DATA),
+    field("m_Bindpose", FieldType::Array(&MATRIX_4X4_F)),
+    field("textureRect", RECT_F),
+    field("textureRectOffset", VECTOR_2F),
+    field("atlasRectOffset", VECTOR_2F),
+    field("settingsRaw", FieldType::UInt32),
+    field("uvTransform", VECTOR_4F),
+    field("downscaleMultiplier", FieldType::Float),
+]);
+
+const SPRITE_2019: &[Field] = &[
+    field("m_Name", FieldType::String),
+    field("m_Rect", RECT_F),
+    field("m_Offset", VECTOR_2F),
+    field("m_Border", VECTOR_4F),
+    field("m_PixelsToUnits", FieldType::Float),
+    field("m_Pivot", VECTOR_2F),
+    field("m_Extrude", FieldType::UInt32),
+    aligned("m_IsPolygon", FieldType::Bool),
+    field(
+        "m_RenderDataKey",
+        FieldType::Struct(&[
+            field(
+                "first",
+                FieldType::Struct(&[
+                    field("data[0]", FieldType::UInt32),
+                    field("data[1]", FieldType::UInt32),
+                    field("data[2]", FieldType::UInt32),
+                    field("data[3]", FieldType::UInt32),
+                ]),
+            ),
+            field("second", FieldType::Int64),
+        ]),
+    ),
+    field("m_AtlasTags", FieldType::Array(&FieldType::String)),
+    field("m_SpriteAtlas", FieldType::PPtr),
+    field("m_RD", SPRITE_RENDER_DATA_2019),
+    field(
+        "m_PhysicsShape",
+        FieldType::Array(&FieldType::Array(&VECTOR_2F)),
+    ),
+    field("m_Bones", FieldType::Array(&SPRITE_BONE)),
+];
+
+const BLEND_SHAPE_DATA: FieldType = FieldType::Struct(&[
+    field(
+        "vertices",
+        FieldType::Array(&FieldType::Struct(&[
+            field("vertex", VECTOR_3F),
+            field("normal", VECTOR_3F),
+            field("tangent", VECTOR_3F),
+            field("index", FieldType::UInt32),
+        ])),
+    ),
+    field(
+        "shapes",
+        FieldType::Array(&FieldType::Struct(&[
+            field("firstVertex", FieldType::UInt32),
+            field("vertexCount", FieldType::UInt32),
+            field("hasNormals", FieldType::Bool),
+            aligned("hasTangents", FieldType::Bool),
+        ])),
+    ),
+    field(
+        "channels",
+        FieldType::Array(&FieldType::Struct(&[
+            field("name", FieldType::String),
+            field("nameHash", FieldType::UInt32),
+            field("frameIndex", FieldType::Int32),
+            field("frameCount", FieldType::Int32),
+        ])),
+    ),
+    field("fullWeights", FieldType::Array(&FieldType::Float)),
+]);
+
+const COMPRESSED_MESH: FieldType = FieldType::Struct(&[
+    field("m_Vertices", PACKED_FLOAT_VECTOR),
+    field("m_UV", PACKED_FLOAT_VECTOR),
+    field("m_Normals", PACKED_FLOAT_VECTOR),
+    field("m_Tangents", PACKED_FLOAT_VECTOR),
+    field("m_Weights", PACKED_INT_VECTOR),
+    field("m_NormalSigns", PACKED_INT_VECTOR),
+    field("m_TangentSigns", PACKED_INT_VECTOR),
+    field("m_FloatColors", PACKED_FLOAT_VECTOR),
+    field("m_BoneIndices", PACKED_INT_VECTOR),
+    field("m_Triangles", PACKED_INT_VECTOR),
+    field("m_UVInfo", FieldType::UInt32),
+]);
+
+const MESH_2019: &[Field] = &[
+    field("m_Name", FieldType::String),
+    field("m_SubMeshes", FieldType::Array(&SUB_MESH)),
+    field("m_Shapes", BLEND_SHAPE_DATA),
+    field("m_BindPose", FieldType::Array(&MATRIX_4X4_F)),
+    field("m_BoneNameHashes", FieldType::Array(&FieldType::UInt32)),
+    field("m_RootBoneNameHash", FieldType::UInt32),
+    field("m_BonesAABB", FieldType::Array(&MIN_MAX_AABB)),
+    field(
+        "m_VariableBoneCountWeights",
+        FieldType::Array(&FieldType::UInt32),
+    ),
+    field("m_MeshCompression", FieldType::UInt8),
+    field("m_IsReadable", FieldType::Bool),
+    field("m_KeepVertices", FieldType::Bool),
+    aligned("m_KeepIndices", FieldType::Bool),
+    field("m_IndexFormat", FieldType::Int32),
+    aligned("m_IndexBuffer", FieldType::ByteArray),
+    field("m_VertexData", VERTEX_DATA),
+    field("m_CompressedMesh", COMPRESSED_MESH),
+    field("m_LocalAABB", AABB),
+    field("m_MeshUsageFlags", FieldType::Int32),
+    aligned("m_BakedConvexCollisionMesh", FieldType::ByteArray),
+    aligned("m_BakedTriangleCollisionMesh", FieldType::ByteArray),
+    field("m_MeshMetrics[0]", FieldType::Float),
+    aligned("m_MeshMetrics[1]", FieldType::Float),
+    field("m_StreamData", STREAMING_INFO),
+];
+
+pub const CLASS_LAYOUTS: &[ClassLayout] = &[
+    ClassLayout {
+        class_id: TEXTURE_2D_CLASS_ID,
+        class_name: "Texture2D",
+        versions: VersionRange {
+            start: (2018, 2),
+            end: Some((2019, 3)),
+        },
+        fields: TEXTURE_2D_2018_2,
+    },
+    ClassLayout {
+        class_id: TEXTURE_2D_CLASS_ID,
+        class_name: "Texture2D",
+        versions: VersionRange {
+            start: (2019, 3),
+            end: Some((2020, 1)),
+        },
+        fields: TEXTURE_2D_2019_3,
+    },
+    ClassLayout {
+        class_id: MESH_CLASS_ID,
+        class_name: "Mesh",
+        versions: VersionRange {
+            start: (2019, 1),
+            end: Some((2020, 1)),
+        },
+        fields: MESH_2019,
+    },
+    ClassLayout {
+        class_id: TEXT_ASSET_CLASS_ID,
+        class_name: "TextAsset",
+        versions: VersionRange {
+            start: (5, 0),
+            end: None,
+        },
+        fields: TEXT_ASSET,
+    },
+    ClassLayout {
+        class_id: AUDIO_CLIP_CLASS_ID,
+        class_name: "AudioClip",
+        versions: VersionRange {
+            start: (5, 0),
+            end: None,
+        },
+        fields: AUDIO_CLIP_5,
+    },
+    ClassLayout {
+        class_id: SPRITE_CLASS_ID,
+        class_name: "Sprite",
+        versions: VersionRange {
+            start: (2019, 1),
+            end: Some((2020, 1)),
+        },
+        fields: SPRITE_2019,
+    },
+];
+
+// "2019.4.1f1" -> (2019, 4); stripped builds report "0.0.0", which matches no layout
+fn major_minor(unity_version: &str) -> Option<(u32, u32)> {
+    let mut parts = unity_version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[must_use]
+pub fn find_layout(class_id: i32, unity_version: &str) -> Option<&'static ClassLayout> {
+    let version = major_minor(unity_version)?;
+    CLASS_LAYOUTS
+        .iter()
+        .find(|layout| layout.class_id == class_id && layout.versions.contains(version))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    String(String),
+    Bytes(Vec<u8>),
+    PPtr(PPtr),
+    Array(Vec<FieldValue>),
+    Struct(Vec<(&'static str, FieldValue)>),
+}
+
+impl FieldValue {
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        match self {
+            FieldValue::Struct(fields) => fields
+                .iter()
+                .find(|(field_name, _)| *field_name == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FieldValue::Int(value) => Some(*value),
+            FieldValue::UInt(value) => i64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            FieldValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            FieldValue::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[FieldValue]> {
+        match self {
+            FieldValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_pptr(&self) -> Option<PPtr> {
+        match self {
+            FieldValue::PPtr(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_with_layout<'a>(
+    object: &'a [u8],
+    endianness: Endianness,
+    layout: &ClassLayout,
+) -> IResult<&'a [u8], FieldValue, UnityPackError> {
+    read_struct(object, object, layout.fields, endianness)
+}
+
+fn read_struct<'a>(
+    object: &[u8],
+    mut input: &'a [u8],
+    fields: &'static [Field],
+    endianness: Endianness,
+) -> IResult<&'a [u8], FieldValue, UnityPackError> {
+    let mut values = Vec::with_capacity(fields.len());
+    for field in fields {
+        let (rest, value) = read_value(object, input, &field.field_type, endianness)?;
+        input = rest;
+        if field.align {
+            // the last field of an object may omit its padding
+            input = align(object, input).map_or(&input[input.len()..], |(rest, _)| rest);
+        }
+        values.push((field.name, value));
+    }
+    Ok((input, FieldValue::Struct(values)))
+}
+
+fn read_value<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    field_type: &FieldType,
+    endianness: Endianness,
+) -> IResult<&'a [u8], FieldValue, UnityPackError> {
+    match *field_type {
+        FieldType::Bool => {
+            let (input, value) = u8(input)?;
+            Ok((input, FieldValue::Bool(value != 0)))
+        }
+        FieldType::UInt8 => {
+            let (input, value) = u8(input)?;
+            Ok((input, FieldValue::UInt(value.into())))
+        }
+        FieldType::Int16 => {
+            let (input, value) = i16(endianness)(input)?;
+            Ok((input, FieldValue::Int(value.into())))
+        }
+        FieldType::UInt16 => {
+            let (input, value) = u16(endianness)(input)?;
+            Ok((input, FieldValue::UInt(value.into())))
+        }
+        FieldType::Int32 => {
+            let (input, value) = i32(endianness)(input)?;
+            Ok((input, FieldValue::Int(value.into())))
+        }
+        FieldType::UInt32 => {
+            let (input, value) = u32(endianness)(input)?;
+            Ok((input, FieldValue::UInt(value.into())))
+        }
+        FieldType::Int64 => {
+            let (input, value) = i64(endianness)(input)?;
+            Ok((input, FieldValue::Int(value)))
+        }
+        FieldType::UInt64 => {
+            let (input, value) = u64(endianness)(input)?;
+            Ok((input, FieldValue::UInt(value)))
+        }
+        FieldType::Float => {
+            let (input, value) = f32(endianness)(input)?;
+            Ok((input, FieldValue::Float(value)))
+        }
+        FieldType::String => {
+            let (rest, length) = read_length(input, endianness)?;
+            let (rest, bytes) = take(length)(rest)?;
+            let string = std::str::from_utf8(bytes)
+                .map_err(|_| {
+                    nom::Err::Error(UnityPackError::from_error_kind(input, ErrorKind::Char))
+                })?
+                .to_string();
+            let rest = align(object, rest).map_or(&rest[rest.len()..], |(rest, _)| rest);
+            Ok((rest, FieldValue::String(string)))
+        }
+        FieldType::ByteArray => {
+            let (input, length) = read_length(input, endianness)?;
+            let (input, bytes) = take(length)(input)?;
+            Ok((input, FieldValue::Bytes(bytes.to_vec())))
+        }
+        FieldType::PPtr => {
+            let (input, pptr) = read_pptr(input, endianness)?;
+            Ok((input, FieldValue::PPtr(pptr)))
+        }
+        FieldType::Array(element) => {
+            let (input, length) = read_length(input, endianness)?;
+            let (input, values) =
+                bounded_count(|i| read_value(object, i, element, endianness), length)(input)?;
+            Ok((input, FieldValue::Array(values)))
+        }
+        FieldType::Struct(fields) => read_struct(object, input, fields, endianness),
+    }
+}
+
+#[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
+mod tests {
+    use crate::asset::{read_serialized_file, Endianness};
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::objects::layouts::{
+        find_layout, read_with_layout, FieldValue, AUDIO_CLIP_CLASS_ID, CLASS_LAYOUTS,
+        MESH_CLASS_ID, SPRITE_CLASS_ID, TEXTURE_2D_CLASS_ID, TEXT_ASSET_CLASS_ID,
+    };
+    use crate::objects::texture_2d::read_texture_2d;
+    use crate::objects::PPtr;
+    use crate::test_fixtures::{push_i32, push_pptr, push_string, push_u32, read_file};
+
+    fn push_f32s(object: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            object.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    // firstByte, indexCount, topology, baseVertex, firstVertex, vertexCount, localAABB
+    fn push_sub_mesh(object: &mut Vec<u8>, index_count: u32, vertex_count: u32) {
+        for value in [0, index_count, 0, 0, 0, vertex_count].iter().copied() {
+            push_u32(object, value);
+        }
+        push_f32s(object, &[0.0; 6]);
+    }
+
+    #[test]
+    fn test_find_layout() {
+        let texture = find_layout(TEXTURE_2D_CLASS_ID, "2019.4.1f1").unwrap();
+        assert_eq!("Texture2D", texture.class_name);
+        assert!(texture
+            .fields
+            .iter()
+            .any(|field| field.name == "m_IgnoreMasterTextureLimit"));
+        let older = find_layout(TEXTURE_2D_CLASS_ID, "2018.4.36f1").unwrap();
+        assert!(!older
+            .fields
+            .iter()
+            .any(|field| field.name == "m_IgnoreMasterTextureLimit"));
+        assert!(find_layout(TEXTURE_2D_CLASS_ID, "2017.4.40f1").is_none());
+        assert!(find_layout(TEXTURE_2D_CLASS_ID, "2020.3.0f1").is_none());
+        assert!(find_layout(TEXT_ASSET_CLASS_ID, "5.6.7f1").is_some());
+        assert!(find_layout(TEXT_ASSET_CLASS_ID, "0.0.0").is_none());
+        assert!(find_layout(114, "2019.4.1f1").is_none());
+        assert!(find_layout(TEXT_ASSET_CLASS_ID, "unknown").is_none());
+    }
+
+    #[test]
+    fn test_layout_version_ranges_do_not_overlap() {
+        for (i, a) in CLASS_LAYOUTS.iter().enumerate() {
+            for b in &CLASS_LAYOUTS[i + 1..] {
+                if a.class_id != b.class_id {
+                    continue;
+                }
+                assert!(
+                    a.versions.end.is_some_and(|end| end <= b.versions.start)
+                        || b.versions.end.is_some_and(|end| end <= a.versions.start),
+                    "{} layouts overlap",
+                    a.class_name
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_texture_2d_with_layout() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let serialized_file =
+            read_serialized_file(&unity_asset_bundle.stream_files[0].body).unwrap();
+        let layout = find_layout(TEXTURE_2D_CLASS_ID, serialized_file.unity_version()).unwrap();
+        let (_, object) = serialized_file.objects_of_class(TEXTURE_2D_CLASS_ID)[0];
+
+        let (rest, value) = read_with_layout(object, Endianness::Little, layout).unwrap();
+        assert!(rest.is_empty());
+        let texture = read_texture_2d(object, Endianness::Little).unwrap().1;
+        assert_eq!(
+            Some(texture.name.as_str()),
+            value.get("m_Name").and_then(FieldValue::as_str)
+        );
+        assert_eq!(
+            Some(i64::from(texture.width)),
+            value.get("m_Width").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(i64::from(texture.texture_format)),
+            value.get("m_TextureFormat").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(texture.texture_settings.mip_bias),
+            value
+                .get("m_TextureSettings")
+                .and_then(|settings| settings.get("m_MipBias"))
+                .and_then(FieldValue::as_f32)
+        );
+        let stream_data = value.get("m_StreamData").unwrap();
+        assert_eq!(
+            Some(i64::from(texture.stream_data.size)),
+            stream_data.get("size").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(texture.stream_data.path.as_str()),
+            stream_data.get("path").and_then(FieldValue::as_str)
+        );
+    }
+
+    #[test]
+    fn test_read_text_asset_with_layout() {
+        let mut object = vec![];
+        push_string(&mut object, "master_data");
+        let script = br#"{"id":1}"#;
+        push_u32(&mut object, script.len() as u32);
+        object.extend_from_slice(script);
+
+        let layout = find_layout(TEXT_ASSET_CLASS_ID, "2019.4.1f1").unwrap();
+        let (rest, value) = read_with_layout(&object, Endianness::Little, layout).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            Some("master_data"),
+            value.get("m_Name").and_then(FieldValue::as_str)
+        );
+        assert_eq!(
+            Some(&script[..]),
+            value.get("m_Script").and_then(FieldValue::as_bytes)
+        );
+    }
+
+    #[test]
+    fn test_read_audio_clip_with_layout() {
+        let mut object = vec![];
+        push_string(&mut object, "se_click");
+        // m_LoadType, m_Channels, m_Frequency, m_BitsPerSample
+        for value in [1, 2, 44100, 16].iter().copied() {
+            push_u32(&mut object, value);
+        }
+        push_f32s(&mut object, &[0.25]);
+        object.extend_from_slice(&[0, 0, 0, 0]);
+        push_u32(&mut object, 0);
+        object.extend_from_slice(&[1, 0, 0, 0]);
+        push_string(&mut object, "archive:/CAB-0/CAB-0.resource");
+        object.extend_from_slice(&32u64.to_le_bytes());
+        object.extend_from_slice(&4096u64.to_le_bytes());
+        // Vorbis
+        push_u32(&mut object, 1);
+
+        let layout = find_layout(AUDIO_CLIP_CLASS_ID, "2019.4.1f1").unwrap();
+        let (rest, value) = read_with_layout(&object, Endianness::Little, layout).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            Some(44100),
+            value.get("m_Frequency").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(0.25),
+            value.get("m_Length").and_then(FieldValue::as_f32)
+        );
+        assert_eq!(
+            Some(true),
+            value
+                .get("m_PreloadAudioData")
+                .and_then(FieldValue::as_bool)
+        );
+        let resource = value.get("m_Resource").unwrap();
+        assert_eq!(
+            Some("archive:/CAB-0/CAB-0.resource"),
+            resource.get("m_Source").and_then(FieldValue::as_str)
+        );
+        assert_eq!(
+            Some(4096),
+            resource.get("m_Size").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(1),
+            value
+                .get("m_CompressionFormat")
+                .and_then(FieldValue::as_i64)
+        );
+    }
+
+    #[test]
+    fn test_read_sprite_with_layout() {
+        let mut object = vec![];
+        push_string(&mut object, "icon");
+        // m_Rect, m_Offset, m_Border, m_PixelsToUnits, m_Pivot
+        push_f32s(&mut object, &[0.0, 0.0, 64.0, 64.0]);
+        push_f32s(&mut object, &[0.0; 2]);
+        push_f32s(&mut object, &[0.0; 4]);
+        push_f32s(&mut object, &[100.0, 0.5, 0.5]);
+        push_u32(&mut object, 1);
+        object.extend_from_slice(&[0, 0, 0, 0]);
+        // m_RenderDataKey
+        for value in [1, 2, 3, 4].iter().copied() {
+            push_u32(&mut object, value);
+        }
+        object.extend_from_slice(&21300000i64.to_le_bytes());
+        push_u32(&mut object, 1);
+        push_string(&mut object, "ui");
+        push_pptr(&mut object, 0, 0);
+        // m_RD
+        push_pptr(&mut object, 0, 42);
+        push_pptr(&mut object, 0, 0);
+        push_u32(&mut object, 0);
+        push_u32(&mut object, 1);
+        push_sub_mesh(&mut object, 6, 4);
+        push_u32(&mut object, 12);
+        object.extend_from_slice(&[0, 0, 1, 0, 2, 0, 2, 0, 3, 0, 0, 0]);
+        push_u32(&mut object, 4);
+        push_u32(&mut object, 1);
+        object.extend_from_slice(&[0, 0, 0, 3]);
+        push_u32(&mut object, 48);
+        push_f32s(&mut object, &[0.0; 12]);
+        push_u32(&mut object, 0);
+        push_f32s(&mut object, &[0.0, 0.0, 64.0, 64.0, 0.0, 0.0, 0.0, 0.0]);
+        push_u32(&mut object, 0);
+        push_f32s(&mut object, &[0.0; 4]);
+        push_f32s(&mut object, &[1.0]);
+        // m_PhysicsShape with one outline of a single point
+        push_u32(&mut object, 1);
+        push_u32(&mut object, 1);
+        push_f32s(&mut object, &[-0.32, 0.32]);
+        // m_Bones
+        push_u32(&mut object, 1);
+        push_string(&mut object, "root");
+        push_f32s(&mut object, &[0.0; 3]);
+        push_f32s(&mut object, &[0.0, 0.0, 0.0, 1.0, 0.5]);
+        push_i32(&mut object, -1);
+
+        let layout = find_layout(SPRITE_CLASS_ID, "2019.4.1f1").unwrap();
+        let (rest, value) = read_with_layout(&object, Endianness::Little, layout).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            Some("icon"),
+            value.get("m_Name").and_then(FieldValue::as_str)
+        );
+        assert_eq!(
+            Some(100.0),
+            value.get("m_PixelsToUnits").and_then(FieldValue::as_f32)
+        );
+        assert_eq!(
+            Some(21300000),
+            value
+                .get("m_RenderDataKey")
+                .and_then(|key| key.get("second"))
+                .and_then(FieldValue::as_i64)
+        );
+        let render_data = value.get("m_RD").unwrap();
+        assert_eq!(
+            Some(PPtr {
+                file_id: 0,
+                path_id: 42
+            }),
+            render_data.get("texture").and_then(FieldValue::as_pptr)
+        );
+        let sub_meshes = render_data
+            .get("m_SubMeshes")
+            .and_then(FieldValue::as_array)
+            .unwrap();
+        assert_eq!(
+            Some(6),
+            sub_meshes[0].get("indexCount").and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(12),
+            render_data
+                .get("m_IndexBuffer")
+                .and_then(FieldValue::as_bytes)
+                .map(<[u8]>::len)
+        );
+        let bones = value.get("m_Bones").and_then(FieldValue::as_array).unwrap();
+        assert_eq!(
+            Some(-1),
+            bones[0].get("parentId").and_then(FieldValue::as_i64)
+        );
+    }
+
+    #[test]
+    fn test_read_mesh_with_layout() {
+        let mut object = vec![];
+        push_string(&mut object, "quad");
+        push_u32(&mut object, 1);
+        push_sub_mesh(&mut object, 6, 4);
+        // m_Shapes, m_BindPose, m_BoneNameHashes, m_RootBoneNameHash,
+        // m_BonesAABB and m_VariableBoneCountWeights are all empty
+        for _ in 0..9 {
+            push_u32(&mut object, 0);
+        }
+        // m_MeshCompression, m_IsReadable, m_KeepVertices, m_KeepIndices
+        object.extend_from_slice(&[0, 1, 0, 0]);
+        // m_IndexFormat is UInt16
+        push_u32(&mut object, 0);
+        push_u32(&mut object, 12);
+        object.extend_from_slice(&[0, 0, 1, 0, 2, 0, 2, 0, 3, 0, 0, 0]);
+        // m_VertexData with a position channel
+        push_u32(&mut object, 4);
+        push_u32(&mut object, 1);
+        object.extend_from_slice(&[0, 0, 0, 3]);
+        push_u32(&mut object, 48);
+        push_f32s(&mut object, &[0.0; 12]);
+        // m_CompressedMesh
+        for _ in 0..4 {
+            push_u32(&mut object, 0);
+            push_f32s(&mut object, &[0.0, 0.0]);
+            push_u32(&mut object, 0);
+            push_u32(&mut object, 0);
+        }
+        for _ in 0..3 {
+            push_u32(&mut object, 0);
+            push_u32(&mut object, 0);
+            push_u32(&mut object, 0);
+        }
+        push_u32(&mut object, 0);
+        push_f32s(&mut object, &[0.0, 0.0]);
+        push_u32(&mut object, 0);
+        push_u32(&mut object, 0);
+        for _ in 0..2 {
+            push_u32(&mut object, 0);
+            push_u32(&mut object, 0);
+            push_u32(&mut object, 0);
+        }
+        push_u32(&mut object, 0);
+        // m_LocalAABB, m_MeshUsageFlags, both baked collision meshes
+        push_f32s(&mut object, &[0.0, 0.0, 0.0, 0.5, 0.5, 0.0]);
+        for _ in 0..3 {
+            push_u32(&mut object, 0);
+        }
+        push_f32s(&mut object, &[1.0, 1.0]);
+        push_u32(&mut object, 0);
+        push_u32(&mut object, 0);
+        push_string(&mut object, "");
+
+        let layout = find_layout(MESH_CLASS_ID, "2019.4.1f1").unwrap();
+        let (rest, value) = read_with_layout(&object, Endianness::Little, layout).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            Some("quad"),
+            value.get("m_Name").and_then(FieldValue::as_str)
+        );
+        assert_eq!(
+            Some(1),
+            value
+                .get("m_SubMeshes")
+                .and_then(FieldValue::as_array)
+                .map(<[FieldValue]>::len)
+        );
+        assert_eq!(
+            Some(true),
+            value.get("m_IsReadable").and_then(FieldValue::as_bool)
+        );
+        assert_eq!(
+            Some(4),
+            value
+                .get("m_VertexData")
+                .and_then(|vertex_data| vertex_data.get("m_VertexCount"))
+                .and_then(FieldValue::as_i64)
+        );
+        assert_eq!(
+            Some(0.5),
+            value
+                .get("m_LocalAABB")
+                .and_then(|aabb| aabb.get("m_Extent"))
+                .and_then(|extent| extent.get("x"))
+                .and_then(FieldValue::as_f32)
+        );
+        assert_eq!(
+            Some(""),
+            value
+                .get("m_StreamData")
+                .and_then(|stream_data| stream_data.get("path"))
+                .and_then(FieldValue::as_str)
+        );
+    }
+
+    #[test]
+    fn test_read_with_layout_truncated() {
+        let layout = find_layout(TEXT_ASSET_CLASS_ID, "2019.4.1f1").unwrap();
+        let mut object = vec![];
+        push_string(&mut object, "master_data");
+        push_u32(&mut object, 64);
+        assert!(read_with_layout(&object, Endianness::Little, layout).is_err());
+    }
+}
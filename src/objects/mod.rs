@@ -0,0 +1,117 @@
+use nom::{
+    bytes::complete::take,
+    error::{ErrorKind, ParseError},
+    number::complete::{i32, i64, u32, u8},
+    IResult,
+};
+
+use crate::asset::{read_aligned_string, Endianness};
+use crate::asset_bundle::UnityAssetBundle;
+use crate::error::UnityPackError;
+
+pub mod asset_bundle;
+pub mod game_object;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layouts;
+pub mod shader;
+pub mod text_asset;
+pub mod texture_2d;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PPtr {
+    pub file_id: i32,
+    pub path_id: i64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamingInfo {
+    pub offset: u64,
+    pub size: u32,
+    pub path: String,
+}
+
+impl StreamingInfo {
+    // paths look like archive:/CAB-xxxx/CAB-xxxx.resS
+    #[must_use]
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    #[must_use]
+    pub fn read_from<'a>(&self, bundle: &'a UnityAssetBundle) -> Option<&'a [u8]> {
+        let stream_file = bundle.resource_by_name(&self.path)?;
+        use std::convert::TryFrom;
+        let start = usize::try_from(self.offset).ok()?;
+        stream_file
+            .body
+            .get(start..start.checked_add(self.size as usize)?)
+    }
+}
+
+// the named file may live in another bundle, such as a separate resource bundle loaded next
+// to the one holding the object; the first bundle that has it is read
+#[must_use]
+pub fn resolve_streaming_info(
+    info: &StreamingInfo,
+    bundles: &[&UnityAssetBundle],
+) -> Option<Vec<u8>> {
+    let bundle = bundles
+        .iter()
+        .find(|bundle| bundle.resource_by_name(&info.path).is_some())?;
+    info.read_from(bundle).map(<[u8]>::to_vec)
+}
+
+pub(crate) fn read_streaming_info(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], StreamingInfo, UnityPackError> {
+    let (input, offset) = u32(endianness)(input)?;
+    let (input, size) = u32(endianness)(input)?;
+    let (input, path) = read_aligned_string(input, endianness)?;
+    Ok((
+        input,
+        StreamingInfo {
+            offset: offset as u64,
+            size,
+            path,
+        },
+    ))
+}
+
+pub(crate) fn read_pptr(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], PPtr, UnityPackError> {
+    let (input, file_id) = i32(endianness)(input)?;
+    let (input, path_id) = i64(endianness)(input)?;
+    Ok((input, PPtr { file_id, path_id }))
+}
+
+// array and string lengths are signed 32-bit, and a negative one is invalid
+pub(crate) fn read_length(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], usize, UnityPackError> {
+    use std::convert::TryFrom;
+    let (rest, length) = i32(endianness)(input)?;
+    let length = usize::try_from(length).map_err(|_| {
+        nom::Err::Error(UnityPackError::from_error_kind(
+            input,
+            ErrorKind::LengthValue,
+        ))
+    })?;
+    Ok((rest, length))
+}
+
+pub(crate) fn read_bool(input: &[u8]) -> IResult<&[u8], bool, UnityPackError> {
+    let (input, value) = u8(input)?;
+    Ok((input, value != 0))
+}
+
+// fields flagged kAlignBytes pad to 4 bytes relative to the start of the object
+pub(crate) fn align<'a>(object: &[u8], input: &'a [u8]) -> IResult<&'a [u8], (), UnityPackError> {
+    let padding = (4 - (object.len() - input.len()) % 4) % 4;
+    let (input, _) = take(padding)(input)?;
+    Ok((input, ()))
+}
@@ -0,0 +1,453 @@
+use nom::{
+    bytes::complete::take,
+    number::complete::{f32, i32, u32},
+    IResult,
+};
+
+use crate::asset::{read_aligned_string, Endianness};
+use crate::bounded_count;
+use crate::error::UnityPackError;
+use crate::objects::{align, read_bool};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SerializedProperty {
+    pub name: String,
+    pub description: String,
+    pub attributes: Vec<String>,
+    // SerializedPropertyType: 0 color, 1 vector, 2 float, 3 range, 4 texture, 5 int
+    pub property_type: i32,
+    pub flags: u32,
+    pub default_value: [f32; 4],
+    pub default_texture_name: String,
+    pub texture_dimension: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Shader {
+    pub name: String,
+    // the name in the shader source, such as "Universal Render Pipeline/Lit"
+    pub parsed_name: String,
+    pub properties: Vec<SerializedProperty>,
+}
+
+// field layout of Unity 2019.3 and 2019.4; the sub shaders are skipped and everything after
+// m_ParsedForm, including the compiled blobs, is left unread
+pub fn read_shader(
+    object: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], Shader, UnityPackError> {
+    let (input, name) = read_aligned_string(object, endianness)?;
+    let (input, property_count) = i32(endianness)(input)?;
+    let (input, properties) = bounded_count(
+        |i| read_serialized_property(i, endianness),
+        property_count as usize,
+    )(input)?;
+    let (input, _) = skip_array(input, endianness, |i| {
+        skip_sub_shader(object, i, endianness)
+    })?;
+    let (input, parsed_name) = read_aligned_string(input, endianness)?;
+
+    Ok((
+        input,
+        Shader {
+            name,
+            parsed_name,
+            properties,
+        },
+    ))
+}
+
+fn read_serialized_property(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], SerializedProperty, UnityPackError> {
+    let (input, name) = read_aligned_string(input, endianness)?;
+    let (input, description) = read_aligned_string(input, endianness)?;
+    let (input, attribute_count) = i32(endianness)(input)?;
+    let (input, attributes) = bounded_count(
+        |i| read_aligned_string(i, endianness),
+        attribute_count as usize,
+    )(input)?;
+    let (input, property_type) = i32(endianness)(input)?;
+    let (input, flags) = u32(endianness)(input)?;
+    let mut default_value = [0.0; 4];
+    let mut input = input;
+    for value in default_value.iter_mut() {
+        let result = f32(endianness)(input)?;
+        input = result.0;
+        *value = result.1;
+    }
+    let (input, default_texture_name) = read_aligned_string(input, endianness)?;
+    let (input, texture_dimension) = i32(endianness)(input)?;
+
+    Ok((
+        input,
+        SerializedProperty {
+            name,
+            description,
+            attributes,
+            property_type,
+            flags,
+            default_value,
+            default_texture_name,
+            texture_dimension,
+        },
+    ))
+}
+
+fn skip_array<'a, F>(
+    input: &'a [u8],
+    endianness: Endianness,
+    skip_item: F,
+) -> IResult<&'a [u8], (), UnityPackError>
+where
+    F: FnMut(&'a [u8]) -> IResult<&'a [u8], (), UnityPackError>,
+{
+    let (input, count) = i32(endianness)(input)?;
+    let (input, _) = bounded_count(skip_item, count as usize)(input)?;
+    Ok((input, ()))
+}
+
+// an array of `item_size` byte elements, padded to 4 bytes
+fn skip_aligned_array<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+    item_size: usize,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    let (input, count) = i32(endianness)(input)?;
+    let (input, _) = take((count as usize).saturating_mul(item_size))(input)?;
+    align(object, input)
+}
+
+fn skip_string(input: &[u8], endianness: Endianness) -> IResult<&[u8], (), UnityPackError> {
+    let (input, _) = read_aligned_string(input, endianness)?;
+    Ok((input, ()))
+}
+
+fn skip_tag_map(input: &[u8], endianness: Endianness) -> IResult<&[u8], (), UnityPackError> {
+    skip_array(input, endianness, |i| {
+        let (i, _) = skip_string(i, endianness)?;
+        skip_string(i, endianness)
+    })
+}
+
+fn skip_sub_shader<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    let (input, _) = skip_array(input, endianness, |i| skip_pass(object, i, endianness))?;
+    let (input, _) = skip_tag_map(input, endianness)?;
+    // m_LOD
+    let (input, _) = take(4usize)(input)?;
+    Ok((input, ()))
+}
+
+fn skip_pass<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    // m_NameIndices
+    let (input, _) = skip_array(input, endianness, |i| {
+        let (i, _) = skip_string(i, endianness)?;
+        let (i, _) = take(4usize)(i)?;
+        Ok((i, ()))
+    })?;
+    // m_Type
+    let (input, _) = take(4usize)(input)?;
+    let (input, _) = skip_shader_state(object, input, endianness)?;
+    // m_ProgramMask
+    let (mut input, _) = take(4usize)(input)?;
+    // vertex, fragment, geometry, hull, domain and, since 2019.3, ray tracing
+    for _ in 0..6 {
+        input = skip_array(input, endianness, |i| {
+            skip_sub_program(object, i, endianness)
+        })?
+        .0;
+    }
+    // m_HasInstancingVariant, m_HasProceduralInstancingVariant
+    let (input, _) = take(2usize)(input)?;
+    let (input, _) = align(object, input)?;
+    // m_UseName, m_Name, m_TextureName
+    let (input, _) = skip_string(input, endianness)?;
+    let (input, _) = skip_string(input, endianness)?;
+    let (input, _) = skip_string(input, endianness)?;
+    skip_tag_map(input, endianness)
+}
+
+fn skip_float_value(input: &[u8], endianness: Endianness) -> IResult<&[u8], (), UnityPackError> {
+    let (input, _) = take(4usize)(input)?;
+    skip_string(input, endianness)
+}
+
+fn skip_float_values(
+    input: &[u8],
+    endianness: Endianness,
+    count: usize,
+) -> IResult<&[u8], (), UnityPackError> {
+    let mut input = input;
+    for _ in 0..count {
+        input = skip_float_value(input, endianness)?.0;
+    }
+    Ok((input, ()))
+}
+
+fn skip_shader_state<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    let (input, _) = skip_string(input, endianness)?;
+    // eight render target blend states of seven values each
+    let (input, _) = skip_float_values(input, endianness, 8 * 7)?;
+    let (input, _rt_separate_blend) = read_bool(input)?;
+    let (input, _) = align(object, input)?;
+    // zClip, zTest, zWrite, culling, offsetFactor, offsetUnits, alphaToMask
+    let (input, _) = skip_float_values(input, endianness, 7)?;
+    // stencilOp, stencilOpFront, stencilOpBack
+    let (input, _) = skip_float_values(input, endianness, 3 * 4)?;
+    // stencilReadMask, stencilWriteMask, stencilRef, fogStart, fogEnd, fogDensity
+    let (input, _) = skip_float_values(input, endianness, 6)?;
+    // fogColor
+    let (input, _) = skip_float_values(input, endianness, 4)?;
+    let (input, _) = skip_string(input, endianness)?;
+    // fogMode, gpuProgramID
+    let (input, _) = take(8usize)(input)?;
+    let (input, _) = skip_tag_map(input, endianness)?;
+    // m_LOD
+    let (input, _) = take(4usize)(input)?;
+    let (input, _lighting) = read_bool(input)?;
+    align(object, input)
+}
+
+fn skip_sub_program<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    // m_BlobIndex
+    let (input, _) = take(4usize)(input)?;
+    // m_Channels: source and target bytes per channel, then m_SourceMap
+    let (input, _) = skip_aligned_array(object, input, endianness, 2)?;
+    let (input, _) = take(4usize)(input)?;
+    // m_GlobalKeywordIndices, m_LocalKeywordIndices
+    let (input, _) = skip_aligned_array(object, input, endianness, 2)?;
+    let (input, _) = skip_aligned_array(object, input, endianness, 2)?;
+    // m_ShaderHardwareTier, m_GpuProgramType
+    let (input, _) = take(2usize)(input)?;
+    let (input, _) = align(object, input)?;
+    let (input, _) = skip_array(input, endianness, skip_vector_parameter)?;
+    let (input, _) = skip_array(input, endianness, skip_vector_parameter)?;
+    // texture parameters: name index, index, sampler index, multisampled and dimension
+    let (input, _) = skip_array(input, endianness, |i| take_padded(i, 14))?;
+    // buffer bindings: name index and index
+    let (input, _) = skip_array(input, endianness, |i| take_padded(i, 8))?;
+    let (input, _) = skip_array(input, endianness, |i| {
+        skip_constant_buffer(object, i, endianness)
+    })?;
+    let (input, _) = skip_array(input, endianness, |i| take_padded(i, 8))?;
+    // UAV parameters: name index, index and original index
+    let (input, _) = skip_array(input, endianness, |i| take_padded(i, 12))?;
+    // samplers: sampler and bind point
+    let (input, _) = skip_array(input, endianness, |i| take_padded(i, 8))?;
+    // m_ShaderRequirements
+    let (input, _) = take(4usize)(input)?;
+    Ok((input, ()))
+}
+
+// a fixed-size struct whose trailing bytes are padded to 4
+fn take_padded(input: &[u8], size: usize) -> IResult<&[u8], (), UnityPackError> {
+    let (input, _) = take(size.div_ceil(4) * 4)(input)?;
+    Ok((input, ()))
+}
+
+// vector and matrix parameters share a layout: name index, index, array size, then two bytes
+fn skip_vector_parameter(input: &[u8]) -> IResult<&[u8], (), UnityPackError> {
+    take_padded(input, 14)
+}
+
+fn skip_constant_buffer<'a>(
+    object: &[u8],
+    input: &'a [u8],
+    endianness: Endianness,
+) -> IResult<&'a [u8], (), UnityPackError> {
+    // m_NameIndex
+    let (input, _) = take(4usize)(input)?;
+    // m_MatrixParams, m_VectorParams
+    let (input, _) = skip_array(input, endianness, skip_vector_parameter)?;
+    let (input, _) = skip_array(input, endianness, skip_vector_parameter)?;
+    // m_StructParams
+    let (input, _) = skip_array(input, endianness, |i| {
+        // name index, index, array size and struct size
+        let (i, _) = take(16usize)(i)?;
+        let (i, _) = skip_array(i, endianness, skip_vector_parameter)?;
+        skip_array(i, endianness, skip_vector_parameter)
+    })?;
+    // m_Size
+    let (input, _) = take(4usize)(input)?;
+    align(object, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Endianness;
+    use crate::objects::shader::{read_shader, SerializedProperty};
+    use crate::test_fixtures::{push_i32, push_string};
+
+    fn push_float_value(object: &mut Vec<u8>, name: &str) {
+        object.extend_from_slice(&1f32.to_le_bytes());
+        push_string(object, name);
+    }
+
+    fn push_pass(object: &mut Vec<u8>) {
+        // m_NameIndices
+        push_i32(object, 1);
+        push_string(object, "_MainTex");
+        push_i32(object, 0);
+        // m_Type
+        push_i32(object, 0);
+
+        // m_State
+        push_string(object, "");
+        for _ in 0..8 * 7 {
+            push_float_value(object, "");
+        }
+        object.push(0);
+        object.resize(object.len() + 3, 0);
+        for _ in 0..7 + 12 + 6 + 4 {
+            push_float_value(object, "");
+        }
+        push_string(object, "fogColor");
+        push_i32(object, 0);
+        push_i32(object, -1);
+        push_i32(object, 1);
+        push_string(object, "LightMode");
+        push_string(object, "ForwardBase");
+        push_i32(object, 0);
+        object.push(1);
+        object.resize(object.len() + 3, 0);
+
+        // m_ProgramMask
+        push_i32(object, 6);
+        // a vertex program with one sub program
+        push_i32(object, 1);
+        push_i32(object, 0);
+        push_i32(object, 1);
+        object.extend_from_slice(&[0, 0]);
+        object.resize(object.len() + 2, 0);
+        push_i32(object, 7);
+        push_i32(object, 1);
+        object.extend_from_slice(&3u16.to_le_bytes());
+        object.resize(object.len() + 2, 0);
+        push_i32(object, 0);
+        object.extend_from_slice(&[0, 9]);
+        object.resize(object.len() + 2, 0);
+        // one vector parameter
+        push_i32(object, 1);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        object.extend_from_slice(&[0, 4]);
+        object.resize(object.len() + 2, 0);
+        // matrix, texture and buffer parameters
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        // one constant buffer with a struct parameter
+        push_i32(object, 1);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 1);
+        object.extend_from_slice(&[0; 16]);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 64);
+        // constant buffer bindings, UAVs, samplers, m_ShaderRequirements
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        push_i32(object, 0);
+        // fragment, geometry, hull, domain and ray tracing programs
+        for _ in 0..5 {
+            push_i32(object, 0);
+        }
+
+        object.extend_from_slice(&[0, 0]);
+        object.resize(object.len() + 2, 0);
+        push_string(object, "");
+        push_string(object, "FORWARD");
+        push_string(object, "");
+        push_i32(object, 0);
+    }
+
+    // none of the sample bundles holds a Shader, so this one is laid out by hand: one
+    // texture property and a sub shader with a single pass
+    fn build_shader() -> Vec<u8> {
+        let mut object = vec![];
+        push_string(&mut object, "Unlit/Texture");
+        push_i32(&mut object, 1);
+        push_string(&mut object, "_MainTex");
+        push_string(&mut object, "Base (RGB)");
+        push_i32(&mut object, 1);
+        push_string(&mut object, "NoScaleOffset");
+        push_i32(&mut object, 4);
+        object.extend_from_slice(&0u32.to_le_bytes());
+        for value in [0f32, 0.0, 0.0, 1.0] {
+            object.extend_from_slice(&value.to_le_bytes());
+        }
+        push_string(&mut object, "white");
+        push_i32(&mut object, 2);
+
+        push_i32(&mut object, 1);
+        push_i32(&mut object, 1);
+        push_pass(&mut object);
+        push_i32(&mut object, 1);
+        push_string(&mut object, "RenderType");
+        push_string(&mut object, "Opaque");
+        push_i32(&mut object, 100);
+
+        push_string(&mut object, "Unlit/Texture");
+        // m_CustomEditorName and whatever follows is not read
+        push_string(&mut object, "");
+        object
+    }
+
+    #[test]
+    fn test_read_shader() {
+        let object = build_shader();
+        let (rest, shader) = read_shader(&object, Endianness::Little).unwrap();
+        assert_eq!(4, rest.len());
+        assert_eq!("Unlit/Texture", shader.name);
+        assert_eq!("Unlit/Texture", shader.parsed_name);
+        assert_eq!(
+            vec![SerializedProperty {
+                name: "_MainTex".to_string(),
+                description: "Base (RGB)".to_string(),
+                attributes: vec!["NoScaleOffset".to_string()],
+                property_type: 4,
+                flags: 0,
+                default_value: [0.0, 0.0, 0.0, 1.0],
+                default_texture_name: "white".to_string(),
+                texture_dimension: 2,
+            }],
+            shader.properties
+        );
+    }
+
+    #[test]
+    fn test_read_truncated_shader() {
+        let object = build_shader();
+        // everything up to m_CustomEditorName is read, though the padding after
+        // m_ParsedForm's name may be missing
+        for len in 0..object.len() - 4 - 3 {
+            assert!(
+                read_shader(&object[..len], Endianness::Little).is_err(),
+                "{} bytes",
+                len
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use nom::{bytes::complete::take, number::complete::i32, IResult};
+
+use crate::asset::{read_aligned_string, Endianness};
+use crate::error::UnityPackError;
+use crate::objects::align;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextAsset {
+    pub name: String,
+    // m_Script is a byte string, which also holds binary .bytes assets
+    pub script: Vec<u8>,
+}
+
+impl TextAsset {
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.script
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.script).ok()
+    }
+}
+
+pub fn read_text_asset(
+    object: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], TextAsset, UnityPackError> {
+    let (input, name) = read_aligned_string(object, endianness)?;
+    let (input, script_size) = i32(endianness)(input)?;
+    let (input, script) = take(script_size as usize)(input)?;
+    // the last field of an object may omit its padding
+    let (input, _) = align(object, input).unwrap_or((&input[input.len()..], ()));
+
+    Ok((
+        input,
+        TextAsset {
+            name,
+            script: script.to_vec(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Endianness;
+    use crate::objects::layouts::{find_layout, read_with_layout, FieldValue, TEXT_ASSET_CLASS_ID};
+    use crate::objects::text_asset::read_text_asset;
+    use crate::test_fixtures::{push_i32, push_string};
+
+    // none of the sample bundles holds a TextAsset, so this one is laid out by hand
+    fn build_text_asset(name: &str, script: &[u8]) -> Vec<u8> {
+        let mut object = vec![];
+        push_string(&mut object, name);
+        push_i32(&mut object, script.len() as i32);
+        object.extend_from_slice(script);
+        object
+    }
+
+    #[test]
+    fn test_read_text_asset() {
+        let script = br#"{"id":1}"#;
+        let object = build_text_asset("master_data", script);
+        let (rest, text_asset) = read_text_asset(&object, Endianness::Little).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!("master_data", text_asset.name);
+        assert_eq!(script, text_asset.bytes());
+        assert_eq!(Some(r#"{"id":1}"#), text_asset.as_str());
+    }
+
+    #[test]
+    fn test_read_binary_text_asset() {
+        // a binary .bytes asset padded to 4 bytes, with unrelated bytes after the object
+        let mut object = build_text_asset("blob", &[0xFF, 0x00, 0xFE]);
+        object.extend_from_slice(&[0; 5]);
+
+        let (rest, text_asset) = read_text_asset(&object, Endianness::Little).unwrap();
+        assert_eq!(&[0; 4], rest);
+        assert_eq!(&[0xFF, 0x00, 0xFE], text_asset.bytes());
+        assert_eq!(None, text_asset.as_str());
+    }
+
+    #[test]
+    fn test_read_text_asset_matches_layout() {
+        // the reader and the layout table describe the same fields independently
+        let layout = find_layout(TEXT_ASSET_CLASS_ID, "2019.4.1f1").unwrap();
+        for (name, script) in [
+            ("master_data", &b"{}"[..]),
+            ("blob", &[0xFF, 0x00, 0xFE][..]),
+        ] {
+            let object = build_text_asset(name, script);
+            let (rest, text_asset) = read_text_asset(&object, Endianness::Little).unwrap();
+            let (layout_rest, value) =
+                read_with_layout(&object, Endianness::Little, layout).unwrap();
+            assert_eq!(rest.len(), layout_rest.len());
+            assert_eq!(Some(name), value.get("m_Name").and_then(FieldValue::as_str));
+            assert_eq!(
+                Some(text_asset.bytes()),
+                value.get("m_Script").and_then(FieldValue::as_bytes)
+            );
+        }
+    }
+}
@@ -0,0 +1,938 @@
+use std::io::Write;
+
+use nom::{
+    bytes::complete::take,
+    number::complete::{f32, i32},
+    IResult,
+};
+
+use crate::asset::{read_aligned_string, Endianness};
+use crate::asset_bundle::UnityAssetBundle;
+use crate::error::UnityPackError;
+use crate::objects::{align, read_bool, read_streaming_info, StreamingInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum TextureFormat {
+    Alpha8 = 1,
+    ARGB4444 = 2,
+    RGB24 = 3,
+    RGBA32 = 4,
+    ARGB32 = 5,
+    RGB565 = 7,
+    R16 = 9,
+    DXT1 = 10,
+    DXT5 = 12,
+    RGBA4444 = 13,
+    BGRA32 = 14,
+    RHalf = 15,
+    RGHalf = 16,
+    RGBAHalf = 17,
+    RFloat = 18,
+    RGFloat = 19,
+    RGBAFloat = 20,
+    YUY2 = 21,
+    RGB9e5Float = 22,
+    BC6H = 24,
+    BC7 = 25,
+    BC4 = 26,
+    BC5 = 27,
+    DXT1Crunched = 28,
+    DXT5Crunched = 29,
+    PVRTC_RGB2 = 30,
+    PVRTC_RGBA2 = 31,
+    PVRTC_RGB4 = 32,
+    PVRTC_RGBA4 = 33,
+    ETC_RGB4 = 34,
+    ATC_RGB4 = 35,
+    ATC_RGBA8 = 36,
+    EAC_R = 41,
+    EAC_R_SIGNED = 42,
+    EAC_RG = 43,
+    EAC_RG_SIGNED = 44,
+    ETC2_RGB = 45,
+    ETC2_RGBA1 = 46,
+    ETC2_RGBA8 = 47,
+    ASTC_RGB_4x4 = 48,
+    ASTC_RGB_5x5 = 49,
+    ASTC_RGB_6x6 = 50,
+    ASTC_RGB_8x8 = 51,
+    ASTC_RGB_10x10 = 52,
+    ASTC_RGB_12x12 = 53,
+    ASTC_RGBA_4x4 = 54,
+    ASTC_RGBA_5x5 = 55,
+    ASTC_RGBA_6x6 = 56,
+    ASTC_RGBA_8x8 = 57,
+    ASTC_RGBA_10x10 = 58,
+    ASTC_RGBA_12x12 = 59,
+    ETC_RGB4_3DS = 60,
+    ETC_RGBA8_3DS = 61,
+    RG16 = 62,
+    R8 = 63,
+    ETC_RGB4Crunched = 64,
+    ETC2_RGBA8Crunched = 65,
+}
+
+impl TextureFormat {
+    #[must_use]
+    pub fn from_i32(value: i32) -> Option<TextureFormat> {
+        use TextureFormat::*;
+        let format = match value {
+            1 => Alpha8,
+            2 => ARGB4444,
+            3 => RGB24,
+            4 => RGBA32,
+            5 => ARGB32,
+            7 => RGB565,
+            9 => R16,
+            10 => DXT1,
+            12 => DXT5,
+            13 => RGBA4444,
+            14 => BGRA32,
+            15 => RHalf,
+            16 => RGHalf,
+            17 => RGBAHalf,
+            18 => RFloat,
+            19 => RGFloat,
+            20 => RGBAFloat,
+            21 => YUY2,
+            22 => RGB9e5Float,
+            24 => BC6H,
+            25 => BC7,
+            26 => BC4,
+            27 => BC5,
+            28 => DXT1Crunched,
+            29 => DXT5Crunched,
+            30 => PVRTC_RGB2,
+            31 => PVRTC_RGBA2,
+            32 => PVRTC_RGB4,
+            33 => PVRTC_RGBA4,
+            34 => ETC_RGB4,
+            35 => ATC_RGB4,
+            36 => ATC_RGBA8,
+            41 => EAC_R,
+            42 => EAC_R_SIGNED,
+            43 => EAC_RG,
+            44 => EAC_RG_SIGNED,
+            45 => ETC2_RGB,
+            46 => ETC2_RGBA1,
+            47 => ETC2_RGBA8,
+            48 => ASTC_RGB_4x4,
+            49 => ASTC_RGB_5x5,
+            50 => ASTC_RGB_6x6,
+            51 => ASTC_RGB_8x8,
+            52 => ASTC_RGB_10x10,
+            53 => ASTC_RGB_12x12,
+            54 => ASTC_RGBA_4x4,
+            55 => ASTC_RGBA_5x5,
+            56 => ASTC_RGBA_6x6,
+            57 => ASTC_RGBA_8x8,
+            58 => ASTC_RGBA_10x10,
+            59 => ASTC_RGBA_12x12,
+            60 => ETC_RGB4_3DS,
+            61 => ETC_RGBA8_3DS,
+            62 => RG16,
+            63 => R8,
+            64 => ETC_RGB4Crunched,
+            65 => ETC2_RGBA8Crunched,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    // Crunch wraps DXT or ETC data, which has to be transcoded before it can be decoded
+    #[must_use]
+    pub fn is_crunched(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::DXT1Crunched
+                | TextureFormat::DXT5Crunched
+                | TextureFormat::ETC_RGB4Crunched
+                | TextureFormat::ETC2_RGBA8Crunched
+        )
+    }
+
+    // block width and height in pixels; every ASTC block is 16 bytes whatever its size
+    #[must_use]
+    pub fn astc_block_size(self) -> Option<(usize, usize)> {
+        use TextureFormat::*;
+        let size = match self {
+            ASTC_RGB_4x4 | ASTC_RGBA_4x4 => 4,
+            ASTC_RGB_5x5 | ASTC_RGBA_5x5 => 5,
+            ASTC_RGB_6x6 | ASTC_RGBA_6x6 => 6,
+            ASTC_RGB_8x8 | ASTC_RGBA_8x8 => 8,
+            ASTC_RGB_10x10 | ASTC_RGBA_10x10 => 10,
+            ASTC_RGB_12x12 | ASTC_RGBA_12x12 => 12,
+            _ => return None,
+        };
+        Some((size, size))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GLTextureSettings {
+    pub filter_mode: i32,
+    pub aniso: i32,
+    pub mip_bias: f32,
+    pub wrap_u: i32,
+    pub wrap_v: i32,
+    pub wrap_w: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Texture2D {
+    pub name: String,
+    pub forced_fallback_format: i32,
+    pub downscale_fallback: bool,
+    pub width: i32,
+    pub height: i32,
+    pub complete_image_size: i32,
+    pub texture_format: i32,
+    pub mip_count: i32,
+    pub is_readable: bool,
+    pub ignore_master_texture_limit: bool,
+    pub streaming_mipmaps: bool,
+    pub streaming_mipmaps_priority: i32,
+    pub image_count: i32,
+    pub texture_dimension: i32,
+    pub texture_settings: GLTextureSettings,
+    pub lightmap_format: i32,
+    pub color_space: i32,
+    pub image_data: Vec<u8>,
+    pub stream_data: StreamingInfo,
+}
+
+// field layout of Unity 2019.3 and 2019.4; earlier versions lack m_IgnoreMasterTextureLimit,
+// and layouts::find_layout covers them from 2018.2
+pub fn read_texture_2d(
+    object: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], Texture2D, UnityPackError> {
+    let (input, name) = read_aligned_string(object, endianness)?;
+    let (input, forced_fallback_format) = i32(endianness)(input)?;
+    let (input, downscale_fallback) = read_bool(input)?;
+    let (input, _) = align(object, input)?;
+    let (input, width) = i32(endianness)(input)?;
+    let (input, height) = i32(endianness)(input)?;
+    let (input, complete_image_size) = i32(endianness)(input)?;
+    let (input, texture_format) = i32(endianness)(input)?;
+    let (input, mip_count) = i32(endianness)(input)?;
+    let (input, is_readable) = read_bool(input)?;
+    let (input, ignore_master_texture_limit) = read_bool(input)?;
+    let (input, streaming_mipmaps) = read_bool(input)?;
+    let (input, _) = align(object, input)?;
+    let (input, streaming_mipmaps_priority) = i32(endianness)(input)?;
+    let (input, image_count) = i32(endianness)(input)?;
+    let (input, texture_dimension) = i32(endianness)(input)?;
+    let (input, texture_settings) = read_gl_texture_settings(input, endianness)?;
+    let (input, lightmap_format) = i32(endianness)(input)?;
+    let (input, color_space) = i32(endianness)(input)?;
+    let (input, image_data_size) = i32(endianness)(input)?;
+    let (input, image_data) = take(image_data_size as usize)(input)?;
+    let (input, _) = align(object, input)?;
+    let (input, stream_data) = read_streaming_info(input, endianness)?;
+
+    Ok((
+        input,
+        Texture2D {
+            name,
+            forced_fallback_format,
+            downscale_fallback,
+            width,
+            height,
+            complete_image_size,
+            texture_format,
+            mip_count,
+            is_readable,
+            ignore_master_texture_limit,
+            streaming_mipmaps,
+            streaming_mipmaps_priority,
+            image_count,
+            texture_dimension,
+            texture_settings,
+            lightmap_format,
+            color_space,
+            image_data: image_data.to_vec(),
+            stream_data,
+        },
+    ))
+}
+
+fn read_gl_texture_settings(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], GLTextureSettings, UnityPackError> {
+    let (input, filter_mode) = i32(endianness)(input)?;
+    let (input, aniso) = i32(endianness)(input)?;
+    let (input, mip_bias) = f32(endianness)(input)?;
+    let (input, wrap_u) = i32(endianness)(input)?;
+    let (input, wrap_v) = i32(endianness)(input)?;
+    let (input, wrap_w) = i32(endianness)(input)?;
+    Ok((
+        input,
+        GLTextureSettings {
+            filter_mode,
+            aniso,
+            mip_bias,
+            wrap_u,
+            wrap_v,
+            wrap_w,
+        },
+    ))
+}
+
+impl Texture2D {
+    #[must_use]
+    pub fn format(&self) -> Option<TextureFormat> {
+        TextureFormat::from_i32(self.texture_format)
+    }
+
+    #[must_use]
+    pub fn image_bytes<'a>(&'a self, bundle: &'a UnityAssetBundle) -> Option<&'a [u8]> {
+        if !self.image_data.is_empty() {
+            return Some(&self.image_data);
+        }
+        self.stream_data.read_from(bundle)
+    }
+
+    // decodes the first mip level to RGBA8 with rows ordered top to bottom
+    pub fn decode_rgba(&self, data: &[u8]) -> Result<Vec<u8>, UnityPackError> {
+        let format = self
+            .format()
+            .ok_or(UnityPackError::UnsupportedTextureFormat(
+                self.texture_format,
+            ))?;
+        if self.width <= 0 || self.height <= 0 {
+            return Err(UnityPackError::TextureDecode(format!(
+                "invalid texture size {}x{}",
+                self.width, self.height
+            )));
+        }
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let mut rgba = match decode_uncompressed(format, data, pixel_count(width, height)?)? {
+            Some(rgba) => rgba,
+            None => decode_compressed(format, data, width, height)?,
+        };
+        flip_rows(&mut rgba, width * 4);
+        Ok(rgba)
+    }
+
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, data: &[u8], writer: W) -> Result<(), UnityPackError> {
+        let rgba = self.decode_rgba(data)?;
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&rgba).map_err(png_error)?;
+        writer.finish().map_err(png_error)
+    }
+
+    // writes `data` untouched behind a DDS header, so rows stay bottom to top like in Unity
+    pub fn write_dds<W: Write>(&self, data: &[u8], mut writer: W) -> Result<(), UnityPackError> {
+        let format = self
+            .format()
+            .ok_or(UnityPackError::UnsupportedTextureFormat(
+                self.texture_format,
+            ))?;
+        let pixel_format = DdsPixelFormat::from_texture_format(format)
+            .ok_or(UnityPackError::UnsupportedTextureFormat(format as i32))?;
+        let width = self.width as u32;
+        let height = self.height as u32;
+        let mip_count = self.mip_count.max(1) as u32;
+
+        let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+        let pitch_or_linear_size = match pixel_format {
+            DdsPixelFormat::Compressed { block_size, .. } => {
+                flags |= DDSD_LINEARSIZE;
+                width.div_ceil(4).max(1) * height.div_ceil(4).max(1) * block_size
+            }
+            DdsPixelFormat::Uncompressed { bit_count, .. } => {
+                flags |= DDSD_PITCH;
+                (width * bit_count).div_ceil(8)
+            }
+        };
+        let mut caps = DDSCAPS_TEXTURE;
+        if mip_count > 1 {
+            flags |= DDSD_MIPMAPCOUNT;
+            caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+        }
+
+        let mut header = b"DDS ".to_vec();
+        for value in [
+            124,
+            flags,
+            height,
+            width,
+            pitch_or_linear_size,
+            0,
+            mip_count,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&[0; 44]);
+        header.extend_from_slice(&32u32.to_le_bytes());
+        let (pixel_flags, four_cc, bit_count, masks) = match pixel_format {
+            DdsPixelFormat::Compressed { four_cc, .. } => (DDPF_FOURCC, four_cc, 0, [0; 4]),
+            DdsPixelFormat::Uncompressed {
+                flags,
+                bit_count,
+                masks,
+            } => (flags, [0; 4], bit_count, masks),
+        };
+        header.extend_from_slice(&pixel_flags.to_le_bytes());
+        header.extend_from_slice(&four_cc);
+        header.extend_from_slice(&bit_count.to_le_bytes());
+        for mask in masks {
+            header.extend_from_slice(&mask.to_le_bytes());
+        }
+        for value in [caps, 0, 0, 0, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        if let Some(dxgi_format) = dxgi_format(format) {
+            // DDS_HEADER_DXT10 for a single 2D texture
+            for value in [dxgi_format, 3, 0, 1, 0] {
+                header.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        writer.write_all(&header)?;
+        writer.write_all(data)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "png")]
+fn png_error(e: png::EncodingError) -> UnityPackError {
+    match e {
+        png::EncodingError::IoError(e) => UnityPackError::Io(e),
+        e => UnityPackError::TextureEncode(e.to_string()),
+    }
+}
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+#[derive(Debug, Clone, Copy)]
+enum DdsPixelFormat {
+    Compressed {
+        four_cc: [u8; 4],
+        block_size: u32,
+    },
+    Uncompressed {
+        flags: u32,
+        bit_count: u32,
+        // r, g, b, a
+        masks: [u32; 4],
+    },
+}
+
+impl DdsPixelFormat {
+    fn from_texture_format(format: TextureFormat) -> Option<DdsPixelFormat> {
+        let compressed = |four_cc: &[u8; 4], block_size| DdsPixelFormat::Compressed {
+            four_cc: *four_cc,
+            block_size,
+        };
+        let uncompressed = |flags, bit_count, masks| DdsPixelFormat::Uncompressed {
+            flags,
+            bit_count,
+            masks,
+        };
+        let rgba = DDPF_RGB | DDPF_ALPHAPIXELS;
+        Some(match format {
+            TextureFormat::DXT1 => compressed(b"DXT1", 8),
+            TextureFormat::DXT5 => compressed(b"DXT5", 16),
+            TextureFormat::BC4 => compressed(b"ATI1", 8),
+            TextureFormat::BC5 => compressed(b"ATI2", 16),
+            TextureFormat::BC6H | TextureFormat::BC7 => compressed(b"DX10", 16),
+            TextureFormat::Alpha8 => uncompressed(DDPF_ALPHA, 8, [0, 0, 0, 0xFF]),
+            TextureFormat::RGB24 => uncompressed(DDPF_RGB, 24, [0xFF, 0xFF00, 0xFF0000, 0]),
+            TextureFormat::RGBA32 => uncompressed(rgba, 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000]),
+            TextureFormat::ARGB32 => uncompressed(rgba, 32, [0xFF00, 0xFF0000, 0xFF000000, 0xFF]),
+            TextureFormat::BGRA32 => uncompressed(rgba, 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000]),
+            _ => return None,
+        })
+    }
+}
+
+fn dxgi_format(format: TextureFormat) -> Option<u32> {
+    match format {
+        // DXGI_FORMAT_BC6H_UF16
+        TextureFormat::BC6H => Some(95),
+        // DXGI_FORMAT_BC7_UNORM
+        TextureFormat::BC7 => Some(98),
+        _ => None,
+    }
+}
+
+type PixelConverter = fn(&[u8]) -> [u8; 4];
+
+fn pixel_count(width: usize, height: usize) -> Result<usize, UnityPackError> {
+    width.checked_mul(height).ok_or_else(|| {
+        UnityPackError::TextureDecode(format!("texture size {}x{} overflows", width, height))
+    })
+}
+
+// `None` for formats that need a block decoder
+fn decode_uncompressed(
+    format: TextureFormat,
+    data: &[u8],
+    pixels: usize,
+) -> Result<Option<Vec<u8>>, UnityPackError> {
+    let (bytes_per_pixel, convert): (usize, PixelConverter) = match format {
+        TextureFormat::Alpha8 => (1, |p| [255, 255, 255, p[0]]),
+        TextureFormat::R8 => (1, |p| [p[0], 0, 0, 255]),
+        TextureFormat::RG16 => (2, |p| [p[0], p[1], 0, 255]),
+        TextureFormat::R16 => (2, |p| [p[1], 0, 0, 255]),
+        TextureFormat::RGB24 => (3, |p| [p[0], p[1], p[2], 255]),
+        TextureFormat::RGBA32 => (4, |p| [p[0], p[1], p[2], p[3]]),
+        TextureFormat::ARGB32 => (4, |p| [p[1], p[2], p[3], p[0]]),
+        TextureFormat::BGRA32 => (4, |p| [p[2], p[1], p[0], p[3]]),
+        TextureFormat::RGB565 => (2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [
+                expand((v >> 11) as u8, 5),
+                expand((v >> 5) as u8 & 0x3F, 6),
+                expand(v as u8 & 0x1F, 5),
+                255,
+            ]
+        }),
+        TextureFormat::RGBA4444 => (2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [
+                expand((v >> 12) as u8, 4),
+                expand((v >> 8) as u8 & 0xF, 4),
+                expand((v >> 4) as u8 & 0xF, 4),
+                expand(v as u8 & 0xF, 4),
+            ]
+        }),
+        TextureFormat::ARGB4444 => (2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [
+                expand((v >> 8) as u8 & 0xF, 4),
+                expand((v >> 4) as u8 & 0xF, 4),
+                expand(v as u8 & 0xF, 4),
+                expand((v >> 12) as u8, 4),
+            ]
+        }),
+        _ => return Ok(None),
+    };
+    let needed = pixels
+        .checked_mul(bytes_per_pixel)
+        .filter(|needed| *needed <= data.len())
+        .ok_or_else(|| {
+            UnityPackError::TextureDecode(format!(
+                "{} bytes of image data is too short for {} pixels of {:?}",
+                data.len(),
+                pixels,
+                format
+            ))
+        })?;
+    Ok(Some(
+        data[..needed]
+            .chunks_exact(bytes_per_pixel)
+            .flat_map(convert)
+            .collect(),
+    ))
+}
+
+fn expand(value: u8, bits: u32) -> u8 {
+    ((value as u32 * 255 + ((1 << bits) - 1) / 2) / ((1 << bits) - 1)) as u8
+}
+
+#[cfg(feature = "texture2ddecoder")]
+fn decode_compressed(
+    format: TextureFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, UnityPackError> {
+    use texture2ddecoder as t2d;
+
+    let mut image = vec![0u32; pixel_count(width, height)?];
+    let result = match format {
+        TextureFormat::DXT1 => t2d::decode_bc1(data, width, height, &mut image),
+        TextureFormat::DXT5 => t2d::decode_bc3(data, width, height, &mut image),
+        TextureFormat::BC4 => t2d::decode_bc4(data, width, height, &mut image),
+        TextureFormat::BC5 => t2d::decode_bc5(data, width, height, &mut image),
+        TextureFormat::BC6H => t2d::decode_bc6_unsigned(data, width, height, &mut image),
+        TextureFormat::BC7 => t2d::decode_bc7(data, width, height, &mut image),
+        TextureFormat::ETC_RGB4 | TextureFormat::ETC_RGB4_3DS => {
+            t2d::decode_etc1(data, width, height, &mut image)
+        }
+        TextureFormat::ETC2_RGB => t2d::decode_etc2_rgb(data, width, height, &mut image),
+        TextureFormat::ETC2_RGBA1 => t2d::decode_etc2_rgba1(data, width, height, &mut image),
+        TextureFormat::ETC2_RGBA8 | TextureFormat::ETC_RGBA8_3DS => {
+            t2d::decode_etc2_rgba8(data, width, height, &mut image)
+        }
+        TextureFormat::EAC_R => t2d::decode_eacr(data, width, height, &mut image),
+        TextureFormat::EAC_R_SIGNED => t2d::decode_eacr_signed(data, width, height, &mut image),
+        TextureFormat::EAC_RG => t2d::decode_eacrg(data, width, height, &mut image),
+        TextureFormat::ATC_RGB4 => t2d::decode_atc_rgb4(data, width, height, &mut image),
+        TextureFormat::ATC_RGBA8 => t2d::decode_atc_rgba8(data, width, height, &mut image),
+        TextureFormat::PVRTC_RGB2 | TextureFormat::PVRTC_RGBA2 => {
+            t2d::decode_pvrtc_2bpp(data, width, height, &mut image)
+        }
+        TextureFormat::PVRTC_RGB4 | TextureFormat::PVRTC_RGBA4 => {
+            t2d::decode_pvrtc_4bpp(data, width, height, &mut image)
+        }
+        // Unity's fork of Crunch, used since 2017.3 (AssetStudio switches decoders at that
+        // version); textures crunched by older versions would need t2d::decode_crunch
+        _ if format.is_crunched() => t2d::decode_unity_crunch(data, width, height, &mut image),
+        _ => match format.astc_block_size() {
+            Some((block_width, block_height)) => {
+                t2d::decode_astc(data, width, height, block_width, block_height, &mut image)
+            }
+            None => return Err(UnityPackError::UnsupportedTextureFormat(format as i32)),
+        },
+    };
+    result.map_err(|e| UnityPackError::TextureDecode(e.to_string()))?;
+    // texture2ddecoder packs pixels as BGRA
+    Ok(image
+        .iter()
+        .flat_map(|pixel| {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            [r, g, b, a]
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "texture2ddecoder"))]
+fn decode_compressed(
+    format: TextureFormat,
+    _data: &[u8],
+    _width: usize,
+    _height: usize,
+) -> Result<Vec<u8>, UnityPackError> {
+    Err(UnityPackError::UnsupportedTextureFormat(format as i32))
+}
+
+// Unity stores rows bottom to top
+fn flip_rows(rgba: &mut [u8], stride: usize) {
+    if stride == 0 {
+        return;
+    }
+    let rows = rgba.len() / stride;
+    for y in 0..rows / 2 {
+        let (top, bottom) = rgba.split_at_mut((rows - 1 - y) * stride);
+        top[y * stride..(y + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+// the tests reading the LZ4HC sample bundles need the lz4 feature
+#[cfg_attr(not(feature = "lz4"), allow(unused_imports, dead_code))]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::asset::Endianness;
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::objects::resolve_streaming_info;
+    use crate::objects::texture_2d::{read_texture_2d, Texture2D, TextureFormat};
+    use crate::test_fixtures::read_file;
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_read_texture_2d() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        // the Texture2D object occupies 4312..4512 of the serialized file
+        let object = &unity_asset_bundle.stream_files[0].body[4312..4512];
+        let (rest, texture) = read_texture_2d(object, Endianness::Little).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!("item_icon_00000", texture.name);
+        assert_eq!(256, texture.width);
+        assert_eq!(256, texture.height);
+        assert_eq!(Some(TextureFormat::DXT5), texture.format());
+        assert_eq!(0, texture.stream_data.offset);
+        assert_eq!(65536, texture.stream_data.size);
+        assert_eq!(
+            "CAB-5813386f0ea15049abeb5a688d9031d3.resS",
+            texture.stream_data.file_name()
+        );
+        assert_eq!(
+            Some(&unity_asset_bundle.stream_files[1].body[..]),
+            texture.image_bytes(&unity_asset_bundle)
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_resolve_streaming_info_in_sibling_bundle() {
+        let file = read_file("./item_icon_00000");
+        let resource = read_unity_asset_bundle(&file).unwrap().1.stream_files[1]
+            .body
+            .clone();
+        // split the sample into a bundle with only the serialized file and a resource
+        // bundle with only the .resS
+        let mut texture_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        texture_bundle.directory_info.truncate(1);
+        texture_bundle.stream_files.truncate(1);
+        let mut resource_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        resource_bundle.directory_info.remove(0);
+        resource_bundle.stream_files.remove(0);
+
+        let object = &texture_bundle.stream_files[0].body[4312..4512];
+        let (_, texture) = read_texture_2d(object, Endianness::Little).unwrap();
+        assert_eq!(None, texture.image_bytes(&texture_bundle));
+        assert_eq!(
+            Some(resource.clone()),
+            resolve_streaming_info(&texture.stream_data, &[&texture_bundle, &resource_bundle])
+        );
+        assert_eq!(
+            None,
+            resolve_streaming_info(&texture.stream_data, &[&texture_bundle])
+        );
+
+        // a range past the end of the named file is not looked for elsewhere
+        let mut stream_data = texture.stream_data.clone();
+        stream_data.offset = 1;
+        assert_eq!(
+            None,
+            resolve_streaming_info(&stream_data, &[&resource_bundle, &resource_bundle])
+        );
+        stream_data.offset = 16;
+        stream_data.size = 16;
+        assert_eq!(
+            Some(resource[16..32].to_vec()),
+            resolve_streaming_info(&stream_data, &[&texture_bundle, &resource_bundle])
+        );
+    }
+
+    #[test]
+    fn test_decode_rgba32_flips_rows() {
+        let texture = Texture2D {
+            width: 1,
+            height: 2,
+            texture_format: TextureFormat::RGBA32 as i32,
+            ..Default::default()
+        };
+        let rgba = texture.decode_rgba(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(vec![5, 6, 7, 8, 1, 2, 3, 4], rgba);
+    }
+
+    #[test]
+    fn test_decode_rgba_rejects_bad_sizes() {
+        for (width, height, data) in [
+            (0, 2, &[0; 8][..]),
+            (1, -2, &[0; 8][..]),
+            (1, 2, &[0; 7][..]),
+            (i32::MAX, i32::MAX, &[0; 8][..]),
+        ] {
+            let texture = Texture2D {
+                width,
+                height,
+                texture_format: TextureFormat::RGBA32 as i32,
+                ..Default::default()
+            };
+            assert!(matches!(
+                texture.decode_rgba(data),
+                Err(crate::error::UnityPackError::TextureDecode(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_write_dds() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let object = &unity_asset_bundle.stream_files[0].body[4312..4512];
+        let texture = read_texture_2d(object, Endianness::Little).unwrap().1;
+        let data = texture.image_bytes(&unity_asset_bundle).unwrap();
+        let mut dds = vec![];
+        texture.write_dds(data, &mut dds).unwrap();
+
+        let field =
+            |index: usize| u32::from_le_bytes(dds[index * 4..index * 4 + 4].try_into().unwrap());
+        assert_eq!(b"DDS ", &dds[..4]);
+        assert_eq!(124, field(1));
+        assert_eq!((256, 256), (field(3), field(4)));
+        // a single mip of 64x64 DXT5 blocks
+        assert_eq!(65536, field(5));
+        assert_eq!(b"DXT5", &dds[84..88]);
+        assert_eq!(data, &dds[128..]);
+
+        let texture = Texture2D {
+            texture_format: TextureFormat::EAC_R as i32,
+            ..texture.clone()
+        };
+        assert!(matches!(
+            texture.write_dds(data, vec![]),
+            Err(crate::error::UnityPackError::UnsupportedTextureFormat(41))
+        ));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_write_png() {
+        let texture = Texture2D {
+            width: 1,
+            height: 2,
+            texture_format: TextureFormat::RGBA32 as i32,
+            ..Default::default()
+        };
+        let mut encoded = vec![];
+        texture
+            .write_png(&[1, 2, 3, 4, 5, 6, 7, 8], &mut encoded)
+            .unwrap();
+
+        let mut reader = png::Decoder::new(&encoded[..]).read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        assert_eq!((1, 2), (info.width, info.height));
+        assert_eq!(png::ColorType::Rgba, info.color_type);
+        assert_eq!(vec![5, 6, 7, 8, 1, 2, 3, 4], rgba);
+    }
+
+    #[cfg(all(feature = "png", feature = "texture2ddecoder", feature = "lz4"))]
+    #[test]
+    fn test_write_png_sample() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let object = &unity_asset_bundle.stream_files[0].body[4312..4512];
+        let texture = read_texture_2d(object, Endianness::Little).unwrap().1;
+        let data = texture.image_bytes(&unity_asset_bundle).unwrap();
+        let mut encoded = vec![];
+        texture.write_png(data, &mut encoded).unwrap();
+
+        let reader = png::Decoder::new(&encoded[..]).read_info().unwrap();
+        assert_eq!((256, 256), reader.info().size());
+    }
+
+    #[cfg(not(feature = "texture2ddecoder"))]
+    #[test]
+    fn test_decode_compressed_without_decoder() {
+        let texture = Texture2D {
+            width: 4,
+            height: 4,
+            texture_format: TextureFormat::DXT5 as i32,
+            ..Default::default()
+        };
+        assert!(matches!(
+            texture.decode_rgba(&[0; 16]),
+            Err(crate::error::UnityPackError::UnsupportedTextureFormat(12))
+        ));
+    }
+
+    #[cfg(feature = "texture2ddecoder")]
+    #[test]
+    fn test_decode_bc7() {
+        // a mode 6 block whose endpoints are both (0x40, 0x20, 0x10, 0x7F) with p-bits set,
+        // so every pixel decodes to (0x81, 0x41, 0x21, 0xFF)
+        let mut bits = 1u128 << 6;
+        let mut position = 7;
+        for endpoint in [0x40u128, 0x40, 0x20, 0x20, 0x10, 0x10, 0x7F, 0x7F] {
+            bits |= endpoint << position;
+            position += 7;
+        }
+        bits |= 0b11 << position;
+        let texture = Texture2D {
+            width: 4,
+            height: 4,
+            texture_format: TextureFormat::BC7 as i32,
+            ..Default::default()
+        };
+        let rgba = texture.decode_rgba(&bits.to_le_bytes()).unwrap();
+        assert_eq!([0x81, 0x41, 0x21, 0xFF].repeat(16), rgba);
+    }
+
+    #[test]
+    fn test_is_crunched() {
+        assert!(TextureFormat::DXT1Crunched.is_crunched());
+        assert!(TextureFormat::ETC2_RGBA8Crunched.is_crunched());
+        assert!(!TextureFormat::DXT1.is_crunched());
+    }
+
+    #[cfg(feature = "texture2ddecoder")]
+    #[test]
+    fn test_decode_invalid_crunch() {
+        // without a Crunch encoder at hand there is no valid sample, but data that is not a
+        // Crunch file must be reported instead of decoding as garbage
+        let texture = Texture2D {
+            width: 4,
+            height: 4,
+            texture_format: TextureFormat::DXT5Crunched as i32,
+            ..Default::default()
+        };
+        assert!(matches!(
+            texture.decode_rgba(&[0; 64]),
+            Err(crate::error::UnityPackError::TextureDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_astc_block_size() {
+        assert_eq!(Some((4, 4)), TextureFormat::ASTC_RGB_4x4.astc_block_size());
+        assert_eq!(Some((6, 6)), TextureFormat::ASTC_RGBA_6x6.astc_block_size());
+        assert_eq!(
+            Some((12, 12)),
+            TextureFormat::ASTC_RGBA_12x12.astc_block_size()
+        );
+        assert_eq!(None, TextureFormat::ETC2_RGBA8.astc_block_size());
+    }
+
+    #[cfg(feature = "texture2ddecoder")]
+    #[test]
+    fn test_decode_astc() {
+        // a void-extent block paints its whole footprint with one 16-bit UNORM color,
+        // here (0x12, 0x34, 0x56, 0xFF) once reduced to 8 bits
+        let mut block = vec![0xFC, 0xFD, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        for channel in [0x1212u16, 0x3434, 0x5656, 0xFFFF] {
+            block.extend_from_slice(&channel.to_le_bytes());
+        }
+        for (format, size, blocks) in [
+            (TextureFormat::ASTC_RGB_4x4, 4, 1),
+            (TextureFormat::ASTC_RGBA_6x6, 6, 1),
+            // partial blocks at the right and bottom edges
+            (TextureFormat::ASTC_RGBA_6x6, 8, 4),
+        ] {
+            let texture = Texture2D {
+                width: size,
+                height: size,
+                texture_format: format as i32,
+                ..Default::default()
+            };
+            let rgba = texture.decode_rgba(&block.repeat(blocks)).unwrap();
+            assert_eq!(
+                [0x12, 0x34, 0x56, 0xFF].repeat((size * size) as usize),
+                rgba
+            );
+        }
+        let texture = Texture2D {
+            width: 8,
+            height: 8,
+            texture_format: TextureFormat::ASTC_RGBA_6x6 as i32,
+            ..Default::default()
+        };
+        assert!(matches!(
+            texture.decode_rgba(&block),
+            Err(crate::error::UnityPackError::TextureDecode(_))
+        ));
+    }
+
+    #[cfg(all(feature = "texture2ddecoder", feature = "lz4"))]
+    #[test]
+    fn test_decode_dxt5_sample() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let object = &unity_asset_bundle.stream_files[0].body[4312..4512];
+        let texture = read_texture_2d(object, Endianness::Little).unwrap().1;
+        let data = texture.image_bytes(&unity_asset_bundle).unwrap();
+        let rgba = texture.decode_rgba(data).unwrap();
+        assert_eq!(256 * 256 * 4, rgba.len());
+    }
+}
@@ -0,0 +1,31 @@
+//! The types most programs reading bundles need.
+//!
+//! ```
+//! use unitypack::prelude::*;
+//!
+//! # if cfg!(not(feature = "lz4")) { return; }
+//! let file = std::fs::read("item_icon_00000").unwrap();
+//! let bundle: UnityAssetBundle = read_unity_asset_bundle(&file).unwrap().1;
+//! let serialized_file: SerializedFile = read_serialized_file(&bundle[0].body).unwrap();
+//! let textures = serialized_file
+//!     .object_iter()
+//!     .filter(|(object, _)| object.class_id == 28)
+//!     .count();
+//! assert_eq!(1, textures);
+//! assert!(matches!(
+//!     bundle.fs().read("CAB-missing"),
+//!     Err(UnityPackError::FileNotFound(_))
+//! ));
+//! assert_eq!(Ok(CompressionType::Lz4Hc), "lz4hc".parse().map_err(drop));
+//! ```
+
+pub use crate::asset::{read_serialized_file, Endianness, ObjectInfo, SerializedFile};
+pub use crate::asset_bundle::{
+    read_unity_asset_bundle, read_unity_asset_bundle_with_options, ParseOptions, UnityAssetBundle,
+    UnityNode, UnityStreamFile,
+};
+pub use crate::block_cache::BlockCache;
+pub use crate::bundle_fs::BundleFs;
+pub use crate::compression::{CompressionType, Decompressor, Decryptor};
+pub use crate::error::{ParseStage, UnityPackError};
+pub use crate::objects::PPtr;
@@ -0,0 +1,33 @@
+// helpers the test modules share for reading the sample bundles and laying out objects
+
+use std::{io::Read, path::Path};
+
+pub(crate) fn read_file<P: AsRef<Path>>(file_path: P) -> Vec<u8> {
+    let mut file = std::fs::File::open(file_path).expect("file open failed");
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("file read failed");
+    buf
+}
+
+// the object writers below produce little-endian fields, as in the sample bundles
+pub(crate) fn push_i32(object: &mut Vec<u8>, value: i32) {
+    object.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn push_u32(object: &mut Vec<u8>, value: u32) {
+    object.extend_from_slice(&value.to_le_bytes());
+}
+
+// a length-prefixed string padded to 4 bytes, as kAlignBytes strings are serialized
+pub(crate) fn push_string(object: &mut Vec<u8>, string: &str) {
+    push_u32(object, string.len() as u32);
+    object.extend_from_slice(string.as_bytes());
+    while !object.len().is_multiple_of(4) {
+        object.push(0);
+    }
+}
+
+pub(crate) fn push_pptr(object: &mut Vec<u8>, file_id: i32, path_id: i64) {
+    push_i32(object, file_id);
+    object.extend_from_slice(&path_id.to_le_bytes());
+}
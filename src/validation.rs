@@ -0,0 +1,159 @@
+use crate::asset_bundle::UnityAssetBundle;
+use crate::compression::CompressionType;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    NodeSizesExceedData {
+        total_node_size: i64,
+        total_uncompressed_size: u64,
+    },
+    // the node sizes add up to more than an i64 holds
+    NodeSizesOverflow,
+    NodeOutOfBounds {
+        index: usize,
+        offset: i64,
+        size: i64,
+    },
+    EmptyStorageBlock {
+        index: usize,
+    },
+    StoredBlockSizeMismatch {
+        index: usize,
+        compressed_size: u32,
+        uncompressed_size: u32,
+    },
+    UnknownContainerCompression {
+        flags: u32,
+    },
+    UnknownBlockCompression {
+        index: usize,
+        flags: u16,
+    },
+}
+
+impl UnityAssetBundle {
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = vec![];
+        let total_uncompressed_size = self.total_uncompressed_size();
+
+        if CompressionType::from_flags(self.container_header.flags).is_err() {
+            issues.push(ValidationIssue::UnknownContainerCompression {
+                flags: self.container_header.flags,
+            });
+        }
+
+        for (index, sb) in self.storage_blocks.iter().enumerate() {
+            if sb.uncompressed_size == 0 {
+                issues.push(ValidationIssue::EmptyStorageBlock { index });
+            }
+            match CompressionType::from_flags(sb.flags as u32) {
+                Ok(CompressionType::None) if sb.compressed_size != sb.uncompressed_size => {
+                    issues.push(ValidationIssue::StoredBlockSizeMismatch {
+                        index,
+                        compressed_size: sb.compressed_size,
+                        uncompressed_size: sb.uncompressed_size,
+                    });
+                }
+                Ok(_) => {}
+                Err(_) => issues.push(ValidationIssue::UnknownBlockCompression {
+                    index,
+                    flags: sb.flags,
+                }),
+            }
+        }
+
+        let total_node_size = self
+            .directory_info
+            .iter()
+            .try_fold(0i64, |total, di| total.checked_add(di.size));
+        match total_node_size {
+            Some(total_node_size)
+                if total_node_size < 0 || total_node_size as u64 > total_uncompressed_size =>
+            {
+                issues.push(ValidationIssue::NodeSizesExceedData {
+                    total_node_size,
+                    total_uncompressed_size,
+                });
+            }
+            Some(_) => {}
+            None => issues.push(ValidationIssue::NodeSizesOverflow),
+        }
+
+        for (index, di) in self.directory_info.iter().enumerate() {
+            let in_bounds = di.offset >= 0
+                && di.size >= 0
+                && di
+                    .offset
+                    .checked_add(di.size)
+                    .is_some_and(|end| end as u64 <= total_uncompressed_size);
+            if !in_bounds {
+                issues.push(ValidationIssue::NodeOutOfBounds {
+                    index,
+                    offset: di.offset,
+                    size: di.size,
+                });
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::test_fixtures::read_file;
+    use crate::validation::ValidationIssue;
+
+    #[test]
+    fn test_validate_sample_bundle() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert_eq!(Ok(()), unity_asset_bundle.validate());
+    }
+
+    #[test]
+    fn test_validate_corrupted_bundle() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        unity_asset_bundle.container_header.flags = 0x7F;
+        unity_asset_bundle.storage_blocks[0].flags = 9;
+        unity_asset_bundle.directory_info[1].size = 70000;
+
+        assert_eq!(
+            Err(vec![
+                ValidationIssue::UnknownContainerCompression { flags: 0x7F },
+                ValidationIssue::UnknownBlockCompression { index: 0, flags: 9 },
+                ValidationIssue::NodeSizesExceedData {
+                    total_node_size: 74512,
+                    total_uncompressed_size: 70048,
+                },
+                ValidationIssue::NodeOutOfBounds {
+                    index: 1,
+                    offset: 4512,
+                    size: 70000,
+                },
+            ]),
+            unity_asset_bundle.validate()
+        );
+    }
+
+    #[test]
+    fn test_validate_overflowing_node_sizes() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        unity_asset_bundle.directory_info[0].size = i64::MAX;
+
+        let issues = unity_asset_bundle.validate().unwrap_err();
+        assert_eq!(ValidationIssue::NodeSizesOverflow, issues[0]);
+        assert!(matches!(
+            issues[1],
+            ValidationIssue::NodeOutOfBounds { index: 0, .. }
+        ));
+    }
+}
@@ -0,0 +1,302 @@
+use std::io::Write;
+
+use crate::asset_bundle::{UnityAssetBundle, DEFAULT_ALIGNMENT, KNOWN_CONTAINER_FLAGS};
+use crate::compression::{compress, CompressionType};
+use crate::error::UnityPackError;
+use crate::usize_from_i64;
+
+// Unity splits chunk-based (LZ4) bundles into 128KiB blocks
+const BLOCK_SIZE: usize = 0x20000;
+
+// m_UncompressedDataHash, the Hash128 in front of the block table. Unity's build
+// pipeline leaves it zeroed and its loaders never check it, so a repacked bundle
+// writes zeros exactly like a freshly built one
+const UNCOMPRESSED_DATA_HASH: [u8; 16] = [0; 16];
+
+impl UnityAssetBundle {
+    pub fn repack<W: Write>(
+        &self,
+        mut writer: W,
+        compression: CompressionType,
+    ) -> Result<(), UnityPackError> {
+        let data = self.node_data()?;
+        let chunks: Vec<&[u8]> = match compression {
+            // LZMA bundles are a single stream
+            CompressionType::Lzma => vec![&data[..]],
+            _ => data.chunks(BLOCK_SIZE).collect(),
+        };
+
+        let mut blocks = vec![];
+        let mut block_info = UNCOMPRESSED_DATA_HASH.to_vec();
+        block_info.extend_from_slice(&(chunks.len() as i32).to_be_bytes());
+        for chunk in chunks {
+            let compressed = compress(chunk, compression)?;
+            block_info.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            block_info.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+            block_info.extend_from_slice(&(compression.to_flags() as u16).to_be_bytes());
+            blocks.push(compressed);
+        }
+        block_info.extend_from_slice(&(self.directory_info.len() as i32).to_be_bytes());
+        for node in self.directory_info.iter() {
+            block_info.extend_from_slice(&node.offset.to_be_bytes());
+            block_info.extend_from_slice(&node.size.to_be_bytes());
+            block_info.extend_from_slice(&node.flags.to_be_bytes());
+            block_info.extend_from_slice(node.path.as_bytes());
+            block_info.push(0);
+        }
+        let compressed_block_info = compress(&block_info, compression)?;
+
+        let mut bundle = vec![];
+        bundle.extend_from_slice(self.header.signature.as_bytes());
+        bundle.push(0);
+        bundle.extend_from_slice(&self.header.version.to_be_bytes());
+        bundle.extend_from_slice(self.header.raw_unity_version.as_bytes());
+        bundle.push(0);
+        bundle.extend_from_slice(self.header.raw_unity_revision.as_bytes());
+        bundle.push(0);
+        let container_header_start = bundle.len();
+        // keep unknown bits, but always write the blocks info right after the header with
+        // no padding in front of the blocks
+        let flags =
+            (self.container_header.flags & !KNOWN_CONTAINER_FLAGS) | 0x40 | compression.to_flags();
+        bundle.extend_from_slice(&0i64.to_be_bytes());
+        bundle.extend_from_slice(&(compressed_block_info.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(&(block_info.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(&flags.to_be_bytes());
+        if self.header.version >= 7 {
+            bundle.resize(
+                bundle.len().div_ceil(DEFAULT_ALIGNMENT) * DEFAULT_ALIGNMENT,
+                0,
+            );
+        }
+        bundle.extend_from_slice(&compressed_block_info);
+        for block in blocks {
+            bundle.extend_from_slice(&block);
+        }
+        if let Some(custom_data) = self.custom_data() {
+            bundle.extend_from_slice(custom_data);
+        }
+        let size = bundle.len() as i64;
+        bundle[container_header_start..container_header_start + 8]
+            .copy_from_slice(&size.to_be_bytes());
+
+        writer.write_all(&bundle).map_err(UnityPackError::Io)
+    }
+
+    // lays the stream files back out at their node offsets
+    pub(crate) fn node_data(&self) -> Result<Vec<u8>, UnityPackError> {
+        if self.stream_files.len() != self.directory_info.len() {
+            return Err(UnityPackError::StreamFileCountMismatch {
+                nodes: self.directory_info.len(),
+                stream_files: self.stream_files.len(),
+            });
+        }
+        let mut size = 0;
+        for (node, stream_file) in self.directory_info.iter().zip(self.stream_files.iter()) {
+            // a body edited in place must still fit the node it is written back to
+            if usize_from_i64(node.size)? != stream_file.body.len() {
+                return Err(UnityPackError::StreamFileSizeMismatch {
+                    path: node.path.clone(),
+                    expected: node.size,
+                    actual: stream_file.body.len(),
+                });
+            }
+            let end = node
+                .offset
+                .checked_add(node.size)
+                .ok_or(UnityPackError::OffsetOverflow(node.offset))?;
+            size = size.max(usize_from_i64(end)?);
+        }
+        let mut data = vec![0u8; size];
+        for (node, stream_file) in self.directory_info.iter().zip(self.stream_files.iter()) {
+            let start = usize_from_i64(node.offset)?;
+            data[start..start + stream_file.body.len()].copy_from_slice(&stream_file.body);
+        }
+        Ok(data)
+    }
+}
+
+// the sample bundles are LZ4HC compressed
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use crate::asset_bundle::read_unity_asset_bundle;
+    use crate::compression::{CompressionType, Decompressor, DefaultDecompressor};
+    use crate::error::UnityPackError;
+    use crate::test_fixtures::read_file;
+    use crate::writer::UNCOMPRESSED_DATA_HASH;
+
+    #[test]
+    fn test_repack() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut compressions = vec![
+            CompressionType::None,
+            CompressionType::Lz4,
+            CompressionType::Lz4Hc,
+        ];
+        if cfg!(feature = "lzma") {
+            compressions.push(CompressionType::Lzma);
+        }
+        for compression in compressions {
+            let mut repacked = vec![];
+            unity_asset_bundle
+                .repack(&mut repacked, compression)
+                .unwrap();
+            let (rest, repacked_bundle) = read_unity_asset_bundle(&repacked).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(unity_asset_bundle.header, repacked_bundle.header);
+            assert_eq!(repacked.len() as i64, repacked_bundle.container_header.size);
+            assert_eq!(
+                compression.to_flags() | 0x40,
+                repacked_bundle.container_header.flags
+            );
+            assert_eq!(
+                unity_asset_bundle.directory_info,
+                repacked_bundle.directory_info
+            );
+            assert_eq!(
+                unity_asset_bundle.stream_files,
+                repacked_bundle.stream_files
+            );
+            assert!(repacked_bundle
+                .storage_blocks
+                .iter()
+                .all(|sb| sb.flags as u32 == compression.to_flags()));
+        }
+    }
+
+    #[test]
+    fn test_repack_keeps_custom_data() {
+        let file = read_file("./item_icon_00000_custom_data");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        let (rest, repacked_bundle) = read_unity_asset_bundle(&repacked).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(repacked.len() as i64, repacked_bundle.container_header.size);
+        assert_eq!(
+            unity_asset_bundle.custom_data(),
+            repacked_bundle.custom_data()
+        );
+    }
+
+    #[test]
+    fn test_repack_keeps_header_bytes() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let header_len = unity_asset_bundle.blocks_info_range().start - 20;
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        assert_eq!(file[..header_len], repacked[..header_len]);
+
+        // the strings go back as stored, not trimmed
+        unity_asset_bundle.header.raw_unity_revision.push_str("\r ");
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        let repacked_bundle = read_unity_asset_bundle(&repacked).unwrap().1;
+        assert_eq!(unity_asset_bundle.header, repacked_bundle.header);
+        assert_eq!(
+            unity_asset_bundle.stream_files,
+            repacked_bundle.stream_files
+        );
+    }
+
+    #[test]
+    fn test_repack_drops_layout_flags() {
+        let file = read_file("./item_icon_00000_padded");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        unity_asset_bundle.container_header.flags |= 0x180;
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        let repacked_bundle = read_unity_asset_bundle(&repacked).unwrap().1;
+        assert_eq!(0x40, repacked_bundle.container_header.flags);
+        assert_eq!(
+            unity_asset_bundle.stream_files,
+            repacked_bundle.stream_files
+        );
+    }
+
+    #[test]
+    fn test_repack_aligns_version_7() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        unity_asset_bundle.header.version = 7;
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        let repacked_bundle = read_unity_asset_bundle(&repacked).unwrap().1;
+        assert_eq!(64, repacked_bundle.blocks_info_range().start);
+        assert_eq!(
+            unity_asset_bundle.stream_files,
+            repacked_bundle.stream_files
+        );
+    }
+
+    #[test]
+    fn test_repack_rejects_mismatched_stream_files() {
+        let file = read_file("./item_icon_00000");
+        let mut unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        unity_asset_bundle.stream_files[1].body.push(0);
+        assert!(matches!(
+            unity_asset_bundle.repack(vec![], CompressionType::None),
+            Err(UnityPackError::StreamFileSizeMismatch {
+                expected: 65536,
+                actual: 65537,
+                ..
+            })
+        ));
+
+        unity_asset_bundle.stream_files.pop();
+        assert!(matches!(
+            unity_asset_bundle.repack(vec![], CompressionType::None),
+            Err(UnityPackError::StreamFileCountMismatch {
+                nodes: 2,
+                stream_files: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_repack_lzham_is_unsupported() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        assert!(matches!(
+            unity_asset_bundle.repack(vec![], CompressionType::Lzham),
+            Err(UnityPackError::UnsupportedCompression(4))
+        ));
+    }
+
+    #[test]
+    fn test_repack_writes_data_hash_like_unity() {
+        let file = read_file("./item_icon_00000");
+        let unity_asset_bundle = read_unity_asset_bundle(&file).unwrap().1;
+        let mut repacked = vec![];
+        unity_asset_bundle
+            .repack(&mut repacked, CompressionType::None)
+            .unwrap();
+        let repacked_bundle = read_unity_asset_bundle(&repacked).unwrap().1;
+
+        let original_hash = DefaultDecompressor
+            .decompress(
+                &file[unity_asset_bundle.blocks_info_range()],
+                unity_asset_bundle
+                    .container_header
+                    .uncompressed_blocks_info_size as usize,
+                CompressionType::Lz4Hc,
+            )
+            .unwrap()[..16]
+            .to_vec();
+        let repacked_hash = &repacked[repacked_bundle.blocks_info_range()][..16];
+        assert_eq!(original_hash, repacked_hash);
+        assert_eq!(&UNCOMPRESSED_DATA_HASH, repacked_hash);
+    }
+}